@@ -1,6 +1,7 @@
 from io import BufferedRWPair
 from pydantic import BaseModel as Schema
 from wing_rpc.enums import Enum
+from wing_rpc.flatten import FlatSchema
 from typing import Protocol
 
 
@@ -23,4 +24,4 @@ class RW(Writer, Reader):
 
 type Stream = RW | BufferedRWPair
 
-__all__ = ["Schema", "Enum", "Writer", "Reader", "RW", "Stream"]
+__all__ = ["Schema", "Enum", "FlatSchema", "Writer", "Reader", "RW", "Stream"]
@@ -1,4 +1,4 @@
-from typing import Any
+from typing import Any, ClassVar
 from pydantic import model_validator, model_serializer
 from wing_rpc import Schema
 
@@ -6,10 +6,15 @@ from wing_rpc import Schema
 class Enum(Schema):
     tag: Any
     value: Any
+    # Tags of the variants without data, which are sent as bare strings.
+    __units__: ClassVar[tuple] = ()
 
     @model_validator(mode="before")
     @classmethod
     def deserialize_external(cls, data: Any) -> Any:
+        if isinstance(data, str):
+            assert data in cls.__units__
+            return {"tag": data}
         assert isinstance(data, dict)
         assert len(data) == 1
         tag = tuple(data.keys())[0]
@@ -17,4 +22,6 @@ class Enum(Schema):
 
     @model_serializer(mode="plain")
     def serialize_external(self) -> Any:
+        if self.tag in self.__units__:
+            return self.tag
         return {self.tag: self.value}
@@ -0,0 +1,29 @@
+from typing import Any, ClassVar
+from pydantic import model_validator, model_serializer
+from wing_rpc import Schema
+
+
+class FlatSchema(Schema):
+    """A struct whose flattened fields are sent as part of the struct itself."""
+
+    # The keys each flattened field takes from the message.
+    __wing_flatten__: ClassVar[dict] = {}
+
+    @model_validator(mode="before")
+    @classmethod
+    def deserialize_flattened(cls, data: Any) -> Any:
+        if not isinstance(data, dict):
+            return data
+        data = dict(data)
+        for name, keys in cls.__wing_flatten__.items():
+            # Fields given by name when creating the message are left as they are.
+            if name not in data or any(key in data for key in keys):
+                data[name] = {key: data.pop(key) for key in keys if key in data}
+        return data
+
+    @model_serializer(mode="wrap")
+    def serialize_flattened(self, handler) -> Any:
+        data = handler(self)
+        for name in self.__wing_flatten__:
+            data.update(data.pop(name))
+        return data
@@ -1,7 +1,28 @@
+from collections import deque
+from collections.abc import Callable, Iterator
+from dataclasses import dataclass
 from io import BufferedRWPair
+from typing import Any
 from wing_rpc import Schema, Stream
-from wing_rpc.protocol import MismatchingMessageException, wrap, unwrap, WireHeader
+from wing_rpc.protocol import (
+    CALL_ERROR,
+    CONTROL_FLAG,
+    CREDIT,
+    STREAM_CANCEL,
+    STREAM_END,
+    Data,
+    MismatchingMessageException,
+    _name,
+    wrap,
+    unwrap,
+    WireHeader,
+)
+import hashlib
+import hmac
 import json
+import secrets
+import select
+import socket
 
 
 class ClientDisconnectedError(Exception):
@@ -12,15 +33,167 @@ class NoMessages(Exception):
     pass
 
 
+class AuthFailedError(Exception):
+    pass
+
+
+class CancelledError(Exception):
+    """The other peer is no longer interested in the stream."""
+
+
+class RemoteError(Exception):
+    """The other peer failed to answer a call."""
+
+
+@dataclass
+class Frame:
+    header: WireHeader
+    message: Data
+
+    def is_control(self) -> bool:
+        return bool(self.header.flags & CONTROL_FLAG)
+
+    def is_plain(self) -> bool:
+        """Returns `True` for messages that are not part of a call."""
+        return not self.is_control() and all(
+            self.message.get(key) is None for key in ("call", "reply", "stream")
+        )
+
+    def answers(self, id: int) -> bool:
+        """Returns `True` if this frame answers our call `id`."""
+        ref = self.message.get("stream", self.message.get("reply"))
+        if self.is_control():
+            return self.message.get("type") in (STREAM_END, CALL_ERROR) and ref == id
+        return ref == id
+
+
+class Call:
+    """A call made by the other peer, waiting for an answer."""
+
+    def __init__(self, id: int, message: Data):
+        self.id = id
+        self.message = message
+
+    @property
+    def name(self) -> str:
+        return self.message["type"]
+
+    def decode[T: Schema](self, cls: type[T]) -> T:
+        return unwrap(cls, self.message)
+
+
+class CallStream[T: Schema]:
+    """The items of a streamed reply, as returned by `Peer.call_stream`."""
+
+    def __init__(self, peer: "Peer", id: int, cls: type[T]):
+        self.peer = peer
+        self.id = id
+        self.cls = cls
+        self.done = False
+
+    def __iter__(self) -> Iterator[T]:
+        return self
+
+    def __next__(self) -> T:
+        if self.done:
+            raise StopIteration
+        frame = self.peer._next_frame_serving(lambda f: f.answers(self.id))
+        if frame.is_control():
+            self.done = True
+            if frame.message["type"] == CALL_ERROR:
+                raise RemoteError(frame.message["data"])
+            raise StopIteration
+        return unwrap(self.cls, frame.message)
+
+    def cancel(self):
+        """Tells the other peer to stop sending items.
+
+        Items that were already sent are silently discarded when they arrive.
+        """
+        if self.done:
+            return
+        self.done = True
+        self.peer._frames = deque(f for f in self.peer._frames if not f.answers(self.id))
+        self.peer._cancelled.add(self.id)
+        self.peer._send_control(STREAM_CANCEL, None, stream=self.id)
+
+
+class ReplyStream[T: Schema]:
+    """Sends the items of a streamed reply, as returned by `Peer.reply_stream`."""
+
+    def __init__(self, peer: "Peer", id: int):
+        self.peer = peer
+        self.id = id
+        self.finished = False
+
+    def is_cancelled(self) -> bool:
+        """Returns `True` if the caller isn't interested in more items."""
+        self.peer._poll_incoming()
+        return self.id in self.peer._cancelled_by_peer
+
+    def send(self, item: T):
+        """Sends the next item, raising `CancelledError` once the caller cancels the stream."""
+        if self.is_cancelled():
+            raise CancelledError()
+        self.peer._send_frame(wrap(item) | {"stream": self.id})
+
+    def finish(self):
+        """Tells the caller there are no more items."""
+        if self.finished:
+            return
+        self.finished = True
+        self.peer._cancelled_by_peer.discard(self.id)
+        self.peer._send_control(STREAM_END, None, stream=self.id)
+
+    def __enter__(self) -> "ReplyStream[T]":
+        return self
+
+    def __exit__(self, *_):
+        self.finish()
+
+
 class Peer:
-    def __init__(self, file: Stream):
+    def __init__(self, file: Stream, sock: socket.socket | None = None):
+        """Creates a peer that talks through `file`.
+
+        If `file` wraps `sock`, passing it lets the peer check for incoming
+        frames without blocking, which is needed to notice cancelled streams.
+        """
         self.file = file
+        self.sock = sock
+        self._frames: deque[Frame] = deque()
+        self._next_call = 0
+        self._cancelled: set[int] = set()
+        self._cancelled_by_peer: set[int] = set()
+        self._handlers: dict[str, Callable[[Call], None]] = {}
+        self._credit: int | None = None
 
     def send(self, obj: Schema):
-        message = wrap(obj)
-        message = json.dumps(message).encode()
-        header = WireHeader.from_message(message)
-        self.file.write(header.encode() + message)
+        self._send_frame(wrap(obj))
+
+    @property
+    def credit(self) -> int | None:
+        """How many more messages the other peer is willing to take, if it uses `grant`."""
+        return self._credit
+
+    def grant(self, credit: int):
+        """Allows the other peer to send `credit` more messages.
+
+        Peers can send as much as they like until they are first granted credit.
+        From then on, the receiver is expected to grant more as it handles messages.
+        """
+        self._send_control(CREDIT, credit)
+
+    def _send_frame(self, message: Data, flags: int = 0):
+        if not flags & CONTROL_FLAG:
+            # Wait for the other peer to make room.
+            while self._credit == 0:
+                self._read_frame()
+            if self._credit is not None:
+                self._credit -= 1
+        encoded = json.dumps(message).encode()
+        header = WireHeader.from_message(encoded, flags)
+        self.file.write(header.encode() + encoded)
         self.file.flush()
 
     def _read(self, count: int):
@@ -31,12 +204,237 @@ class Peer:
             raise ClientDisconnectedError()
         return b
 
-    def receive[T: Schema](self, cls: type[T]) -> T:
+    def _receive_frame(self) -> tuple[WireHeader, Data]:
         header = self._read(WireHeader.byte_count())
         header = WireHeader.from_encoded(header)
         encoded_msg = self._read(header.len)
-        data = json.loads(encoded_msg)
-        return unwrap(cls, data)
+        return header, json.loads(encoded_msg)
+
+    def _admit(self, frame: Frame) -> bool:
+        """Handles control frames that concern calls as soon as they are read.
+
+        Returns `False` if the frame has been fully dealt with and must not be queued.
+        """
+        message = frame.message
+        if frame.is_control():
+            if message["type"] == CREDIT:
+                self._credit = (self._credit or 0) + message["data"]
+                return False
+            if message["type"] == STREAM_CANCEL:
+                self._cancelled_by_peer.add(message["stream"])
+                return False
+            if message["type"] in (STREAM_END, CALL_ERROR):
+                ref = message.get("stream", message.get("reply"))
+                if ref in self._cancelled:
+                    self._cancelled.discard(ref)
+                    return False
+            return True
+        # Items of streams we cancelled are of no interest anymore.
+        return message.get("stream") not in self._cancelled
+
+    def _read_frame(self):
+        header, message = self._receive_frame()
+        frame = Frame(header, message)
+        if self._admit(frame):
+            self._frames.append(frame)
+
+    def _next_frame(self, pick: Callable[[Frame], bool]) -> Frame:
+        """Returns the first frame accepted by `pick`, reading more frames if needed.
+
+        Frames that are not picked are kept in order for later calls.
+        """
+        while True:
+            for frame in self._frames:
+                if pick(frame):
+                    self._frames.remove(frame)
+                    return frame
+            self._read_frame()
+
+    def _handled(self, frame: Frame) -> bool:
+        """Returns `True` if `frame` is a call that has a handler."""
+        message = frame.message
+        return (
+            not frame.is_control()
+            and message.get("call") is not None
+            and message["type"] in self._handlers
+        )
+
+    def _next_frame_serving(self, pick: Callable[[Frame], bool]) -> Frame:
+        """Same as `_next_frame`, but answers calls that have a handler while waiting."""
+        while True:
+            frame = self._next_frame(lambda f: self._handled(f) or pick(f))
+            if not self._handled(frame):
+                return frame
+            call = Call(frame.message["call"], frame.message)
+            handler = self._handlers[call.name]
+            try:
+                handler(call)
+            except (OSError, ClientDisconnectedError):
+                raise
+            except Exception:
+                # It was already reported to the caller, and is none of our business.
+                pass
+
+    def handle[Req: Schema](self, cls: type[Req], handler: Callable[[Req], Schema]):
+        """Answers every call of `cls` with `handler`, see `answer`.
+
+        Handlers run whenever this peer waits for something else, such as a message,
+        a call without a handler or the reply to one of its own calls.
+        """
+        self._handlers[_name(cls)] = lambda call: self.answer(call, cls, handler)
+
+    def handle_stream[Req: Schema](
+        self, cls: type[Req], handler: Callable[[Req, ReplyStream[Any]], None]
+    ):
+        """Answers every call of `cls` with the items `handler` sends, see `answer_stream`."""
+        self._handlers[_name(cls)] = lambda call: self.answer_stream(call, cls, handler)
+
+    def remove_handler(self, cls: type[Schema]) -> bool:
+        """Stops answering calls of `cls` automatically."""
+        return self._handlers.pop(_name(cls), None) is not None
+
+    def _readable(self) -> bool:
+        if self.sock is None:
+            return False
+        if select.select([self.sock], [], [], 0)[0]:
+            return True
+        # The file may have buffered a frame the socket no longer knows about.
+        peek = getattr(self.file, "peek", None)
+        if peek is None:
+            return False
+        blocking = self.sock.getblocking()
+        self.sock.setblocking(False)
+        try:
+            return len(peek(1) or b"") > 0
+        except BlockingIOError:
+            return False
+        finally:
+            self.sock.setblocking(blocking)
+
+    def _poll_incoming(self):
+        """Reads every frame that is available without blocking."""
+        while self._readable():
+            self._read_frame()
+
+    def receive[T: Schema](self, cls: type[T]) -> T:
+        frame = self._next_frame_serving(Frame.is_plain)
+        return unwrap(cls, frame.message)
+
+    def call[T: Schema](self, request: Schema, cls: type[T]) -> T:
+        """Sends `request` and waits for the other peer to `reply` to it.
+
+        Calls of the other peer that have a handler are answered meanwhile, see `handle`.
+        """
+        self._next_call += 1
+        id = self._next_call
+        self._send_frame(wrap(request) | {"call": id})
+        frame = self._next_frame_serving(lambda f: f.answers(id))
+        if frame.is_control():
+            raise RemoteError(frame.message["data"])
+        return unwrap(cls, frame.message)
+
+    def call_stream[T: Schema](self, request: Schema, cls: type[T]) -> CallStream[T]:
+        """Sends `request` and returns an iterator over the items the other peer streams back."""
+        self._next_call += 1
+        self._send_frame(wrap(request) | {"call": self._next_call})
+        return CallStream(self, self._next_call, cls)
+
+    def next_call(self) -> Call:
+        """Waits for the other peer to make a call."""
+        frame = self._next_frame_serving(
+            lambda f: not f.is_control() and f.message.get("call") is not None
+        )
+        return Call(frame.message["call"], frame.message)
+
+    def reply(self, call: Call, obj: Schema):
+        """Answers `call` with a single reply."""
+        self._send_frame(wrap(obj) | {"reply": call.id})
+
+    def reply_error(self, call: Call, message: str):
+        """Answers `call` with an error, which the caller gets as a `RemoteError`."""
+        self._send_control(CALL_ERROR, message, reply=call.id)
+
+    def reply_stream[T: Schema](self, call: Call) -> ReplyStream[T]:
+        """Answers `call` with a stream of items."""
+        return ReplyStream(self, call.id)
+
+    def answer[Req: Schema](
+        self, call: Call, cls: type[Req], handler: Callable[[Req], Schema]
+    ):
+        """Answers `call` with whatever `handler` returns for its request.
+
+        If `handler` raises, the caller gets a `RemoteError` and the exception is re-raised.
+        """
+        try:
+            reply = handler(call.decode(cls))
+        except Exception as e:
+            self.reply_error(call, str(e))
+            raise
+        self.reply(call, reply)
+
+    def answer_stream[Req: Schema](
+        self,
+        call: Call,
+        cls: type[Req],
+        handler: Callable[[Req, ReplyStream[Any]], None],
+    ):
+        """Answers `call` with the items `handler` sends for its request.
+
+        The stream ends once `handler` returns. A `CancelledError` is not treated as a failure.
+        """
+        try:
+            request = call.decode(cls)
+        except Exception as e:
+            self.reply_error(call, str(e))
+            raise
+        stream = self.reply_stream(call)
+        try:
+            handler(request, stream)
+        except CancelledError:
+            pass
+        except Exception as e:
+            # The error replaces the end of the stream.
+            stream.finished = True
+            self.reply_error(call, str(e))
+            raise
+        stream.finish()
+
+    def _send_control(self, type: str, data: Any, **ids: int):
+        self._send_frame({"type": type, "data": data} | ids, CONTROL_FLAG)
+
+    def _receive_control(self, type: str) -> Any:
+        frame = self._next_frame(lambda _: True)
+        if not frame.is_control() or frame.message.get("type") != type:
+            raise AuthFailedError()
+        return frame.message["data"]
+
+    def require_auth(self, secret: bytes):
+        """Challenges the other peer to prove it knows `secret`.
+
+        This must be called by the accepting side, before any other message is exchanged.
+        Raises `AuthFailedError` if the other peer answers incorrectly.
+        """
+        nonce = secrets.token_bytes(32)
+        self._send_control("AuthChallenge", {"nonce": nonce.hex()})
+        response = self._receive_control("AuthResponse")
+        expected = hmac.new(secret, nonce, hashlib.sha256).hexdigest()
+        ok = hmac.compare_digest(expected, str(response["mac"]))
+        self._send_control("AuthResult", {"ok": ok})
+        if not ok:
+            raise AuthFailedError()
+
+    def authenticate(self, secret: bytes):
+        """Answers the challenge sent by `require_auth` on the other side, proving we know `secret`.
+
+        This must be called by the connecting side, before any other message is exchanged.
+        Raises `AuthFailedError` if the other peer rejects us.
+        """
+        challenge = self._receive_control("AuthChallenge")
+        nonce = bytes.fromhex(challenge["nonce"])
+        mac = hmac.new(secret, nonce, hashlib.sha256).hexdigest()
+        self._send_control("AuthResponse", {"mac": mac})
+        if not self._receive_control("AuthResult")["ok"]:
+            raise AuthFailedError()
 
     def try_receive[T: Schema](self, cls: type[T]) -> T | None:
         try:
@@ -9,11 +9,28 @@ def _name(obj: Schema | type[Schema]) -> str:
         cls = obj.__class__
     else:
         cls = obj
-    return cls.__name__
+    # Instantiations of generic types are named after their arguments, such as `Page<User>`.
+    generic = getattr(cls, "__pydantic_generic_metadata__", None)
+    if generic and generic["origin"] is not None:
+        return generic["origin"].__wing_instances__.get(generic["args"], cls.__name__)
+    # Types of a package may be sent with a qualified name, such as `foo.bar.Point`.
+    return getattr(cls, "__wing_name__", cls.__name__)
 
 
 type Data = dict[str, Any]
 
+CONTROL_FLAG = 0b0000_0001
+"""The frame is used by the runtime itself and is never handed to the user."""
+
+STREAM_END = "StreamEnd"
+"""Sent by the callee after the last item of a stream."""
+STREAM_CANCEL = "StreamCancel"
+"""Sent by the caller when it isn't interested in the rest of a stream."""
+CALL_ERROR = "CallError"
+"""Sent by the callee instead of a reply when it couldn't answer a call."""
+CREDIT = "Credit"
+"""Sent by the receiver to let the sender know how many more messages it is willing to take."""
+
 
 class MismatchingMessageException(Exception):
     def __init__(self, got: str, expected: type) -> None:
@@ -42,7 +59,7 @@ class WireHeader:
     len: int
     flags: int
 
-    wire: ClassVar = Struct("<cH")
+    wire: ClassVar = Struct("<BH")
 
     @classmethod
     def from_encoded(cls, msg: bytes) -> Self:
@@ -50,8 +67,7 @@ class WireHeader:
         return cls(flags=flags, len=len)
 
     def encode(self) -> bytes:
-        flags = bytes([self.flags])
-        return self.wire.pack(flags, self.len)
+        return self.wire.pack(self.flags, self.len)
 
     @classmethod
     def from_message(cls, msg: bytes, flags: int = 0) -> Self:
@@ -14,27 +14,34 @@ class TcpSocket:
             self.socket.setsockopt(socket.SOL_SOCKET, socket.SO_REUSEADDR, 1)
             self.socket.bind((host, port))
 
-    def connect(self, *, blocking=True) -> Peer:
+    def connect(self, *, blocking=True, secret: bytes | None = None) -> Peer:
         """Connects to a peer in the configured location.
 
         Server:
             Blocks the thread waiting for a client to connect.
         Client:
             Initiates a connection to the server.
+
+        If `secret` is set, the server requires the client to prove it knows it
+        before any message is exchanged, raising `AuthFailedError` otherwise.
         """
         print("Connecting to", self.host, self.port)
         if self.is_server:
             conn, _ = self.socket.accept()
+            peer = Peer(conn.makefile("rbw"), conn)
+            if secret is not None:
+                peer.require_auth(secret)
             if not blocking:
                 conn.setblocking(0)
-            stream = conn.makefile("rbw")
         else:
             self.socket.connect((self.host, self.port))
+            peer = Peer(self.socket.makefile("rbw"), self.socket)
+            if secret is not None:
+                peer.authenticate(secret)
             if not blocking:
                 self.socket.setblocking(0)
-            stream = self.socket.makefile("rbw")
 
-        return Peer(stream)
+        return peer
 
     def close(self):
         """Closes the underlying socket."""
@@ -0,0 +1,109 @@
+//! Compares the latency and throughput of every transport a [`Peer`] can use on the same host.
+//!
+//! Run with `cargo bench -p wing-rpc --bench transports`.
+use std::{
+    net::{TcpListener, TcpStream},
+    os::unix::net::UnixStream,
+    thread,
+};
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use serde::{Deserialize, Serialize};
+use wing_rpc::{Message, Peer, PeerBuilder};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Reading {
+    sensor: u32,
+    timestamp: u64,
+    values: [f64; 3],
+}
+
+impl Message<'_> for Reading {
+    const NAME: &'static str = "Reading";
+}
+
+const READING: Reading = Reading {
+    sensor: 7,
+    timestamp: 1_700_000_000,
+    values: [0.25, -1.5, 9.81],
+};
+
+/// Sends every message back until the other peer goes away.
+fn echo(mut peer: Peer) {
+    thread::spawn(move || {
+        while let Ok(reading) = peer.recv::<Reading>() {
+            if peer.send(reading).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+fn tcp() -> Peer {
+    let listener = TcpListener::bind("localhost:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let builder = PeerBuilder::new().nodelay(true);
+    let client = builder.socket(TcpStream::connect(("localhost", port)).unwrap());
+    echo(builder.socket(listener.accept().unwrap().0).unwrap());
+    client.unwrap()
+}
+
+fn unix() -> Peer {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    echo(Peer::from_unix_socket(theirs));
+    Peer::from_unix_socket(ours)
+}
+
+#[cfg(target_os = "linux")]
+fn shm() -> Peer {
+    use wing_rpc::shm::SharedMemory;
+
+    let (ours, theirs) = SharedMemory::pair(1 << 20).unwrap();
+    echo(PeerBuilder::new().shared_memory(theirs).unwrap());
+    PeerBuilder::new().shared_memory(ours).unwrap()
+}
+
+type Connect = fn() -> Peer;
+
+fn transports() -> Vec<(&'static str, Connect)> {
+    let mut transports: Vec<(&'static str, Connect)> = vec![("tcp", tcp), ("unix", unix)];
+    #[cfg(target_os = "linux")]
+    transports.push(("shm", shm));
+    transports
+}
+
+fn round_trip(c: &mut Criterion) {
+    let mut group = c.benchmark_group("round_trip");
+    for (name, connect) in transports() {
+        let mut peer = connect();
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                peer.send(READING).unwrap();
+                peer.recv::<Reading>().unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+fn batch(c: &mut Criterion) {
+    const BATCH: usize = 1000;
+    let mut group = c.benchmark_group("batch");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    for (name, connect) in transports() {
+        let mut peer = connect();
+        group.bench_function(BenchmarkId::new(name, BATCH), |b| {
+            b.iter(|| {
+                peer.send_batch(std::iter::repeat_n(READING, BATCH))
+                    .unwrap();
+                for _ in 0..BATCH {
+                    peer.recv::<Reading>().unwrap();
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, round_trip, batch);
+criterion_main!(benches);
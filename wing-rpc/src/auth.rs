@@ -0,0 +1,153 @@
+//! Shared-secret authentication, performed right after a connection is established.
+//!
+//! The accepting side sends a random challenge, which the connecting side must answer with the
+//! HMAC-SHA256 of the challenge keyed with the shared [`Secret`]. The secret itself never travels
+//! over the wire. Every frame of the handshake is a control frame, so user messages are only
+//! exchanged after both sides agree.
+//!
+//! [`server::TcpServer::with_auth`](crate::server::TcpServer::with_auth) and
+//! [`client::tcp_with_auth`](crate::client::tcp_with_auth) do this for you. For other transports,
+//! use [`Peer::require_auth`] and [`Peer::authenticate`].
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::{Error, Message, Peer, Timeout, WingResult, WrappedData, flags};
+
+const NONCE_LEN: usize = 32;
+
+/// A secret shared by both peers, used to prove the connecting peer is allowed to talk to us.
+#[derive(Clone)]
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    pub fn new(secret: impl Into<Vec<u8>>) -> Self {
+        Self(secret.into())
+    }
+    fn mac(&self, nonce: &[u8]) -> Hmac<Sha256> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts keys of any size");
+        mac.update(nonce);
+        mac
+    }
+}
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(..)")
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthChallenge {
+    nonce: String,
+}
+
+impl Message<'_> for AuthChallenge {
+    const NAME: &'static str = "AuthChallenge";
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthResponse {
+    mac: String,
+}
+
+impl Message<'_> for AuthResponse {
+    const NAME: &'static str = "AuthResponse";
+}
+
+#[derive(Serialize, Deserialize)]
+struct AuthResult {
+    ok: bool,
+}
+
+impl Message<'_> for AuthResult {
+    const NAME: &'static str = "AuthResult";
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn from_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+impl Peer {
+    /// Challenges the other peer to prove it knows `secret`.
+    ///
+    /// This must be called by the accepting side, before any other message is exchanged.
+    /// Returns [`Error::AuthFailed`] if the other peer answers incorrectly, and
+    /// [`std::io::ErrorKind::TimedOut`] if it doesn't answer within
+    /// [`crate::PeerBuilder::recv_timeout`].
+    pub fn require_auth(&mut self, secret: &Secret) -> WingResult<()> {
+        let timeout = self.config.recv_timeout.clone();
+        self.require_auth_within(secret, timeout)
+    }
+
+    /// Same as [`Peer::require_auth`], but the other peer must answer within `timeout`.
+    pub(crate) fn require_auth_within(
+        &mut self,
+        secret: &Secret,
+        timeout: Timeout,
+    ) -> WingResult<()> {
+        let mut nonce = [0u8; NONCE_LEN];
+        getrandom::fill(&mut nonce).map_err(std::io::Error::from)?;
+        self.send_flagged(
+            flags::CONTROL,
+            AuthChallenge {
+                nonce: to_hex(&nonce),
+            },
+        )?;
+        let response: AuthResponse = self.recv_control(timeout)?;
+        let ok = from_hex(&response.mac)
+            .is_some_and(|mac| secret.mac(&nonce).verify_slice(&mac).is_ok());
+        self.send_flagged(flags::CONTROL, AuthResult { ok })?;
+        if ok { Ok(()) } else { Err(Error::AuthFailed) }
+    }
+
+    /// Answers the challenge sent by [`Peer::require_auth`] on the other side, proving we know `secret`.
+    ///
+    /// This must be called by the connecting side, before any other message is exchanged.
+    /// Returns [`Error::AuthFailed`] if the other peer rejects us.
+    pub fn authenticate(&mut self, secret: &Secret) -> WingResult<()> {
+        let timeout = self.config.recv_timeout.clone();
+        let challenge: AuthChallenge = self.recv_control(timeout.clone())?;
+        let nonce = from_hex(&challenge.nonce).ok_or(Error::AuthFailed)?;
+        let mac = secret.mac(&nonce).finalize().into_bytes();
+        self.send_flagged(flags::CONTROL, AuthResponse { mac: to_hex(&mac) })?;
+        let result: AuthResult = self.recv_control(timeout)?;
+        if result.ok {
+            Ok(())
+        } else {
+            Err(Error::AuthFailed)
+        }
+    }
+
+    /// Receives the next step of the handshake, anything else failing it.
+    fn recv_control<T: for<'a> Message<'a>>(&mut self, timeout: Timeout) -> WingResult<T> {
+        let frame = match self.next_frame(timeout, |_| true) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => return Err(crate::timed_out()),
+            // Such as a frame over the size limit.
+            Err(e) if e.kind() == std::io::ErrorKind::InvalidData => {
+                return Err(Error::AuthFailed);
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if !frame.is_control() || frame.header.typ != T::NAME {
+            return Err(Error::AuthFailed);
+        }
+        self.config
+            .codec
+            .decode::<WrappedData<T>>(&frame.data)
+            .map(|msg| msg.data)
+            .map_err(|_| Error::AuthFailed)
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    io::{self, BufReader, BufWriter, Read, Write},
+    net::TcpStream,
+};
+
+use crate::{Codec, Error, Peer, SharedStream, Timeout, WingResult, WirePacket};
+
+/// Settings shared by every part of a [`Peer`].
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Config {
+    pub max_frame_size: usize,
+    pub read_buffer: usize,
+    pub write_buffer: usize,
+    pub auto_flush: bool,
+    pub recv_timeout: Timeout,
+    pub send_timeout: Timeout,
+    pub codec: Codec,
+    pub nodelay: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_frame_size: u16::MAX as usize,
+            read_buffer: 8 * 1024,
+            write_buffer: 8 * 1024,
+            auto_flush: true,
+            recv_timeout: Timeout::Block,
+            send_timeout: Timeout::Block,
+            codec: Codec::Json,
+            nodelay: false,
+        }
+    }
+}
+
+impl Config {
+    /// Fails with [`io::ErrorKind::InvalidInput`] if a frame of `len` bytes can't be sent.
+    pub(crate) fn check_frame_size(&self, len: usize) -> WingResult<()> {
+        if len > self.max_frame_size {
+            return Err(Error::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Message of {len} bytes is over the limit of {}",
+                    self.max_frame_size
+                ),
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Creates [`Peer`]s with custom settings.
+///
+/// Every setting has a sensible default, which is what [`Peer::from_socket`] and friends use.
+/// ```no_run
+/// use std::{net::TcpStream, time::Duration};
+/// use wing_rpc::PeerBuilder;
+///
+/// let sock = TcpStream::connect("localhost:6000").unwrap();
+/// let peer = PeerBuilder::new()
+///     .max_frame_size(4 * 1024)
+///     .recv_timeout(Duration::from_secs(5))
+///     .nodelay(true)
+///     .socket(sock)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeerBuilder {
+    config: Config,
+}
+
+impl PeerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Sets the size of the largest frame that can be sent or received, up to [`u16::MAX`].
+    ///
+    /// Larger incoming frames are skipped without being read into memory, and reported as an
+    /// [`io::ErrorKind::InvalidData`] error.
+    pub fn max_frame_size(mut self, size: usize) -> Self {
+        self.config.max_frame_size = size.min(u16::MAX as usize);
+        self
+    }
+    /// Sets how many bytes are read from the connection at once.
+    pub fn read_buffer(mut self, size: usize) -> Self {
+        self.config.read_buffer = size.max(WirePacket::HEADER_LEN);
+        self
+    }
+    /// Sets how many bytes can be buffered before they are written to the connection.
+    pub fn write_buffer(mut self, size: usize) -> Self {
+        self.config.write_buffer = size;
+        self
+    }
+    /// Sets whether every message is written out as soon as it is sent.
+    ///
+    /// If disabled, messages are buffered until [`Peer::flush`] is called or the buffer is full,
+    /// which saves system calls when sending many messages at once.
+    pub fn auto_flush(mut self, auto_flush: bool) -> Self {
+        self.config.auto_flush = auto_flush;
+        self
+    }
+    /// Sets how long [`Peer::recv`], [`Peer::call`] and similar operations wait before failing
+    /// with [`io::ErrorKind::TimedOut`].
+    pub fn recv_timeout(mut self, timeout: impl Into<Timeout>) -> Self {
+        self.config.recv_timeout = timeout.into();
+        self
+    }
+    /// Sets how long sending or [`Peer::flush`] wait for a stalled peer to catch up before
+    /// failing with [`io::ErrorKind::TimedOut`].
+    ///
+    /// Only sockets and [`crate::shm`] are waited on with a timeout. A message that was already
+    /// queued when the time ran out stays queued, see [`Peer::set_send_queue`]. With shared
+    /// memory, the message may have been partly written, so the connection can't be used anymore.
+    pub fn send_timeout(mut self, timeout: impl Into<Timeout>) -> Self {
+        self.config.send_timeout = timeout.into();
+        self
+    }
+    /// Sets the format messages are encoded with.
+    pub fn codec(mut self, codec: Codec) -> Self {
+        self.config.codec = codec;
+        self
+    }
+    /// Sets `TCP_NODELAY` on TCP connections, so small messages are sent right away instead of
+    /// being coalesced.
+    pub fn nodelay(mut self, nodelay: bool) -> Self {
+        self.config.nodelay = nodelay;
+        self
+    }
+    #[cfg(any(
+        feature = "client",
+        all(feature = "server", any(feature = "tls", feature = "websocket"))
+    ))]
+    pub(crate) fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Creates a [`Peer`] which sends messages to `write` and receives from `read`. See [`Peer::new`].
+    pub fn build(
+        &self,
+        read: impl Read + Send + 'static,
+        write: impl Write + Send + 'static,
+    ) -> Peer {
+        Peer::with_config(
+            Box::new(read),
+            Box::new(BufWriter::with_capacity(self.config.write_buffer, write)),
+            self.config.clone(),
+        )
+    }
+    /// Creates a [`Peer`] from a TCP connection. See [`Peer::from_socket`].
+    pub fn socket(&self, con: TcpStream) -> io::Result<Peer> {
+        con.set_nodelay(self.config.nodelay)?;
+        #[cfg(unix)]
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&con);
+        let peer = self.build(con.try_clone()?, con);
+        #[cfg(unix)]
+        let peer = peer.with_fd(fd);
+        Ok(peer)
+    }
+    /// Creates a [`Peer`] from a Unix domain socket connection. See [`Peer::from_unix_socket`].
+    #[cfg(unix)]
+    pub fn unix_socket(&self, con: std::os::unix::net::UnixStream) -> io::Result<Peer> {
+        let fd = std::os::fd::AsRawFd::as_raw_fd(&con);
+        Ok(self.build(con.try_clone()?, con).with_fd(fd))
+    }
+    /// Creates a [`Peer`] from one end of a shared memory connection, see [`crate::shm`].
+    #[cfg(target_os = "linux")]
+    pub fn shared_memory(&self, shm: crate::shm::SharedMemory) -> io::Result<Peer> {
+        let (read, write) = shm.split(self.config.send_timeout.clone())?;
+        let readable = read.readable();
+        Ok(self.build(read, write).with_readable(readable))
+    }
+    /// Creates a [`Peer`] from a single stream that is used for both sending and receiving.
+    /// See [`Peer::from_stream`].
+    pub fn stream(&self, stream: impl Read + Write + Send + 'static) -> Peer {
+        let stream = SharedStream::new(stream);
+        let read = BufReader::with_capacity(self.config.read_buffer, stream.clone());
+        self.build(read, stream)
+    }
+}
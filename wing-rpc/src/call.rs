@@ -0,0 +1,450 @@
+//! Request/reply calls and streamed replies on top of plain messages.
+//!
+//! A call is a message that carries an id chosen by the caller. The callee answers it either with a
+//! single reply ([`Peer::call`]) or with many stream items followed by an end-of-stream frame
+//! ([`Peer::call_stream`]). Both kinds of answer carry the id of the call, so they can't be
+//! confused with plain messages or answers to other calls.
+use std::marker::PhantomData;
+
+use serde::Serialize;
+
+use crate::{Codec, Error, Message, Peer, Timeout, WingResult, flags, inbox::Frame, timed_out};
+
+/// Sent by the callee after the last item of a stream.
+pub(crate) const STREAM_END: &str = "StreamEnd";
+/// Sent by the caller when it isn't interested in the rest of a stream.
+pub(crate) const STREAM_CANCEL: &str = "StreamCancel";
+/// Sent by the callee instead of a reply when it couldn't answer a call.
+pub(crate) const CALL_ERROR: &str = "CallError";
+
+/// Handles control frames that concern calls as soon as they are read.
+///
+/// Returns `false` if `frame` has been fully dealt with and must not be queued.
+pub(crate) fn admit(peer: &mut Peer, frame: &Frame) -> bool {
+    let header = &frame.header;
+    if frame.is_control() {
+        match header.typ.as_str() {
+            STREAM_CANCEL => {
+                if let Some(id) = header.stream {
+                    peer.cancelled_by_peer.insert(id);
+                }
+                false
+            }
+            STREAM_END | CALL_ERROR => {
+                let id = header.stream.or(header.reply);
+                !id.is_some_and(|id| peer.cancelled.remove(&id))
+            }
+            _ => true,
+        }
+    } else {
+        // Items of streams we cancelled are of no interest anymore.
+        !header.stream.is_some_and(|id| peer.cancelled.contains(&id))
+    }
+}
+
+/// A call made by the other peer, waiting for an answer.
+///
+/// Answer it with [`Peer::reply`], [`Peer::reply_stream`] or [`Peer::reply_error`].
+#[derive(Debug)]
+pub struct Call {
+    id: u64,
+    frame: Frame,
+    codec: Codec,
+}
+
+impl Call {
+    pub(crate) fn new(frame: Frame, codec: Codec) -> Self {
+        Self {
+            id: frame.header.call.expect("Frame is a call"),
+            frame,
+            codec,
+        }
+    }
+    /// The [`Message::NAME`] of the request.
+    pub fn name(&self) -> &str {
+        &self.frame.header.typ
+    }
+    /// Deserializes the request.
+    pub fn decode<'a, T: Message<'a>>(&'a self) -> WingResult<T> {
+        let fds = self.frame.take_fds();
+        Ok(self
+            .codec
+            .decode_with::<crate::WrappedData<T>>(&self.frame.data, fds)?
+            .data)
+    }
+}
+
+/// The items of a streamed reply, as returned by [`Peer::call_stream`].
+///
+/// Iterating yields every item until the other peer ends the stream. Dropping the iterator before
+/// the stream ends cancels it, see [`CallStream::cancel`].
+pub struct CallStream<'p, T> {
+    peer: &'p mut Peer,
+    id: u64,
+    done: bool,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T> CallStream<'_, T> {
+    /// Tells the other peer to stop sending items.
+    ///
+    /// Items that were already sent are silently discarded when they arrive.
+    pub fn cancel(mut self) -> WingResult<()> {
+        self.cancel_mut()
+    }
+    fn cancel_mut(&mut self) -> WingResult<()> {
+        if self.done {
+            return Ok(());
+        }
+        self.done = true;
+        let id = self.id;
+        self.peer.frames.retain(|frame| !answers(frame, id));
+        self.peer.cancelled.insert(id);
+        self.peer
+            .send_envelope(flags::CONTROL, STREAM_CANCEL, Ids::stream(id), ())
+    }
+}
+
+impl<T: for<'a> Message<'a>> Iterator for CallStream<'_, T> {
+    type Item = WingResult<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let id = self.id;
+        let timeout = self.peer.config.recv_timeout.clone();
+        let frame = match self.peer.next_frame_serving(timeout, |f| answers(f, id)) {
+            Ok(Some(frame)) => frame,
+            Ok(None) => {
+                self.done = true;
+                return Some(Err(timed_out()));
+            }
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        if frame.is_control() {
+            self.done = true;
+            return match frame.header.typ.as_str() {
+                CALL_ERROR => Some(Err(remote_error(&frame, self.peer.config.codec))),
+                _ => None,
+            };
+        }
+        Some(decode(&frame, self.peer.config.codec))
+    }
+}
+
+impl<T> Drop for CallStream<'_, T> {
+    fn drop(&mut self) {
+        // There's no one to report this error to, the connection is probably dead anyway.
+        let _ = self.cancel_mut();
+    }
+}
+
+/// Sends the items of a streamed reply, as returned by [`Peer::reply_stream`].
+///
+/// Dropping it ends the stream, but [`ReplyStream::finish`] lets you handle errors.
+pub struct ReplyStream<'p, T> {
+    peer: &'p mut Peer,
+    id: u64,
+    finished: bool,
+    _item: PhantomData<fn(T)>,
+}
+
+impl<T: for<'a> Message<'a>> ReplyStream<'_, T> {
+    /// Sends the next item of the stream.
+    ///
+    /// Fails with [`Error::Cancelled`] once the caller cancels the stream, so that no more work is
+    /// wasted on it.
+    pub fn send(&mut self, item: T) -> WingResult<()> {
+        if self.is_cancelled()? {
+            return Err(Error::Cancelled);
+        }
+        self.peer
+            .send_envelope(0, T::NAME, Ids::stream(self.id), item)
+    }
+}
+
+impl<T> ReplyStream<'_, T> {
+    /// Returns `true` if the caller isn't interested in more items.
+    pub fn is_cancelled(&mut self) -> WingResult<bool> {
+        self.peer.poll_incoming()?;
+        Ok(self.peer.cancelled_by_peer.contains(&self.id))
+    }
+    /// Tells the caller there are no more items.
+    pub fn finish(mut self) -> WingResult<()> {
+        self.finish_mut()
+    }
+    fn finish_mut(&mut self) -> WingResult<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+        self.peer.cancelled_by_peer.remove(&self.id);
+        self.peer
+            .send_envelope(flags::CONTROL, STREAM_END, Ids::stream(self.id), ())
+    }
+}
+
+impl<T> Drop for ReplyStream<'_, T> {
+    fn drop(&mut self) {
+        let _ = self.finish_mut();
+    }
+}
+
+/// The call ids carried by an envelope.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Ids {
+    pub call: Option<u64>,
+    pub reply: Option<u64>,
+    pub stream: Option<u64>,
+}
+
+impl Ids {
+    pub fn call(id: u64) -> Self {
+        Self {
+            call: Some(id),
+            ..Default::default()
+        }
+    }
+    pub fn reply(id: u64) -> Self {
+        Self {
+            reply: Some(id),
+            ..Default::default()
+        }
+    }
+    pub fn stream(id: u64) -> Self {
+        Self {
+            stream: Some(id),
+            ..Default::default()
+        }
+    }
+}
+
+/// Returns `true` if `frame` answers our call `id`.
+fn answers(frame: &Frame, id: u64) -> bool {
+    let header = &frame.header;
+    if frame.is_control() {
+        matches!(header.typ.as_str(), STREAM_END | CALL_ERROR)
+            && header.stream.or(header.reply) == Some(id)
+    } else {
+        header.stream == Some(id) || header.reply == Some(id)
+    }
+}
+
+fn decode<T: for<'a> Message<'a>>(frame: &Frame, codec: Codec) -> WingResult<T> {
+    let fds = frame.take_fds();
+    Ok(codec
+        .decode_with::<crate::WrappedData<T>>(&frame.data, fds)?
+        .data)
+}
+
+fn remote_error(frame: &Frame, codec: Codec) -> Error {
+    match codec.decode::<crate::WrappedData<String>>(&frame.data) {
+        Ok(msg) => Error::Remote(msg.data),
+        Err(e) => e,
+    }
+}
+
+impl Peer {
+    fn next_call_id(&mut self) -> u64 {
+        self.next_call += 1;
+        self.next_call
+    }
+
+    /// Sends `request` and waits for the other peer to [`reply`](Peer::reply) to it.
+    ///
+    /// Messages that arrive in the meantime are kept for [`Peer::recv`], while calls of the other
+    /// peer are answered if they have a handler (see [`Peer::handle`]).
+    /// ```no_run
+    /// # use wing_rpc::{client, Message};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct ById { id: usize }
+    /// # impl Message<'_> for ById { const NAME: &'static str = "ById"; }
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Result { title: String }
+    /// # impl Message<'_> for Result { const NAME: &'static str = "Result"; }
+    /// let mut peer = client::tcp("localhost:6000").unwrap();
+    /// let result: Result = peer.call(ById { id: 3 }).unwrap();
+    /// ```
+    pub fn call<'a, R: for<'b> Message<'b>>(&mut self, request: impl Message<'a>) -> WingResult<R> {
+        let id = self.next_call_id();
+        self.send_message(0, Ids::call(id), request)?;
+        let timeout = self.config.recv_timeout.clone();
+        let frame = self
+            .next_frame_serving(timeout, |f| answers(f, id))?
+            .ok_or_else(timed_out)?;
+        if frame.is_control() {
+            return Err(remote_error(&frame, self.config.codec));
+        }
+        decode(&frame, self.config.codec)
+    }
+
+    /// Sends `request` and returns an iterator over the items the other peer streams back with
+    /// [`Peer::reply_stream`].
+    /// ```no_run
+    /// # use wing_rpc::{client, Message};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Search { name: String }
+    /// # impl Message<'_> for Search { const NAME: &'static str = "Search"; }
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Result { title: String }
+    /// # impl Message<'_> for Result { const NAME: &'static str = "Result"; }
+    /// let mut peer = client::tcp("localhost:6000").unwrap();
+    /// let results = peer.call_stream::<Result>(Search { name: "wing".into() }).unwrap();
+    /// for result in results.take(10) {
+    ///     println!("{}", result.unwrap().title);
+    /// }
+    /// ```
+    pub fn call_stream<'a, T: for<'b> Message<'b>>(
+        &mut self,
+        request: impl Message<'a>,
+    ) -> WingResult<CallStream<'_, T>> {
+        let id = self.next_call_id();
+        self.send_message(0, Ids::call(id), request)?;
+        Ok(CallStream {
+            peer: self,
+            id,
+            done: false,
+            _item: PhantomData,
+        })
+    }
+
+    /// Waits for the other peer to make a call.
+    ///
+    /// Plain messages that arrive in the meantime are kept for [`Peer::recv`].
+    pub fn next_call(&mut self) -> WingResult<Call> {
+        let timeout = self.config.recv_timeout.clone();
+        self.try_next_call(timeout)?.ok_or_else(timed_out)
+    }
+
+    /// Waits for the other peer to make a call with a [`Timeout`]. See [`Peer::try_recv`].
+    pub fn try_next_call(&mut self, timeout: Timeout) -> WingResult<Option<Call>> {
+        let frame =
+            self.next_frame_serving(timeout, |f| !f.is_control() && f.header.call.is_some())?;
+        Ok(frame.map(|frame| Call::new(frame, self.config.codec)))
+    }
+
+    /// Answers `call` with a single `reply`.
+    pub fn reply<'a>(&mut self, call: &Call, reply: impl Message<'a>) -> WingResult<()> {
+        self.send_message(0, Ids::reply(call.id), reply)
+    }
+
+    /// Answers `call` with an error, which the caller gets as [`Error::Remote`].
+    pub fn reply_error(&mut self, call: &Call, message: impl Into<String>) -> WingResult<()> {
+        self.send_envelope(
+            flags::CONTROL,
+            CALL_ERROR,
+            Ids::reply(call.id),
+            message.into(),
+        )
+    }
+
+    /// Answers `call` with a stream of items.
+    ///
+    /// ```no_run
+    /// # use wing_rpc::{server::{Mode, TcpServer}, Message};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Search { name: String }
+    /// # impl Message<'_> for Search { const NAME: &'static str = "Search"; }
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Result { title: String }
+    /// # impl Message<'_> for Result { const NAME: &'static str = "Result"; }
+    /// # let server = TcpServer::bind("localhost:6000").unwrap();
+    /// let mut peer = server.accept(Mode::Blocking).unwrap();
+    /// let call = peer.next_call().unwrap();
+    /// let search: Search = call.decode().unwrap();
+    /// let mut results = peer.reply_stream(&call);
+    /// for title in ["wing", "wing-rpc"] {
+    ///     results.send(Result { title: title.into() }).unwrap();
+    /// }
+    /// results.finish().unwrap();
+    /// ```
+    pub fn reply_stream<T>(&mut self, call: &Call) -> ReplyStream<'_, T> {
+        ReplyStream {
+            peer: self,
+            id: call.id,
+            finished: false,
+            _item: PhantomData,
+        }
+    }
+
+    /// Answers `call` with whatever `handler` returns for its request.
+    ///
+    /// If `handler` fails, the caller gets the error as [`Error::Remote`] and it is also returned.
+    pub fn answer<Req: for<'a> Message<'a>, Resp: for<'a> Message<'a>>(
+        &mut self,
+        call: &Call,
+        handler: impl FnOnce(Req) -> WingResult<Resp>,
+    ) -> WingResult<()> {
+        match call.decode().and_then(handler) {
+            Ok(reply) => self.reply(call, reply),
+            Err(e) => {
+                self.reply_error(call, e.to_string())?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Answers `call` with the items `handler` sends for its request. See [`Peer::reply_stream`].
+    ///
+    /// The stream ends once `handler` returns. If the caller cancels the stream, the
+    /// [`Error::Cancelled`] returned by [`ReplyStream::send`] is not treated as a failure.
+    pub fn answer_stream<Req: for<'a> Message<'a>, T: for<'a> Message<'a>>(
+        &mut self,
+        call: &Call,
+        handler: impl FnOnce(Req, &mut ReplyStream<'_, T>) -> WingResult<()>,
+    ) -> WingResult<()> {
+        let request = match call.decode() {
+            Ok(request) => request,
+            Err(e) => {
+                self.reply_error(call, e.to_string())?;
+                return Err(e);
+            }
+        };
+        let mut stream = self.reply_stream(call);
+        match handler(request, &mut stream) {
+            Ok(()) | Err(Error::Cancelled) => stream.finish(),
+            Err(e) => {
+                // The error replaces the end of the stream.
+                stream.finished = true;
+                drop(stream);
+                self.reply_error(call, e.to_string())?;
+                Err(e)
+            }
+        }
+    }
+
+    fn send_message<'a, M: Message<'a>>(
+        &mut self,
+        flags: u8,
+        ids: Ids,
+        message: M,
+    ) -> WingResult<()> {
+        self.send_envelope(flags, M::NAME, ids, message)
+    }
+
+    pub(crate) fn send_envelope(
+        &mut self,
+        flags: u8,
+        typ: &str,
+        ids: Ids,
+        data: impl Serialize,
+    ) -> WingResult<()> {
+        let mut buf = Vec::new();
+        let fds = self.config.codec.encode_into(
+            &mut buf,
+            &crate::WrappedData {
+                typ: typ.into(),
+                call: ids.call,
+                reply: ids.reply,
+                stream: ids.stream,
+                data,
+            },
+        )?;
+        self.write_frame(flags, &buf, fds)?;
+        Ok(())
+    }
+}
@@ -2,8 +2,13 @@
 
 use std::io;
 use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
 
-use crate::Peer;
+use crate::{Peer, WingResult, auth::Secret};
+
+mod reconnect;
+pub use reconnect::{Backoff, ReconnectingPeer};
 
 /// Connects to a TCP server listening at `addr`.
 /// ```no_run
@@ -15,3 +20,87 @@ pub fn tcp(addr: impl ToSocketAddrs) -> io::Result<Peer> {
     let sock = TcpStream::connect(addr)?;
     Ok(Peer::from_socket(sock))
 }
+
+/// Connects to a server listening on the Unix domain socket at `path`.
+/// ```no_run
+/// use wing_rpc::client;
+///
+/// let peer = client::unix("/run/user/1000/app.sock").unwrap();
+/// ```
+#[cfg(unix)]
+pub fn unix(path: impl AsRef<std::path::Path>) -> io::Result<Peer> {
+    let sock = std::os::unix::net::UnixStream::connect(path)?;
+    Ok(Peer::from_unix_socket(sock))
+}
+
+/// Connects to a TCP server listening at `addr` that requires authentication.
+///
+/// Fails with [`crate::Error::AuthFailed`] if the server doesn't accept `secret`.
+/// See [`crate::auth`] for details.
+/// ```no_run
+/// use wing_rpc::{auth::Secret, client};
+///
+/// let peer = client::tcp_with_auth("localhost:6000", &Secret::new("hunter2")).unwrap();
+/// ```
+pub fn tcp_with_auth(addr: impl ToSocketAddrs, secret: &Secret) -> WingResult<Peer> {
+    let mut peer = tcp(addr)?;
+    peer.authenticate(secret)?;
+    Ok(peer)
+}
+
+/// Connects to a TLS server listening at `addr`, which must be in the `host:port` format.
+///
+/// The host is also used to verify the server's certificate. If the server requires client
+/// authentication, `config` must be built with a client certificate.
+/// If the server also requires a shared secret, call [`Peer::authenticate`] on the returned peer.
+/// ```no_run
+/// use std::sync::Arc;
+/// use wing_rpc::{client, rustls};
+///
+/// # let roots = rustls::RootCertStore::empty();
+/// let config = rustls::ClientConfig::builder()
+///     .with_root_certificates(roots)
+///     .with_no_client_auth();
+/// let peer = client::tls("localhost:6000", Arc::new(config)).unwrap();
+/// ```
+#[cfg(feature = "tls")]
+pub fn tls(addr: &str, config: Arc<rustls::ClientConfig>) -> io::Result<Peer> {
+    let (host, _) = addr
+        .rsplit_once(':')
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Missing port in address"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let name = rustls::pki_types::ServerName::try_from(host.to_owned())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let conn = rustls::ClientConnection::new(config, name).map_err(io::Error::other)?;
+    let sock = TcpStream::connect(addr)?;
+    Ok(Peer::from_stream(crate::tls::handshake(conn, sock)?))
+}
+
+/// Connects to a WebSocket server at `url`, such as a [`crate::server::WsServer`].
+///
+/// Only plain `ws://` URLs are supported.
+/// ```no_run
+/// use wing_rpc::client;
+///
+/// let peer = client::websocket("ws://localhost:6000").unwrap();
+/// ```
+#[cfg(feature = "websocket")]
+pub fn websocket(url: &str) -> io::Result<Peer> {
+    use tungstenite::client::IntoClientRequest;
+
+    let request = url.into_client_request().map_err(crate::ws::into_io)?;
+    let uri = request.uri();
+    if uri.scheme_str() != Some("ws") {
+        return Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "Only ws:// URLs are supported",
+        ));
+    }
+    let host = uri
+        .host()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Missing host in URL"))?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let sock = TcpStream::connect((host, uri.port_u16().unwrap_or(80)))?;
+    let (ws, _) = tungstenite::client(request, sock).map_err(crate::ws::handshake_error)?;
+    Ok(Peer::from_stream(crate::ws::WsStream::new(ws)))
+}
@@ -0,0 +1,348 @@
+use std::{
+    collections::VecDeque,
+    io,
+    net::{SocketAddr, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    Error, Fds, Message, Peer, PeerBuilder, Priority, Timeout, WingResult, auth::Secret, encode,
+    timed_out,
+};
+
+/// How long a [`ReconnectingPeer`] waits between connection attempts.
+///
+/// The first retry happens after `initial`, and every failed attempt multiplies the delay by
+/// `multiplier`, up to `max`. Each delay is then randomly stretched or shrunk by up to `jitter`
+/// (a fraction of the delay), so many clients don't hammer a restarting backend in lockstep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+    pub jitter: f64,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(100),
+            max: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl Backoff {
+    /// The delays before each retry of a connection that keeps failing, jitter included.
+    pub fn delays(&self) -> impl Iterator<Item = Duration> + '_ {
+        std::iter::successors(Some(self.initial), |&delay| Some(self.next_delay(delay)))
+            .map(|delay| self.jittered(delay))
+    }
+    fn next_delay(&self, delay: Duration) -> Duration {
+        delay.mul_f64(self.multiplier).min(self.max)
+    }
+    fn jittered(&self, delay: Duration) -> Duration {
+        let mut random = [0u8; 4];
+        // Not having jitter is not worth failing over.
+        let _ = getrandom::fill(&mut random);
+        let unit = u32::from_ne_bytes(random) as f64 / u32::MAX as f64;
+        delay.mul_f64((1.0 + self.jitter * (unit * 2.0 - 1.0)).max(0.0))
+    }
+}
+
+type Resolve = Box<dyn Fn() -> io::Result<Vec<SocketAddr>> + Send>;
+
+enum Target {
+    Tcp(Resolve),
+    #[cfg(unix)]
+    Unix(std::path::PathBuf),
+}
+
+impl Target {
+    fn connect(&self, builder: &PeerBuilder) -> io::Result<Peer> {
+        match self {
+            Self::Tcp(resolve) => builder.socket(std::net::TcpStream::connect(&*resolve()?)?),
+            #[cfg(unix)]
+            Self::Unix(path) => builder.unix_socket(std::os::unix::net::UnixStream::connect(path)?),
+        }
+    }
+}
+
+/// Returns `true` if `error` means the connection is gone, rather than that an operation failed.
+fn is_disconnect(error: &Error) -> bool {
+    use io::ErrorKind::*;
+    match error {
+        Error::Io(e) => matches!(
+            e.kind(),
+            UnexpectedEof | BrokenPipe | ConnectionReset | ConnectionAborted | NotConnected
+        ),
+        _ => false,
+    }
+}
+
+type Callback = Box<dyn FnMut(&mut Peer) -> WingResult<()> + Send>;
+
+/// A client that transparently dials its server again whenever the connection is lost.
+///
+/// Messages sent while disconnected are buffered, up to [`ReconnectingPeer::max_pending`], and
+/// delivered once the connection is back. Note that messages written right before the connection
+/// broke may still be lost, so use [`ReconnectingPeer::on_reconnect`] to resend any state the
+/// server needs.
+/// ```no_run
+/// use wing_rpc::client::{Backoff, ReconnectingPeer};
+/// # #[derive(serde::Serialize, serde::Deserialize)]
+/// # struct Hello;
+/// # impl wing_rpc::Message<'_> for Hello { const NAME: &'static str = "Hello"; }
+///
+/// let mut peer = ReconnectingPeer::tcp("localhost:6000")
+///     .unwrap()
+///     .backoff(Backoff::default())
+///     .on_reconnect(|peer| peer.send(Hello));
+/// peer.send(Hello).unwrap();
+/// ```
+pub struct ReconnectingPeer {
+    target: Target,
+    builder: PeerBuilder,
+    backoff: Backoff,
+    secret: Option<Secret>,
+    on_reconnect: Option<Callback>,
+    max_pending: usize,
+    pending: VecDeque<(u8, Vec<u8>)>,
+    peer: Option<Peer>,
+    connected_once: bool,
+    delay: Duration,
+    next_attempt: Instant,
+}
+
+impl ReconnectingPeer {
+    fn new(target: Target) -> Self {
+        let backoff = Backoff::default();
+        Self {
+            target,
+            builder: PeerBuilder::new(),
+            delay: backoff.initial,
+            backoff,
+            secret: None,
+            on_reconnect: None,
+            max_pending: 1024,
+            pending: VecDeque::new(),
+            peer: None,
+            connected_once: false,
+            next_attempt: Instant::now(),
+        }
+    }
+    /// Creates a [`ReconnectingPeer`] that dials the TCP server listening at `addr`.
+    ///
+    /// No connection is made until the first message is sent or received. `addr` is resolved
+    /// again before every attempt, so the server may move to another address.
+    pub fn tcp(addr: impl ToSocketAddrs + Send + 'static) -> io::Result<Self> {
+        // Fail right away on addresses that can never be resolved.
+        let _ = addr.to_socket_addrs()?;
+        Ok(Self::new(Target::Tcp(Box::new(move || {
+            Ok(addr.to_socket_addrs()?.collect())
+        }))))
+    }
+    /// Creates a [`ReconnectingPeer`] that dials the Unix domain socket at `path`.
+    ///
+    /// No connection is made until the first message is sent or received.
+    #[cfg(unix)]
+    pub fn unix(path: impl Into<std::path::PathBuf>) -> Self {
+        Self::new(Target::Unix(path.into()))
+    }
+    /// Sets how long to wait between connection attempts.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.delay = backoff.initial;
+        self.backoff = backoff;
+        self
+    }
+    /// Sets the settings of every connection. See [`PeerBuilder`].
+    pub fn peer_builder(mut self, builder: PeerBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+    /// Authenticates with `secret` every time a connection is made. See [`crate::auth`].
+    pub fn with_auth(mut self, secret: Secret) -> Self {
+        self.secret = Some(secret);
+        self
+    }
+    /// Sets how many messages can be buffered while disconnected.
+    ///
+    /// Once full, [`ReconnectingPeer::send`] fails with [`Error::WouldOverflow`].
+    pub fn max_pending(mut self, max_pending: usize) -> Self {
+        self.max_pending = max_pending;
+        self
+    }
+    /// Calls `callback` every time the connection is re-established, before buffered messages are
+    /// delivered.
+    ///
+    /// This is the place to resend any state the other peer lost.
+    pub fn on_reconnect(
+        mut self,
+        callback: impl FnMut(&mut Peer) -> WingResult<()> + Send + 'static,
+    ) -> Self {
+        self.on_reconnect = Some(Box::new(callback));
+        self
+    }
+    /// Returns `true` if there's currently a live connection.
+    pub fn is_connected(&self) -> bool {
+        self.peer.is_some()
+    }
+    /// Returns the underlying [`Peer`], if currently connected.
+    pub fn peer(&mut self) -> Option<&mut Peer> {
+        self.peer.as_mut()
+    }
+
+    /// Sends `message`, or buffers it if the connection is down.
+    ///
+    /// This never waits for the connection to come back. Messages over the
+    /// [`PeerBuilder::max_frame_size`] are rejected right away instead of being buffered.
+    pub fn send<'a>(&mut self, message: impl Message<'a>) -> WingResult<()> {
+        self.send_flagged(0, message)
+    }
+    /// Sends `message` with a [`Priority`], or buffers it if the connection is down.
+    ///
+    /// Buffered messages keep their priority once delivered. See [`Peer::send_with_priority`].
+    pub fn send_with_priority<'a>(
+        &mut self,
+        message: impl Message<'a>,
+        priority: Priority,
+    ) -> WingResult<()> {
+        self.send_flagged(priority.flags(), message)
+    }
+    fn send_flagged<'a>(&mut self, flags: u8, message: impl Message<'a>) -> WingResult<()> {
+        let data = encode(self.builder.config().codec, message)?;
+        // Buffering it would block every message behind it, as it could never be delivered.
+        self.builder.config().check_frame_size(data.len())?;
+        if self.pending.len() >= self.max_pending {
+            return Err(Error::WouldOverflow);
+        }
+        self.pending.push_back((flags, data));
+        match self.connect(false) {
+            Ok(()) => Ok(()),
+            // A failed attempt just means the message stays buffered.
+            Err(Error::Io(_)) if self.peer.is_none() => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Waits for a message of `T` to arrive, reconnecting as many times as needed.
+    ///
+    /// Gives up with [`io::ErrorKind::TimedOut`] once the [`PeerBuilder::recv_timeout`] of
+    /// [`ReconnectingPeer::peer_builder`] is over.
+    pub fn recv<T: for<'a> Message<'a>>(&mut self) -> WingResult<T> {
+        let timeout = self.builder.config().recv_timeout.clone();
+        let start = Instant::now();
+        loop {
+            let remaining = timeout.remaining(start);
+            if let Some(msg) = self.try_recv(remaining.clone())? {
+                return Ok(msg);
+            }
+            match remaining {
+                Timeout::Block => {}
+                // Wait for the next connection attempt, but not past the timeout.
+                Timeout::WaitFor(left) if !left.is_zero() => std::thread::sleep(
+                    self.next_attempt
+                        .saturating_duration_since(Instant::now())
+                        .min(left),
+                ),
+                _ => return Err(timed_out()),
+            }
+        }
+    }
+
+    /// Waits for a message of `T` to arrive with a [`Timeout`]. See [`Peer::try_recv`].
+    ///
+    /// Unless `timeout` is [`Timeout::Block`], this makes at most one connection attempt and
+    /// returns `None` if it fails.
+    pub fn try_recv<T: for<'a> Message<'a>>(&mut self, timeout: Timeout) -> WingResult<Option<T>> {
+        loop {
+            match self.connect(timeout == Timeout::Block) {
+                Ok(()) => {}
+                Err(Error::Io(_)) if timeout != Timeout::Block && self.peer.is_none() => {
+                    return Ok(None);
+                }
+                Err(e) => return Err(e),
+            }
+            let peer = self.peer.as_mut().expect("Connected peer");
+            match peer.try_recv::<T>(timeout.clone()) {
+                Err(e) if is_disconnect(&e) => self.disconnect(),
+                result => return result,
+            }
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.peer = None;
+        self.delay = self.backoff.initial;
+        self.next_attempt = Instant::now();
+    }
+
+    /// Makes sure there is a live connection and delivers buffered messages.
+    ///
+    /// If `wait` is set, keeps retrying until it succeeds. Otherwise, attempts to connect at most
+    /// once, and only if the backoff delay has passed.
+    fn connect(&mut self, wait: bool) -> WingResult<()> {
+        loop {
+            if self.peer.is_none() {
+                let now = Instant::now();
+                if now < self.next_attempt {
+                    if !wait {
+                        return Err(Error::Io(io::ErrorKind::NotConnected.into()));
+                    }
+                    std::thread::sleep(self.next_attempt - now);
+                }
+                match self.dial() {
+                    Ok(peer) => {
+                        self.peer = Some(peer);
+                        self.delay = self.backoff.initial;
+                    }
+                    Err(Error::Io(e)) => {
+                        self.next_attempt = Instant::now() + self.backoff.jittered(self.delay);
+                        self.delay = self.backoff.next_delay(self.delay);
+                        if wait {
+                            continue;
+                        }
+                        return Err(Error::Io(e));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            match self.flush_pending() {
+                Ok(()) => return Ok(()),
+                Err(e) if is_disconnect(&e) => {
+                    self.disconnect();
+                    if !wait {
+                        return Err(e);
+                    }
+                }
+                // The connection is fine, so the message is left to be sent later.
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn dial(&mut self) -> WingResult<Peer> {
+        let mut peer = self.target.connect(&self.builder)?;
+        if let Some(secret) = &self.secret {
+            peer.authenticate(secret)?;
+        }
+        if self.connected_once
+            && let Some(callback) = &mut self.on_reconnect
+        {
+            callback(&mut peer)?;
+        }
+        self.connected_once = true;
+        Ok(peer)
+    }
+
+    fn flush_pending(&mut self) -> WingResult<()> {
+        let peer = self.peer.as_mut().expect("Connected peer");
+        while let Some((flags, data)) = self.pending.front() {
+            peer.write_frame(*flags, data, Fds::new())?;
+            self.pending.pop_front();
+        }
+        Ok(())
+    }
+}
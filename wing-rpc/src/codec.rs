@@ -0,0 +1,62 @@
+//! How messages are turned into bytes and back.
+use serde::{Deserialize, Serialize};
+
+use crate::{Fds, WingResult};
+
+/// The format messages are encoded with. Both peers must use the same one.
+///
+/// See [`crate::PeerBuilder::codec`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// Understood by every `Wing RPC` runtime.
+    #[default]
+    Json,
+    /// More compact and faster than JSON, but only understood by the Rust runtime for now.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl Codec {
+    /// Encodes `value`, which must not contain file descriptors.
+    pub(crate) fn encode(self, value: &impl Serialize) -> WingResult<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.encode_plain(&mut buf, value)?;
+        Ok(buf)
+    }
+    /// Appends the encoded `value` to `buf`, so its memory can be reused between messages.
+    ///
+    /// Returns the file descriptors that must be sent along, see [`crate::fd`].
+    pub(crate) fn encode_into(self, buf: &mut Vec<u8>, value: &impl Serialize) -> WingResult<Fds> {
+        #[cfg(unix)]
+        let (encoded, fds) = crate::fd::collect(|| self.encode_plain(buf, value));
+        #[cfg(not(unix))]
+        let (encoded, fds) = (self.encode_plain(buf, value), Fds::new());
+        encoded.map(|()| fds)
+    }
+    fn encode_plain(self, buf: &mut Vec<u8>, value: &impl Serialize) -> WingResult<()> {
+        match self {
+            Self::Json => Ok(serde_json::to_writer(buf, value)?),
+            // Fields are encoded by name, so envelopes can be routed without knowing their data.
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => Ok(rmp_serde::encode::write_named(buf, value)?),
+        }
+    }
+    pub(crate) fn decode<'a, T: Deserialize<'a>>(self, bytes: &'a [u8]) -> WingResult<T> {
+        match self {
+            Self::Json => Ok(serde_json::from_slice(bytes)?),
+            #[cfg(feature = "msgpack")]
+            Self::MessagePack => Ok(rmp_serde::from_slice(bytes)?),
+        }
+    }
+    /// Decodes `bytes`, handing `fds` to the file descriptors within.
+    pub(crate) fn decode_with<'a, T: Deserialize<'a>>(
+        self,
+        bytes: &'a [u8],
+        fds: Fds,
+    ) -> WingResult<T> {
+        #[cfg(unix)]
+        return crate::fd::provide(fds, || self.decode(bytes));
+        #[cfg(not(unix))]
+        return self.decode(bytes);
+    }
+}
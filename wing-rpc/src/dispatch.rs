@@ -0,0 +1,113 @@
+//! Serving calls of the other peer while waiting for something else.
+use std::{collections::HashMap, time::Instant};
+
+use crate::{
+    Error, Message, Peer, Timeout, WingResult,
+    call::{Call, ReplyStream},
+    inbox::Frame,
+};
+
+type Handler = Box<dyn FnMut(&mut Peer, &Call) -> WingResult<()> + Send>;
+/// Handlers for calls of the other peer, indexed by [`Message::NAME`] of the request.
+pub(crate) type Handlers = HashMap<String, Handler>;
+
+/// Returns `true` if `frame` is a call that one of `handlers` answers.
+fn handled(handlers: &Handlers, frame: &Frame) -> bool {
+    !frame.is_control() && frame.header.call.is_some() && handlers.contains_key(&frame.header.typ)
+}
+
+impl Peer {
+    /// Answers every call of `Req` with `handler`. See [`Peer::answer`].
+    ///
+    /// Handlers run whenever this peer waits for something else, such as a message, a call
+    /// without a handler or the reply to one of its own calls. That's what lets both peers call
+    /// each other at the same time.
+    /// ```no_run
+    /// # use wing_rpc::{client, Message};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Confirm { question: String }
+    /// # impl Message<'_> for Confirm { const NAME: &'static str = "Confirm"; }
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Answer { yes: bool }
+    /// # impl Message<'_> for Answer { const NAME: &'static str = "Answer"; }
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Save { path: String }
+    /// # impl Message<'_> for Save { const NAME: &'static str = "Save"; }
+    /// let mut peer = client::tcp("localhost:6000").unwrap();
+    /// peer.handle(|confirm: Confirm| {
+    ///     println!("{}", confirm.question);
+    ///     Ok(Answer { yes: true })
+    /// });
+    /// // The other peer may ask us to confirm before it replies.
+    /// let saved: Answer = peer.call(Save { path: "notes.txt".into() }).unwrap();
+    /// ```
+    pub fn handle<Req, Resp>(
+        &mut self,
+        mut handler: impl FnMut(Req) -> WingResult<Resp> + Send + 'static,
+    ) where
+        Req: for<'a> Message<'a>,
+        Resp: for<'a> Message<'a>,
+    {
+        self.handlers.insert(
+            Req::NAME.into(),
+            Box::new(move |peer, call| peer.answer(call, &mut handler)),
+        );
+    }
+
+    /// Answers every call of `Req` with the items `handler` sends. See [`Peer::answer_stream`] and
+    /// [`Peer::handle`].
+    pub fn handle_stream<Req, T>(
+        &mut self,
+        mut handler: impl FnMut(Req, &mut ReplyStream<'_, T>) -> WingResult<()> + Send + 'static,
+    ) where
+        Req: for<'a> Message<'a>,
+        T: for<'a> Message<'a>,
+    {
+        self.handlers.insert(
+            Req::NAME.into(),
+            Box::new(move |peer, call| peer.answer_stream(call, &mut handler)),
+        );
+    }
+
+    /// Stops answering calls of `Req` automatically. Returns `false` if there was no handler.
+    pub fn remove_handler<Req: for<'a> Message<'a>>(&mut self) -> bool {
+        self.handlers.remove(Req::NAME).is_some()
+    }
+
+    /// Same as [`Peer::next_frame`], but answers calls that have a handler while waiting.
+    pub(crate) fn next_frame_serving(
+        &mut self,
+        timeout: Timeout,
+        mut pick: impl FnMut(&Frame) -> bool,
+    ) -> WingResult<Option<Frame>> {
+        let start = Instant::now();
+        loop {
+            // Handlers can't be borrowed while the frames are, so they are put aside meanwhile.
+            let handlers = std::mem::take(&mut self.handlers);
+            let frame = self.next_frame(timeout.remaining(start), |frame| {
+                handled(&handlers, frame) || pick(frame)
+            });
+            self.handlers = handlers;
+            let frame = try_harder!(frame);
+            if !handled(&self.handlers, &frame) {
+                return Ok(Some(frame));
+            }
+            self.dispatch(Call::new(frame, self.config.codec))?;
+        }
+    }
+
+    fn dispatch(&mut self, call: Call) -> WingResult<()> {
+        let (name, mut handler) = self
+            .handlers
+            .remove_entry(call.name())
+            .expect("Call has a handler");
+        // The handler is free to make calls of its own, which may in turn run other handlers.
+        let result = handler(self, &call);
+        self.handlers.entry(name).or_insert(handler);
+        match result {
+            // Other errors were already reported to the caller, and are none of our business.
+            Err(Error::Io(e)) => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Lets peers sit in single-threaded event loops, such as [`mio`] or [`calloop`] ones.
+use std::os::fd::RawFd;
+
+use crate::Peer;
+
+impl Peer {
+    /// The file descriptor that becomes readable when there's something to receive.
+    ///
+    /// That's the socket of peers created from one, see [`Peer::from_socket`] and
+    /// [`Peer::from_unix_socket`], and an eventfd for shared memory peers, see [`crate::shm`].
+    /// Other peers, such as TLS or WebSocket ones, don't have one.
+    pub fn raw_fd(&self) -> Option<RawFd> {
+        self.fd.or(self.readable)
+    }
+    /// Same as [`Peer::raw_fd`], but fails for peers that don't have a file descriptor.
+    #[cfg(any(feature = "mio", feature = "calloop"))]
+    fn pollable_fd(&self) -> std::io::Result<RawFd> {
+        self.raw_fd().ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "Only socket and shared memory peers can be polled",
+            )
+        })
+    }
+}
+
+/// Registers the file descriptor of a [`Peer`], see [`Peer::raw_fd`]. Registering a peer
+/// without one fails with [`std::io::ErrorKind::Unsupported`].
+///
+/// Once it is readable, call [`Peer::try_recv`] with [`crate::Timeout::DontBlock`] until it
+/// returns `None`, as events won't be reported again until everything was read.
+#[cfg(feature = "mio")]
+impl mio::event::Source for Peer {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.pollable_fd()?).register(registry, token, interests)
+    }
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.pollable_fd()?).reregister(registry, token, interests)
+    }
+    fn deregister(&mut self, registry: &mio::Registry) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.pollable_fd()?).deregister(registry)
+    }
+}
+
+#[cfg(feature = "calloop")]
+pub use self::calloop_source::PeerSource;
+
+#[cfg(feature = "calloop")]
+mod calloop_source {
+    use std::{
+        io::ErrorKind,
+        marker::PhantomData,
+        os::fd::{AsFd, BorrowedFd, RawFd},
+    };
+
+    use calloop::{
+        EventSource, Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+        generic::Generic,
+    };
+
+    use crate::{Error, Message, Peer, Timeout};
+
+    /// A peer along with its file descriptor, which [`Generic`] needs.
+    struct Polled(Peer, RawFd);
+
+    impl AsFd for Polled {
+        fn as_fd(&self) -> BorrowedFd<'_> {
+            // SAFETY: The descriptor is owned by the peer, so it lives as long as we do.
+            unsafe { BorrowedFd::borrow_raw(self.1) }
+        }
+    }
+
+    enum Source {
+        Polled(Generic<Polled, Error>),
+        /// Peers without a file descriptor fail to register.
+        Unpolled(Peer),
+    }
+
+    /// A [`calloop`] event source that receives messages of `T` from a [`Peer`].
+    ///
+    /// The callback is given every message as soon as it is read, along with the peer to answer
+    /// it. Calls with a handler, see [`Peer::handle`], are served meanwhile. Messages that aren't
+    /// a `T` are skipped. The source removes itself once the other peer disconnects.
+    /// ```no_run
+    /// # use wing_rpc::{Message, Peer, PeerSource};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Ping(u32);
+    /// # impl Message<'_> for Ping { const NAME: &'static str = "Ping"; }
+    /// # let peer: Peer = todo!();
+    /// let mut event_loop = calloop::EventLoop::<()>::try_new().unwrap();
+    /// event_loop
+    ///     .handle()
+    ///     .insert_source(PeerSource::<Ping>::new(peer), |Ping(n), peer, _| {
+    ///         peer.send(Ping(n + 1)).unwrap();
+    ///     })
+    ///     .unwrap();
+    /// event_loop.run(None, &mut (), |_| {}).unwrap();
+    /// ```
+    pub struct PeerSource<T> {
+        source: Source,
+        messages: PhantomData<fn() -> T>,
+    }
+
+    impl<T> PeerSource<T> {
+        /// See [`Peer::raw_fd`] on which peers can be used, inserting any other fails.
+        pub fn new(peer: Peer) -> Self {
+            let source = match peer.raw_fd() {
+                Some(fd) => Source::Polled(Generic::new_with_error(
+                    Polled(peer, fd),
+                    Interest::READ,
+                    Mode::Level,
+                )),
+                None => Source::Unpolled(peer),
+            };
+            Self {
+                source,
+                messages: PhantomData,
+            }
+        }
+        pub fn peer(&self) -> &Peer {
+            match &self.source {
+                Source::Polled(source) => &source.get_ref().0,
+                Source::Unpolled(peer) => peer,
+            }
+        }
+        /// Unregisters the source and returns its peer.
+        pub fn into_inner(self) -> Peer {
+            match self.source {
+                Source::Polled(source) => source.unwrap().0,
+                Source::Unpolled(peer) => peer,
+            }
+        }
+    }
+
+    /// Returns `true` for errors that only concern the frame that was being received.
+    fn skippable(error: &Error) -> bool {
+        match error {
+            Error::Serde(_) => true,
+            #[cfg(feature = "msgpack")]
+            Error::MsgPackDecode(_) => true,
+            // Frames over the size limit.
+            Error::Io(e) => e.kind() == ErrorKind::InvalidData,
+            _ => false,
+        }
+    }
+
+    impl<T: for<'a> Message<'a>> EventSource for PeerSource<T> {
+        type Event = T;
+        type Metadata = Peer;
+        type Ret = ();
+        type Error = Error;
+
+        fn process_events<F>(
+            &mut self,
+            readiness: Readiness,
+            token: Token,
+            mut callback: F,
+        ) -> Result<PostAction, Error>
+        where
+            F: FnMut(T, &mut Peer),
+        {
+            let Source::Polled(source) = &mut self.source else {
+                return Ok(PostAction::Continue);
+            };
+            source.process_events(readiness, token, |_, polled| {
+                // SAFETY: The peer is handed out by reference, so its socket can't be replaced.
+                let peer = unsafe { &mut polled.get_mut().0 };
+                loop {
+                    match peer.try_recv::<T>(Timeout::DontBlock) {
+                        Ok(Some(message)) => callback(message, peer),
+                        Ok(None) => return Ok(PostAction::Continue),
+                        Err(Error::Io(e)) if e.kind() == ErrorKind::UnexpectedEof => {
+                            return Ok(PostAction::Remove);
+                        }
+                        Err(e) if skippable(&e) => continue,
+                        Err(e) => return Err(e),
+                    }
+                }
+            })
+        }
+        fn register(&mut self, poll: &mut Poll, factory: &mut TokenFactory) -> calloop::Result<()> {
+            match &mut self.source {
+                Source::Polled(source) => source.register(poll, factory),
+                Source::Unpolled(peer) => Err(peer.pollable_fd().unwrap_err().into()),
+            }
+        }
+        fn reregister(
+            &mut self,
+            poll: &mut Poll,
+            factory: &mut TokenFactory,
+        ) -> calloop::Result<()> {
+            match &mut self.source {
+                Source::Polled(source) => source.reregister(poll, factory),
+                Source::Unpolled(peer) => Err(peer.pollable_fd().unwrap_err().into()),
+            }
+        }
+        fn unregister(&mut self, poll: &mut Poll) -> calloop::Result<()> {
+            match &mut self.source {
+                Source::Polled(source) => source.unregister(poll),
+                Source::Unpolled(_) => Ok(()),
+            }
+        }
+    }
+}
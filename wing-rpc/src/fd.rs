@@ -0,0 +1,279 @@
+//! Passing file descriptors between peers on the same machine.
+//!
+//! Large payloads such as video frames are better shared through a memfd than copied into a
+//! message. Fields of type [`OwnedFd`] (`fd` in the IDL) are moved to the other peer as
+//! `SCM_RIGHTS` ancillary data alongside their frame, while the message itself only holds their
+//! position. Mark such fields with `#[serde(with = "wing_rpc::fd")]`:
+//! ```no_run
+//! use std::os::{fd::OwnedFd, unix::net::UnixStream};
+//! use serde::{Deserialize, Serialize};
+//! use wing_rpc::{Message, Peer};
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct VideoFrame {
+//!     #[serde(with = "wing_rpc::fd")]
+//!     pixels: OwnedFd,
+//!     width: u32,
+//! }
+//! impl Message<'_> for VideoFrame {
+//!     const NAME: &'static str = "VideoFrame";
+//! }
+//!
+//! # let pixels: OwnedFd = todo!();
+//! let mut peer = Peer::from_unix_socket(UnixStream::connect("/tmp/video.sock").unwrap());
+//! peer.send(VideoFrame { pixels, width: 1920 }).unwrap();
+//! ```
+//! Descriptors can only be sent through peers created from a Unix socket. Sending them through
+//! anything else fails, and so does encoding them outside of a [`crate::Peer`].
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de, de::DeserializeOwned, ser};
+
+/// How many descriptors can be sent along a single frame. This is Linux's `SCM_MAX_FD`.
+const MAX_FDS: usize = 253;
+/// Enough 8 byte words to hold a control message with [`MAX_FDS`] descriptors.
+const CONTROL_WORDS: usize = (MAX_FDS * size_of::<RawFd>()).div_ceil(8) + 4;
+
+thread_local! {
+    /// Descriptors of the message being encoded, if a peer is encoding it.
+    static OUTGOING: RefCell<Option<Vec<OwnedFd>>> = const { RefCell::new(None) };
+    /// Descriptors that came along the frame being decoded, if any.
+    static INCOMING: RefCell<Option<Vec<Option<OwnedFd>>>> = const { RefCell::new(None) };
+}
+
+/// Types made of file descriptors, which can be used with `#[serde(with = "wing_rpc::fd")]`.
+pub trait Descriptors: Sized {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>;
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>;
+}
+
+impl Descriptors for OwnedFd {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let index = OUTGOING.with_borrow_mut(|outgoing| {
+            let Some(outgoing) = outgoing else {
+                return Err(ser::Error::custom(
+                    "File descriptors can only be sent through a Peer",
+                ));
+            };
+            // The message may be gone by the time the frame is written, so keep a copy.
+            outgoing.push(self.try_clone().map_err(ser::Error::custom)?);
+            Ok(outgoing.len() - 1)
+        })?;
+        serializer.serialize_u32(index as u32)
+    }
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let index = u32::deserialize(deserializer)? as usize;
+        INCOMING
+            .with_borrow_mut(|incoming| incoming.as_mut()?.get_mut(index)?.take())
+            .ok_or_else(|| de::Error::custom(format!("Missing file descriptor {index}")))
+    }
+}
+
+/// Lets [`Descriptors`] be (de)serialized as part of a collection.
+struct Item<T>(T);
+
+impl<T: Descriptors> Serialize for Item<&T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de, T: Descriptors> Deserialize<'de> for Item<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        T::deserialize(deserializer).map(Item)
+    }
+}
+
+impl<T: Descriptors> Descriptors for Vec<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter().map(Item))
+    }
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = Vec::<Item<T>>::deserialize(deserializer)?;
+        Ok(items.into_iter().map(|Item(item)| item).collect())
+    }
+}
+
+impl<T: Descriptors> Descriptors for Option<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Some(item) => serializer.serialize_some(&Item(item)),
+            None => serializer.serialize_none(),
+        }
+    }
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let item = Option::<Item<T>>::deserialize(deserializer)?;
+        Ok(item.map(|Item(item)| item))
+    }
+}
+
+impl<K, T> Descriptors for HashMap<K, T>
+where
+    K: Serialize + DeserializeOwned + Eq + Hash,
+    T: Descriptors,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter().map(|(key, item)| (key, Item(item))))
+    }
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = HashMap::<K, Item<T>>::deserialize(deserializer)?;
+        Ok(items
+            .into_iter()
+            .map(|(key, Item(item))| (key, item))
+            .collect())
+    }
+}
+
+impl<K, T> Descriptors for BTreeMap<K, T>
+where
+    K: Serialize + DeserializeOwned + Ord,
+    T: Descriptors,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter().map(|(key, item)| (key, Item(item))))
+    }
+    fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let items = BTreeMap::<K, Item<T>>::deserialize(deserializer)?;
+        Ok(items
+            .into_iter()
+            .map(|(key, Item(item))| (key, item))
+            .collect())
+    }
+}
+
+/// Serializes `value`, to be used with `#[serde(with = "wing_rpc::fd")]`.
+pub fn serialize<T: Descriptors, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    value.serialize(serializer)
+}
+
+/// Deserializes `T`, to be used with `#[serde(with = "wing_rpc::fd")]`.
+pub fn deserialize<'de, T: Descriptors, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<T, D::Error> {
+    T::deserialize(deserializer)
+}
+
+/// Runs `encode`, returning the descriptors it came across.
+pub(crate) fn collect<R>(encode: impl FnOnce() -> R) -> (R, Vec<OwnedFd>) {
+    let outer = OUTGOING.replace(Some(Vec::new()));
+    let encoded = encode();
+    let fds = OUTGOING.replace(outer).unwrap_or_default();
+    (encoded, fds)
+}
+
+/// Runs `decode`, handing it the descriptors that came along its frame.
+///
+/// Descriptors that weren't used are closed.
+pub(crate) fn provide<R>(fds: Vec<OwnedFd>, decode: impl FnOnce() -> R) -> R {
+    let outer = INCOMING.replace(Some(fds.into_iter().map(Some).collect()));
+    let decoded = decode();
+    INCOMING.set(outer);
+    decoded
+}
+
+/// Returns `true` if `fd` is a Unix socket, the only kind that can carry descriptors.
+pub(crate) fn is_unix_socket(fd: RawFd) -> bool {
+    // SAFETY: `sockaddr_storage` is plain old data, and big enough for any address.
+    let mut addr: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut len = size_of_val(&addr) as libc::socklen_t;
+    // SAFETY: `addr` is valid for writes of `len` bytes.
+    let found = unsafe { libc::getsockname(fd, (&raw mut addr).cast(), &mut len) };
+    found == 0 && i32::from(addr.ss_family) == libc::AF_UNIX
+}
+
+/// Sends `bytes` through the socket `fd`, along with `fds`, without blocking.
+pub(crate) fn send(fd: RawFd, bytes: &[u8], fds: &[OwnedFd]) -> io::Result<usize> {
+    if fds.len() > MAX_FDS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Can't send more than {MAX_FDS} file descriptors at once"),
+        ));
+    }
+    let mut iov = libc::iovec {
+        iov_base: bytes.as_ptr() as *mut _,
+        iov_len: bytes.len(),
+    };
+    let mut control = [0u64; CONTROL_WORDS];
+    let payload = size_of_val(fds) as u32;
+    // SAFETY: `msghdr` is plain old data, for which all zeroes is a valid value.
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    // SAFETY: `CMSG_SPACE` only does arithmetic.
+    msg.msg_controllen = unsafe { libc::CMSG_SPACE(payload) } as _;
+    // SAFETY: `control` is aligned and big enough for a header and `MAX_FDS` descriptors, which
+    // is checked above, and `msg` points to it.
+    let sent = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(payload) as _;
+        let data = libc::CMSG_DATA(cmsg).cast::<RawFd>();
+        for (i, fd) in fds.iter().enumerate() {
+            data.add(i).write_unaligned(fd.as_raw_fd());
+        }
+        libc::sendmsg(fd, &msg, libc::MSG_DONTWAIT)
+    };
+    match sent {
+        -1 => Err(io::Error::last_os_error()),
+        sent => Ok(sent as usize),
+    }
+}
+
+/// Reads from the socket `fd` into `buf`, along with any descriptors sent with those bytes.
+pub(crate) fn recv(fd: RawFd, buf: &mut [u8]) -> io::Result<(usize, Vec<OwnedFd>)> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: buf.len(),
+    };
+    let mut control = [0u64; CONTROL_WORDS];
+    // SAFETY: `msghdr` is plain old data, for which all zeroes is a valid value.
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = size_of_val(&control) as _;
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let flags = libc::MSG_CMSG_CLOEXEC;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let flags = 0;
+    // SAFETY: `iov` and `control` outlive the call, and their lengths are correct.
+    let read = unsafe { libc::recvmsg(fd, &mut msg, flags) };
+    if read == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut fds = Vec::new();
+    // SAFETY: The kernel filled `control` with well formed control messages, whose
+    // `SCM_RIGHTS` data are descriptors now owned by us.
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg);
+                let len = (*cmsg).cmsg_len as usize - data.offset_from(cmsg.cast()) as usize;
+                for i in 0..len / size_of::<RawFd>() {
+                    let fd = data.cast::<RawFd>().add(i).read_unaligned();
+                    fds.push(OwnedFd::from_raw_fd(fd));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    if msg.msg_flags & libc::MSG_CTRUNC != 0 {
+        // The descriptors that did arrive are closed, as the frames they belong to can't be told.
+        return Err(io::Error::other(
+            "Some file descriptors were lost, as more were sent at once than can be received",
+        ));
+    }
+    Ok((read as usize, fds))
+}
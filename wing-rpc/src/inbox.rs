@@ -0,0 +1,208 @@
+use std::{
+    cell::RefCell,
+    io::{self, ErrorKind, Read},
+    time::Instant,
+};
+
+use serde::Deserialize;
+
+use crate::{Codec, Fds, Peer, Priority, Timeout, WirePacket, call, flags, outbox};
+
+/// The fields of a message envelope needed to route it, without its data.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Header {
+    #[serde(rename = "type")]
+    pub typ: String,
+    #[serde(default)]
+    pub call: Option<u64>,
+    #[serde(default)]
+    pub reply: Option<u64>,
+    #[serde(default)]
+    pub stream: Option<u64>,
+}
+
+/// A frame that was fully read, but not yet handed to the user.
+#[derive(Debug)]
+pub(crate) struct Frame {
+    pub flags: u8,
+    pub header: Header,
+    pub data: Vec<u8>,
+    /// File descriptors sent along, until the message is decoded.
+    fds: RefCell<Fds>,
+}
+
+impl Frame {
+    fn new(packet: WirePacket, codec: Codec) -> Self {
+        Self {
+            flags: packet.flags(),
+            // A broken envelope will fail again when the user decodes it, so it is reported then.
+            header: codec.decode(packet.data()).unwrap_or_default(),
+            data: packet.data().to_vec(),
+            fds: RefCell::default(),
+        }
+    }
+    /// Takes the file descriptors sent along this frame, to decode its message.
+    pub fn take_fds(&self) -> Fds {
+        self.fds.take()
+    }
+    pub fn priority(&self) -> Priority {
+        Priority::from_flags(self.flags)
+    }
+    pub fn is_control(&self) -> bool {
+        self.flags & flags::CONTROL != 0
+    }
+    /// Returns `true` for messages that are not part of a call.
+    pub fn is_plain(&self) -> bool {
+        !self.is_control()
+            && self.header.call.is_none()
+            && self.header.reply.is_none()
+            && self.header.stream.is_none()
+    }
+}
+
+impl Peer {
+    /// Returns the first frame accepted by `pick`, reading more frames if needed.
+    ///
+    /// Frames that are not picked are kept in order for later calls.
+    pub(crate) fn next_frame(
+        &mut self,
+        timeout: Timeout,
+        mut pick: impl FnMut(&Frame) -> bool,
+    ) -> io::Result<Option<Frame>> {
+        let start = Instant::now();
+        loop {
+            self.pump()?;
+            if let Some(i) = self.frames.iter().position(&mut pick) {
+                return Ok(self.frames.remove(i));
+            }
+            if self.split_frame()? {
+                continue;
+            }
+            let timeout = timeout.remaining(start);
+            match self.wait_readable(&timeout)? {
+                Some(false) => return Ok(None),
+                // Being woken doesn't always mean there is something to read, so wait again.
+                Some(true) => match self.read_some() {
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+                    read => read?,
+                },
+                None => try_harder!(timeout.retry_for(Self::read_some, self)),
+            };
+        }
+    }
+
+    /// Reads every frame that is available without blocking.
+    ///
+    /// If the peer doesn't know how to poll its reader, only frames that were already read are
+    /// considered.
+    pub(crate) fn poll_incoming(&mut self) -> io::Result<()> {
+        self.pump()?;
+        while self.split_frame()? {}
+        while self.wait_readable(&Timeout::DontBlock)? == Some(true) {
+            match self.read_some() {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                read => read?,
+            };
+            while self.split_frame()? {}
+        }
+        Ok(())
+    }
+
+    /// Waits up to `timeout` for something to read.
+    ///
+    /// Returns `None` if the peer doesn't know how to poll its reader.
+    #[cfg(unix)]
+    fn wait_readable(&self, timeout: &Timeout) -> io::Result<Option<bool>> {
+        let Some(fd) = self.fd.or(self.readable) else {
+            return Ok(None);
+        };
+        Ok(Some(timeout.poll(fd, libc::POLLIN)? != 0))
+    }
+
+    #[cfg(not(unix))]
+    fn wait_readable(&self, _timeout: &Timeout) -> io::Result<Option<bool>> {
+        Ok(None)
+    }
+
+    /// Reads whatever the reader has to offer into the incoming buffer.
+    pub(crate) fn read_some(&mut self) -> io::Result<usize> {
+        let start = self.incoming.len();
+        self.incoming.resize(start + self.config.read_buffer, 0);
+        let read = loop {
+            match self.read_into(start) {
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                read => break read,
+            }
+        };
+        self.incoming.truncate(start + *read.as_ref().unwrap_or(&0));
+        match read {
+            Ok(0) => Err(ErrorKind::UnexpectedEof.into()),
+            read => read,
+        }
+    }
+
+    /// Reads into the incoming buffer from `start`, keeping any file descriptors sent along.
+    #[cfg(unix)]
+    fn read_into(&mut self, start: usize) -> io::Result<usize> {
+        let Some(fd) = self.fd else {
+            return self.reader.read(&mut self.incoming[start..]);
+        };
+        let (read, fds) = crate::fd::recv(fd, &mut self.incoming[start..])?;
+        if !fds.is_empty() {
+            self.received_fds.push_back(fds);
+        }
+        Ok(read)
+    }
+
+    #[cfg(not(unix))]
+    fn read_into(&mut self, start: usize) -> io::Result<usize> {
+        self.reader.read(&mut self.incoming[start..])
+    }
+
+    /// Moves a complete frame from the incoming buffer into the frame queue.
+    ///
+    /// Returns `false` if there wasn't a complete frame yet. Frames over the size limit are
+    /// skipped and reported as an error, but the peer can still be used afterwards.
+    pub(crate) fn split_frame(&mut self) -> io::Result<bool> {
+        if self.discard > 0 {
+            let skipped = self.discard.min(self.incoming.len());
+            self.incoming.drain(..skipped);
+            self.discard -= skipped;
+            if self.discard > 0 {
+                return Ok(false);
+            }
+        }
+        let Some((header, _)) = self.incoming.split_first_chunk::<3>() else {
+            return Ok(false);
+        };
+        let len = u16::from_le_bytes([header[1], header[2]]) as usize;
+        if len > self.config.max_frame_size {
+            if header[0] & flags::FDS != 0 {
+                self.received_fds.pop_front();
+            }
+            self.incoming.drain(..WirePacket::HEADER_LEN);
+            self.discard = len;
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Received a frame of {len} bytes, over the limit of {}",
+                    self.config.max_frame_size
+                ),
+            ));
+        }
+        let Some(packet) = WirePacket::decode(&self.incoming) else {
+            return Ok(false);
+        };
+        let frame = Frame::new(packet, self.config.codec);
+        if frame.flags & flags::FDS != 0 {
+            // Descriptors arrive with the first bytes of their frame, so they were read already.
+            *frame.fds.borrow_mut() = self.received_fds.pop_front().unwrap_or_default();
+        }
+        self.incoming
+            .drain(..WirePacket::HEADER_LEN + frame.data.len());
+        if call::admit(self, &frame) && outbox::admit(self, &frame) {
+            self.frames.push_back(frame);
+        }
+        Ok(true)
+    }
+}
@@ -18,8 +18,14 @@
 //! ## Features
 //! - `client`: Enables the [`client`] module.
 //! - `server`: Enables the [`server`] module.
+//! - `tls`: Enables TLS connections through [`rustls`], see [`client::tls`] and [`server::TcpServer::bind_tls`].
+//! - `msgpack`: Enables [`Codec::MessagePack`].
+//! - `websocket`: Enables WebSocket connections through [`tungstenite`], see [`client::websocket`] and [`server::WsServer`].
+//! - `mio`: Implements [`mio::event::Source`] for [`Peer`].
+//! - `calloop`: Enables [`PeerSource`], to receive messages in a [`calloop`] event loop.
 use std::{
-    io::{self, BufReader, BufWriter, ErrorKind, Read, Write},
+    collections::{HashSet, VecDeque},
+    io::{self, ErrorKind, Read, Write},
     net::TcpStream,
     time::{Duration, Instant},
 };
@@ -29,15 +35,69 @@ use derive_more::From;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+// useful macro gotten from here: https://users.rust-lang.org/t/try-operator-for-result-option-t-e/74187/2
+// I just wish rust had this builtin as an operator or something
+macro_rules! try_harder {
+    ($x:expr) => {
+        match $x {
+            Ok(Some(value)) => value,
+            Ok(None) => return Ok(None),
+            Err(error) => return Err(error.into()),
+        }
+    };
+}
+
+pub mod auth;
+pub mod call;
 #[cfg(feature = "client")]
 pub mod client;
+#[cfg(unix)]
+pub mod fd;
 #[cfg(feature = "server")]
 pub mod server;
+#[cfg(target_os = "linux")]
+pub mod shm;
 
+mod builder;
+mod codec;
+mod dispatch;
+#[cfg(unix)]
+mod event_loop;
+mod inbox;
+mod outbox;
 mod sansio;
+mod shared;
+#[cfg(feature = "tls")]
+mod tls;
+mod typed;
+#[cfg(feature = "websocket")]
+mod ws;
+
+#[cfg(feature = "calloop")]
+pub use calloop;
+#[cfg(feature = "mio")]
+pub use mio;
+#[cfg(feature = "tls")]
+pub use rustls;
+#[cfg(feature = "websocket")]
+pub use tungstenite;
+
+use builder::Config;
+pub use builder::PeerBuilder;
+pub use codec::Codec;
+#[cfg(all(unix, feature = "calloop"))]
+pub use event_loop::PeerSource;
+use inbox::Frame;
+pub use outbox::{Overflow, Priority};
+use shared::SharedStream;
+pub use typed::TypedPeer;
 
+/// A single frame as it travels over the wire.
+///
+/// Each frame is made of a one byte `flags` field, followed by the little-endian `u16` length of
+/// the payload and the payload itself.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
-struct WirePacket<'a> {
+pub struct WirePacket<'a> {
     flags: u8,
     len: u16,
     data: &'a [u8],
@@ -51,6 +111,14 @@ impl<'a> WirePacket<'a> {
             data,
         }
     }
+    /// The flags this frame was sent with.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+    /// The payload of this frame, usually a JSON encoded message.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
 }
 
 /// This crate's [`std::error::Error`] type.
@@ -60,19 +128,84 @@ pub enum Error {
     Io(io::Error),
     #[error(transparent)]
     Serde(serde_json::Error),
+    #[cfg(feature = "msgpack")]
+    #[error(transparent)]
+    MsgPackEncode(rmp_serde::encode::Error),
+    #[cfg(feature = "msgpack")]
+    #[error(transparent)]
+    MsgPackDecode(rmp_serde::decode::Error),
+    #[error("Peer failed to authenticate")]
+    AuthFailed,
+    #[error("Too many messages are waiting to be sent")]
+    WouldOverflow,
+    #[error("The stream was cancelled by the other peer")]
+    Cancelled,
+    #[error("The other peer failed to answer the call: {0}")]
+    Remote(String),
+    #[error("Was expecting a {expected} message, but received {got} instead")]
+    #[from(ignore)]
+    UnexpectedMessage { expected: &'static str, got: String },
 }
 
+/// Bits of [`WirePacket::flags`].
+pub(crate) mod flags {
+    /// The frame is used by the runtime itself and is never handed to the user.
+    pub const CONTROL: u8 = 0b0000_0001;
+    /// File descriptors were sent along the frame, see [`crate::fd`].
+    pub const FDS: u8 = 0b0000_0010;
+    /// The [`crate::Priority`] of the frame.
+    pub const PRIORITY: u8 = 0b0000_1100;
+}
+
+/// File descriptors sent along a frame.
+#[cfg(unix)]
+type Fds = Vec<std::os::fd::OwnedFd>;
+/// File descriptors can't be sent on this platform, so there are never any.
+#[cfg(not(unix))]
+type Fds = Vec<std::convert::Infallible>;
+
 /// An active connection to a `Wing RPC` peer.
 pub struct Peer {
     reader: Box<dyn Read + Send>,
     writer: Box<dyn Write + Send>,
+    /// Scratch memory for encoding outgoing messages.
     buf: Vec<u8>,
+    /// Bytes that were read, but don't make up a whole frame yet.
+    incoming: Vec<u8>,
+    /// How many bytes of an oversized frame are still to be skipped.
+    discard: usize,
+    /// File descriptors that were read, one entry per frame they were sent with.
+    received_fds: VecDeque<Fds>,
+    /// Frames that were read, but not yet asked for.
+    frames: VecDeque<Frame>,
+    /// The data of the last frame handed to the user, which received messages borrow from.
+    current: Vec<u8>,
+    next_call: u64,
+    /// Our calls whose streams we are no longer interested in.
+    cancelled: HashSet<u64>,
+    /// Calls of the other peer whose streams it is no longer interested in.
+    cancelled_by_peer: HashSet<u64>,
+    handlers: dispatch::Handlers,
+    outbox: outbox::Outbox,
+    config: Config,
+    /// Lets us check whether there's something to read without blocking.
+    #[cfg(unix)]
+    fd: Option<std::os::fd::RawFd>,
+    /// Polled instead of [`Peer::fd`] by transports that aren't a socket.
+    #[cfg(unix)]
+    readable: Option<std::os::fd::RawFd>,
 }
 
 #[derive(Serialize, Deserialize)]
 struct WrappedData<T> {
     #[serde(rename = "type")]
     typ: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    call: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reply: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    stream: Option<u64>,
     data: T,
 }
 
@@ -80,11 +213,24 @@ impl<'a, T: Message<'a>> WrappedData<T> {
     pub fn wrap(data: T) -> Self {
         Self {
             typ: T::NAME.into(),
+            call: None,
+            reply: None,
+            stream: None,
             data,
         }
     }
 }
 
+/// Serializes `message` into the payload of a frame.
+pub(crate) fn encode<'a>(codec: Codec, message: impl Message<'a>) -> WingResult<Vec<u8>> {
+    codec.encode(&WrappedData::wrap(message))
+}
+
+/// The error returned when a blocking operation took longer than [`PeerBuilder::recv_timeout`].
+pub(crate) fn timed_out() -> Error {
+    Error::Io(ErrorKind::TimedOut.into())
+}
+
 /// A trait for types that can be sent and received through a [`Peer`].
 pub trait Message<'a>: Serialize + Deserialize<'a> {
     const NAME: &'static str;
@@ -115,6 +261,45 @@ impl From<Duration> for Timeout {
 }
 
 impl Timeout {
+    /// What is left of this timeout, if it started counting at `start`.
+    fn remaining(&self, start: Instant) -> Self {
+        match self {
+            Timeout::WaitFor(duration) => {
+                Timeout::WaitFor(duration.saturating_sub(start.elapsed()))
+            }
+            timeout => timeout.clone(),
+        }
+    }
+    /// Waits up to this timeout for any of `events` to happen on `fd`.
+    ///
+    /// Returns the events that happened, or none if the time ran out. Being interrupted by a
+    /// signal doesn't count, so polling goes on for whatever time is left.
+    #[cfg(unix)]
+    pub(crate) fn poll(&self, fd: std::os::fd::RawFd, events: i16) -> io::Result<i16> {
+        let start = Instant::now();
+        loop {
+            let timeout = match self.remaining(start) {
+                Timeout::Block => -1,
+                Timeout::DontBlock => 0,
+                Timeout::WaitFor(duration) => {
+                    i32::try_from(duration.as_nanos().div_ceil(1_000_000)).unwrap_or(i32::MAX)
+                }
+            };
+            let mut pollfd = libc::pollfd {
+                fd,
+                events,
+                revents: 0,
+            };
+            // SAFETY: `pollfd` is a valid array of exactly one element.
+            match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+                -1 => match io::Error::last_os_error() {
+                    e if e.kind() == ErrorKind::Interrupted => continue,
+                    e => return Err(e),
+                },
+                _ => return Ok(pollfd.revents),
+            }
+        }
+    }
     // fn retry_for2<'a, T: 'a>(
     //     &self,
     //     mut retry: impl FnMut() -> io::Result<T>,
@@ -143,7 +328,7 @@ impl Timeout {
         let timeout = match self {
             Timeout::Block => Duration::MAX,
             Timeout::DontBlock => Duration::ZERO,
-            Timeout::WaitFor(duration) => duration.clone(),
+            Timeout::WaitFor(duration) => *duration,
         };
         let now = Instant::now();
         let time = Duration::from_millis(1);
@@ -167,72 +352,197 @@ impl Timeout {
     }
 }
 
-// useful macro gotten from here: https://users.rust-lang.org/t/try-operator-for-result-option-t-e/74187/2
-// I just wish rust had this builtin as an operator or something
-macro_rules! try_harder {
-    ($x:expr) => {
-        match $x {
-            Ok(Some(value)) => value,
-            Ok(None) => return Ok(None),
-            Err(error) => return Err(error.into()),
-        }
-    };
-}
-
 impl Peer {
     /// Creates a new [`Peer`] which sends messages to `write` and receives from `read`.
     ///
     /// This is the most general API, intended to allow every use case, but it's also a bit verbose,
     /// which is why a simpler api for sockets is provided: [`Peer::from_socket`].
     pub fn new(read: impl Read + Send + 'static, write: impl Write + Send + 'static) -> Self {
+        Self::with_config(Box::new(read), Box::new(write), Config::default())
+    }
+    /// Returns a [`PeerBuilder`], to create peers with custom settings.
+    pub fn builder() -> PeerBuilder {
+        PeerBuilder::new()
+    }
+    fn with_config(
+        reader: Box<dyn Read + Send>,
+        writer: Box<dyn Write + Send>,
+        config: Config,
+    ) -> Self {
         Self {
-            reader: Box::new(read),
-            writer: Box::new(write),
+            reader,
+            writer,
             buf: Vec::new(),
+            incoming: Vec::new(),
+            discard: 0,
+            received_fds: VecDeque::new(),
+            frames: VecDeque::new(),
+            current: Vec::new(),
+            next_call: 0,
+            cancelled: HashSet::new(),
+            cancelled_by_peer: HashSet::new(),
+            handlers: dispatch::Handlers::new(),
+            outbox: outbox::Outbox::default(),
+            config,
+            #[cfg(unix)]
+            fd: None,
+            #[cfg(unix)]
+            readable: None,
+        }
+    }
+    #[cfg(unix)]
+    fn with_fd(self, fd: std::os::fd::RawFd) -> Self {
+        Self {
+            fd: Some(fd),
+            ..self
+        }
+    }
+    #[cfg(target_os = "linux")]
+    fn with_readable(self, readable: std::os::fd::RawFd) -> Self {
+        Self {
+            readable: Some(readable),
+            ..self
         }
     }
     /// Creates a new [`Peer`] from a TCP connection.
     ///
     /// Check out [`std::net::TcpStream`] and [`std::net::TcpListener`] on how to create them.
     pub fn from_socket(con: TcpStream) -> Self {
-        let read = Box::new(BufReader::new(con.try_clone().unwrap()));
-        let write = Box::new(BufWriter::new(con));
-        Self::new(read, write)
+        PeerBuilder::new().socket(con).unwrap()
+    }
+    /// Creates a new [`Peer`] from a Unix domain socket connection.
+    ///
+    /// Check out [`std::os::unix::net::UnixStream`] and [`std::os::unix::net::UnixListener`] on how to create them.
+    #[cfg(unix)]
+    pub fn from_unix_socket(con: std::os::unix::net::UnixStream) -> Self {
+        PeerBuilder::new().unix_socket(con).unwrap()
+    }
+    /// Creates a new [`Peer`] from a single stream that is used for both sending and receiving.
+    ///
+    /// This is useful for streams that can't be cloned, such as TLS connections.
+    pub fn from_stream(stream: impl Read + Write + Send + 'static) -> Self {
+        PeerBuilder::new().stream(stream)
     }
     pub fn send<'a>(&mut self, message: impl Message<'a>) -> Result<(), Error> {
-        let data = serde_json::to_vec(&WrappedData::wrap(message))?;
-        let header = WirePacket::new(0, data.as_slice());
-        header.write(&mut self.buf, &mut self.writer)?;
-        self.writer.flush()?;
-        Ok(())
+        self.send_or_feed(message, 0, true)
+    }
+    /// Sends `message` ahead of any queued message of a lower [`Priority`].
+    ///
+    /// The priority travels along the message, so the other peer can handle it first with
+    /// [`Self::recv_prioritized`].
+    /// ```no_run
+    /// # use wing_rpc::{Message, Peer, Priority};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Stop;
+    /// # impl Message<'_> for Stop { const NAME: &'static str = "Stop"; }
+    /// # let mut peer: Peer = todo!();
+    /// peer.send_with_priority(Stop, Priority::Urgent).unwrap();
+    /// ```
+    pub fn send_with_priority<'a>(
+        &mut self,
+        message: impl Message<'a>,
+        priority: Priority,
+    ) -> Result<(), Error> {
+        self.send_or_feed(message, priority.flags(), true)
+    }
+    /// Sends `message` without flushing it, so it may stay buffered until [`Self::flush`].
+    ///
+    /// Feeding many messages and flushing once is much cheaper than sending each of them, as they
+    /// are written together.
+    pub fn feed<'a>(&mut self, message: impl Message<'a>) -> Result<(), Error> {
+        self.send_or_feed(message, 0, false)
+    }
+    /// Sends every message in `messages`, and then flushes them all at once.
+    /// ```no_run
+    /// # use wing_rpc::{Message, Peer};
+    /// # #[derive(serde::Serialize, serde::Deserialize)]
+    /// # struct Point { x: i32, y: i32 }
+    /// # impl Message<'_> for Point { const NAME: &'static str = "Point"; }
+    /// # let mut peer: Peer = todo!();
+    /// let points = (0..1000).map(|x| Point { x, y: x * 2 });
+    /// peer.send_batch(points).unwrap();
+    /// ```
+    pub fn send_batch<'a, M: Message<'a>>(
+        &mut self,
+        messages: impl IntoIterator<Item = M>,
+    ) -> Result<(), Error> {
+        for message in messages {
+            self.feed(message)?;
+        }
+        self.flush()
+    }
+    fn send_or_feed<'a>(
+        &mut self,
+        message: impl Message<'a>,
+        flags: u8,
+        flush: bool,
+    ) -> Result<(), Error> {
+        // Reuse the same memory for every message.
+        let mut data = std::mem::take(&mut self.buf);
+        data.clear();
+        let sent = self
+            .config
+            .codec
+            .encode_into(&mut data, &WrappedData::wrap(message))
+            .and_then(|fds| self.write_droppable_frame(flags, &data, fds, flush));
+        self.buf = data;
+        sent
+    }
+    fn send_flagged<'a>(&mut self, flags: u8, message: impl Message<'a>) -> Result<(), Error> {
+        let data = encode(self.config.codec, message)?;
+        self.write_frame(flags, &data, Fds::new())
     }
     /// Waits for a message of `T` to arrive.
     ///
-    /// This operation blocks the current thread while waiting for a message.
+    /// This operation blocks the current thread while waiting for a message, unless a
+    /// [`PeerBuilder::recv_timeout`] was set.
     /// Check out [`Self::try_recv`] for more control over this.
     pub fn recv<'a, T: Message<'a>>(&'a mut self) -> Result<T, Error> {
-        self.try_recv(Timeout::Block)
-            .map(|h| h.expect("Blocked operation returned None"))
+        let timeout = self.config.recv_timeout.clone();
+        self.try_recv(timeout)?.ok_or_else(timed_out)
     }
     /// Waits for a message of `T` to arrive with a Timeout.
     ///
     /// - [`Timeout::Block`]:
-    ///     Blocks the current thread and always returns `Some`. This is the same as [`Self::recv`].
+    ///   Blocks the current thread and always returns `Some`. This is the same as [`Self::recv`].
     /// - [`Timeout::DontBlock`]:
-    ///     Returns a message if one is available, otherwise, returns `None`.
+    ///   Returns a message if one is available, otherwise, returns `None`.
     /// - [`Timeout::WaitFor`]:
-    ///     Same as [`Timeout::DontBlock`], except that it keeps retrying
-    ///     for the [`Duration`] specified and returns `None` if a message didn't arrive in that time.
+    ///   Same as [`Timeout::DontBlock`], except that it keeps retrying
+    ///   for the [`Duration`] specified and returns `None` if a message didn't arrive in that time.
     ///
     pub fn try_recv<'a, T: Message<'a>>(
         &'a mut self,
         timeout: Timeout,
     ) -> Result<Option<T>, Error> {
-        let wire = try_harder!(timeout.retry_for(
-            |this| WirePacket::read(&mut this.buf, &mut this.reader),
-            self
-        ));
-        let msg = serde_json::from_slice::<WrappedData<T>>(wire.data)?;
-        Ok(Some(msg.data))
+        let frame = try_harder!(self.next_frame_serving(timeout, Frame::is_plain));
+        self.decode_frame(frame).map(Some)
+    }
+    /// Waits for a message of `T`, picking the one with the highest [`Priority`] among those that
+    /// already arrived. Messages of the same priority are received in order.
+    pub fn recv_prioritized<'a, T: Message<'a>>(&'a mut self) -> Result<T, Error> {
+        let timeout = self.config.recv_timeout.clone();
+        self.try_recv_prioritized(timeout)?.ok_or_else(timed_out)
+    }
+    /// Same as [`Self::recv_prioritized`], but with a timeout like [`Self::try_recv`].
+    pub fn try_recv_prioritized<'a, T: Message<'a>>(
+        &'a mut self,
+        timeout: Timeout,
+    ) -> Result<Option<T>, Error> {
+        self.poll_incoming()?;
+        let highest = (self.frames.iter())
+            .filter(|frame| frame.is_plain())
+            .map(Frame::priority)
+            .max();
+        let frame = try_harder!(self.next_frame_serving(timeout, |frame| {
+            frame.is_plain() && highest.is_none_or(|highest| frame.priority() >= highest)
+        }));
+        self.decode_frame(frame).map(Some)
+    }
+    fn decode_frame<'a, T: Message<'a>>(&'a mut self, frame: Frame) -> Result<T, Error> {
+        let fds = frame.take_fds();
+        self.current = frame.data;
+        let msg = (self.config.codec).decode_with::<WrappedData<T>>(&self.current, fds)?;
+        Ok(msg.data)
     }
 }
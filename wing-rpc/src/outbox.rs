@@ -0,0 +1,409 @@
+//! Queued sending, so a slow peer can't stall us for an unbounded time.
+use std::{
+    collections::VecDeque,
+    io::{self, ErrorKind, Write},
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    Error, Fds, Peer, Timeout, WingResult, WirePacket, call::Ids, flags, inbox::Frame, timed_out,
+};
+
+/// Sent by the receiver to let the sender know how many more messages it is willing to take.
+pub(crate) const CREDIT: &str = "Credit";
+
+/// What to do when a message is sent while the send queue is full.
+///
+/// Only messages sent with [`Peer::send`] and its variants can be dropped. Calls, replies and
+/// frames used by the runtime itself are never dropped, and count against the limit like any
+/// other frame. See [`Peer::set_send_queue`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overflow {
+    /// Wait until the other peer catches up, for at most [`crate::PeerBuilder::send_timeout`].
+    #[default]
+    Block,
+    /// Discard the oldest message of the lowest [`Priority`] that wasn't sent yet to make room.
+    /// If none of the queued frames can be dropped, wait like [`Overflow::Block`].
+    DropOldest,
+    /// Discard the message being sent. Frames that can't be dropped are queued anyway, even past
+    /// the limit.
+    DropNewest,
+    /// Fail with [`Error::WouldOverflow`].
+    Fail,
+}
+
+/// How urgently a message must reach the other peer, see [`Peer::send_with_priority`].
+///
+/// Queued messages are written highest priority first, and in order within each priority.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    #[default]
+    Normal,
+    High,
+    /// Also skips [`Overflow`], so it is queued even when the send queue is full.
+    Urgent,
+}
+
+impl Priority {
+    const SHIFT: u32 = flags::PRIORITY.trailing_zeros();
+
+    pub(crate) fn from_flags(flags: u8) -> Self {
+        match (flags & flags::PRIORITY) >> Self::SHIFT {
+            0 => Self::Normal,
+            1 => Self::High,
+            _ => Self::Urgent,
+        }
+    }
+    pub(crate) fn flags(self) -> u8 {
+        (self as u8) << Self::SHIFT
+    }
+}
+
+struct Outgoing {
+    /// The whole frame, header included.
+    bytes: Vec<u8>,
+    /// Only messages sent with [`Peer::send`] may be dropped, everything else is part of a call or
+    /// needed by the runtime.
+    droppable: bool,
+    /// Control frames don't use up credit.
+    control: bool,
+    /// File descriptors to send along the first bytes of the frame.
+    fds: Fds,
+    priority: Priority,
+}
+
+#[derive(Default)]
+pub(crate) struct Outbox {
+    /// How many frames can be waiting before [`Outbox::overflow`] kicks in. `None` means frames
+    /// are written as soon as they are sent.
+    high_water: Option<usize>,
+    overflow: Overflow,
+    queue: VecDeque<Outgoing>,
+    /// How much of the first frame in the queue was already written.
+    written: usize,
+    /// How many more messages the other peer is willing to take, if it ever granted credit.
+    credit: Option<u64>,
+}
+
+impl Outbox {
+    fn is_full(&self) -> bool {
+        self.high_water
+            .is_some_and(|high_water| self.queue.len() >= high_water)
+    }
+    /// Returns `true` if the first frame in the queue may be written now.
+    fn can_write(&self) -> bool {
+        self.queue
+            .front()
+            .is_some_and(|out| out.control || self.credit != Some(0))
+    }
+    /// Queues `out` after every frame of the same or higher priority.
+    fn enqueue(&mut self, out: Outgoing) {
+        // A frame that was partially written must be finished, or the stream would be corrupted.
+        let skip = usize::from(self.written > 0);
+        let at = (self.queue.iter().skip(skip))
+            .position(|queued| queued.priority < out.priority)
+            .map_or(self.queue.len(), |i| i + skip);
+        self.queue.insert(at, out);
+    }
+    /// Drops the oldest frame of the lowest priority that can be dropped, returning `false` if
+    /// there is none.
+    fn drop_oldest(&mut self) -> bool {
+        // A frame that was partially written must be finished, or the stream would be corrupted.
+        let skip = usize::from(self.written > 0);
+        let droppable = || self.queue.iter().skip(skip).filter(|out| out.droppable);
+        let Some(lowest) = droppable().map(|out| out.priority).min() else {
+            return false;
+        };
+        // Frames of the same priority are queued in order, so the first one is the oldest.
+        let oldest =
+            (self.queue.iter().skip(skip)).position(|out| out.droppable && out.priority == lowest);
+        oldest.and_then(|i| self.queue.remove(i + skip)).is_some()
+    }
+}
+
+/// Takes note of credit granted by the other peer as soon as it is read.
+///
+/// Returns `false` if `frame` has been fully dealt with and must not be queued.
+pub(crate) fn admit(peer: &mut Peer, frame: &Frame) -> bool {
+    if !frame.is_control() || frame.header.typ != CREDIT {
+        return true;
+    }
+    #[derive(Deserialize)]
+    struct Credit {
+        data: u32,
+    }
+    if let Ok(Credit { data }) = peer.config.codec.decode(&frame.data) {
+        let credit = peer.outbox.credit.get_or_insert(0);
+        *credit = credit.saturating_add(data.into());
+    }
+    false
+}
+
+impl Peer {
+    /// Queues outgoing messages instead of waiting for each of them to be written.
+    ///
+    /// Up to `high_water` messages can be waiting to be written, after which `overflow` decides
+    /// what happens to new ones. Queued messages are written whenever this peer sends or receives
+    /// something, or when calling [`Peer::flush`].
+    ///
+    /// Only sockets can be written to without blocking, other streams are written to as usual.
+    pub fn set_send_queue(&mut self, high_water: usize, overflow: Overflow) {
+        self.outbox.high_water = Some(high_water);
+        self.outbox.overflow = overflow;
+    }
+
+    /// How many messages are waiting to be written.
+    pub fn queued(&self) -> usize {
+        self.outbox.queue.len()
+    }
+
+    /// How many more messages the other peer is willing to take, if it uses [`Peer::grant`].
+    ///
+    /// Messages sent while there is no credit wait in the send queue until there is.
+    pub fn credit(&self) -> Option<u64> {
+        self.outbox.credit
+    }
+
+    /// Allows the other peer to send `credit` more messages.
+    ///
+    /// Peers can send as much as they like until they are first granted credit. From then on,
+    /// the receiver is expected to grant more credit as it handles messages, which lets the
+    /// sender adapt its rate.
+    pub fn grant(&mut self, credit: u32) -> WingResult<()> {
+        self.send_envelope(flags::CONTROL, CREDIT, Ids::default(), credit)
+    }
+
+    /// Waits until every queued or buffered message is written.
+    pub fn flush(&mut self) -> WingResult<()> {
+        self.drain_until(|outbox| outbox.queue.is_empty())?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Sends an already encoded message, as returned by [`crate::encode`], along with `fds`.
+    pub(crate) fn write_frame(&mut self, flags: u8, data: &[u8], fds: Fds) -> WingResult<()> {
+        self.queue_frame(flags, data, fds, false, true)
+    }
+
+    /// Same as [`Peer::write_frame`], but the message may be dropped by [`Overflow`].
+    ///
+    /// Unless `flush` is set, the frame may stay buffered until [`Peer::flush`].
+    pub(crate) fn write_droppable_frame(
+        &mut self,
+        flags: u8,
+        data: &[u8],
+        fds: Fds,
+        flush: bool,
+    ) -> WingResult<()> {
+        self.queue_frame(flags, data, fds, true, flush)
+    }
+
+    fn queue_frame(
+        &mut self,
+        flags: u8,
+        data: &[u8],
+        fds: Fds,
+        droppable: bool,
+        flush: bool,
+    ) -> WingResult<()> {
+        self.config.check_frame_size(data.len())?;
+        if !fds.is_empty() && !self.can_send_fds() {
+            return Err(Error::Io(io::Error::new(
+                ErrorKind::Unsupported,
+                "File descriptors can only be sent over Unix sockets",
+            )));
+        }
+        let control = flags & flags::CONTROL != 0;
+        let priority = Priority::from_flags(flags);
+        let flags = match fds.is_empty() {
+            true => flags,
+            false => flags | flags::FDS,
+        };
+        let outbox = &self.outbox;
+        // Descriptors can't go through the writer, so they always take the queue.
+        if fds.is_empty()
+            && outbox.high_water.is_none()
+            && outbox.queue.is_empty()
+            && (control || outbox.credit != Some(0))
+        {
+            WirePacket::new(flags, data).write_vectored(&mut self.writer)?;
+            if flush && self.config.auto_flush {
+                self.writer.flush()?;
+            }
+            self.use_credit(control);
+            return Ok(());
+        }
+        self.pump()?;
+        if self.outbox.is_full() && priority != Priority::Urgent {
+            match self.outbox.overflow {
+                Overflow::Block => self.drain_until(|outbox| !outbox.is_full())?,
+                Overflow::DropOldest => {
+                    if !self.outbox.drop_oldest() {
+                        self.drain_until(|outbox| !outbox.is_full())?
+                    }
+                }
+                Overflow::DropNewest if droppable => return Ok(()),
+                Overflow::DropNewest => {}
+                Overflow::Fail => return Err(Error::WouldOverflow),
+            }
+        }
+        let mut bytes = Vec::with_capacity(WirePacket::HEADER_LEN + data.len());
+        WirePacket::new(flags, data).write_sansio(&mut bytes);
+        self.outbox.enqueue(Outgoing {
+            bytes,
+            droppable,
+            control,
+            fds,
+            priority,
+        });
+        if self.outbox.high_water.is_none() {
+            return self.drain_until(|outbox| outbox.queue.is_empty());
+        }
+        self.pump()?;
+        Ok(())
+    }
+
+    fn use_credit(&mut self, control: bool) {
+        if let Some(credit) = &mut self.outbox.credit
+            && !control
+        {
+            *credit = credit.saturating_sub(1);
+        }
+    }
+
+    /// Writes as many queued frames as possible without blocking.
+    pub(crate) fn pump(&mut self) -> io::Result<()> {
+        if self.outbox.can_write() {
+            // Anything buffered before the queue was used must go first.
+            self.writer.flush()?;
+        }
+        while self.outbox.can_write() {
+            let out = self.outbox.queue.pop_front().expect("Queue isn't empty");
+            let written = match self.outbox.written {
+                0 if !out.fds.is_empty() => self.send_fds(&out.bytes, &out.fds),
+                written => self.write_some(&out.bytes[written..]),
+            };
+            match written {
+                Ok(written) => self.outbox.written += written,
+                Err(e) => {
+                    self.outbox.queue.push_front(out);
+                    match e.kind() {
+                        ErrorKind::WouldBlock => break,
+                        ErrorKind::Interrupted => continue,
+                        _ => return Err(e),
+                    }
+                }
+            }
+            if self.outbox.written == out.bytes.len() {
+                self.outbox.written = 0;
+                self.use_credit(out.control);
+            } else {
+                self.outbox.queue.push_front(out);
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes queued frames and reads incoming ones until `done` returns `true`.
+    ///
+    /// Reading is needed to notice new credit, and to keep the other peer from getting stuck
+    /// writing to us while we wait for it. Fails with [`io::ErrorKind::TimedOut`] once the
+    /// [`crate::PeerBuilder::send_timeout`] is over.
+    fn drain_until(&mut self, done: impl Fn(&Outbox) -> bool) -> WingResult<()> {
+        let start = Instant::now();
+        loop {
+            self.pump()?;
+            if done(&self.outbox) {
+                return Ok(());
+            }
+            match self.wait_io(&self.config.send_timeout.remaining(start))? {
+                None => return Err(timed_out()),
+                Some(false) => {}
+                Some(true) => match self.read_some() {
+                    Ok(_) => while self.split_frame()? {},
+                    Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(1))
+                    }
+                    Err(e) => return Err(e.into()),
+                },
+            }
+        }
+    }
+
+    /// Writes some of `bytes` without blocking, if possible.
+    #[cfg(unix)]
+    fn write_some(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let Some(fd) = self.fd else {
+            self.writer.write_all(bytes)?;
+            if self.config.auto_flush {
+                self.writer.flush()?;
+            }
+            return Ok(bytes.len());
+        };
+        // SAFETY: `bytes` is valid for reads of `bytes.len()` bytes.
+        let written =
+            unsafe { libc::send(fd, bytes.as_ptr().cast(), bytes.len(), libc::MSG_DONTWAIT) };
+        match written {
+            -1 => Err(io::Error::last_os_error()),
+            written => Ok(written as usize),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn write_some(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.writer.write_all(bytes)?;
+        if self.config.auto_flush {
+            self.writer.flush()?;
+        }
+        Ok(bytes.len())
+    }
+
+    #[cfg(unix)]
+    fn can_send_fds(&self) -> bool {
+        self.fd.is_some_and(crate::fd::is_unix_socket)
+    }
+
+    #[cfg(not(unix))]
+    fn can_send_fds(&self) -> bool {
+        false
+    }
+
+    /// Writes some of `bytes` along with `fds`, without blocking.
+    #[cfg(unix)]
+    fn send_fds(&mut self, bytes: &[u8], fds: &Fds) -> io::Result<usize> {
+        let fd = self.fd.expect("Checked by Peer::can_send_fds");
+        crate::fd::send(fd, bytes, fds)
+    }
+
+    #[cfg(not(unix))]
+    fn send_fds(&mut self, _: &[u8], fds: &Fds) -> io::Result<usize> {
+        match fds[0] {}
+    }
+
+    /// Waits up to `timeout` until we can either read, or write the first queued frame.
+    ///
+    /// Returns `true` if there's something to read, or `None` if the time ran out.
+    #[cfg(unix)]
+    fn wait_io(&self, timeout: &Timeout) -> io::Result<Option<bool>> {
+        let Some(fd) = self.fd else {
+            // Writes block until they are done, so only credit can be missing.
+            return Ok(Some(true));
+        };
+        let mut events = libc::POLLIN;
+        if self.outbox.can_write() {
+            events |= libc::POLLOUT;
+        }
+        match timeout.poll(fd, events)? {
+            0 => Ok(None),
+            // Errors and hangups are also reported by reading.
+            revents => Ok(Some(revents & libc::POLLOUT == 0)),
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn wait_io(&self, _timeout: &Timeout) -> io::Result<Option<bool>> {
+        Ok(Some(true))
+    }
+}
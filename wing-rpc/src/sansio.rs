@@ -49,7 +49,22 @@ impl<'a> WirePacket<'a> {
         }
     }
 
-    fn write_sansio(&self, buf: &mut Vec<u8>) {
+    /// Size of the `flags` and `len` fields that come before the payload.
+    pub const HEADER_LEN: usize = 3;
+
+    /// Decodes a packet from the start of `bytes`, without doing any IO.
+    ///
+    /// Returns `None` if `bytes` doesn't hold a whole packet yet.
+    /// The packet takes up [`Self::HEADER_LEN`] plus `data().len()` bytes of `bytes`.
+    pub fn decode(bytes: &'a [u8]) -> Option<Self> {
+        let (header, rest) = bytes.split_first_chunk::<3>()?;
+        let [flags, len @ ..] = *header;
+        let len = u16::from_le_bytes(len);
+        let data = rest.get(..len as usize)?;
+        Some(Self { flags, len, data })
+    }
+
+    pub(crate) fn write_sansio(&self, buf: &mut Vec<u8>) {
         buf.push(self.flags);
         buf.extend_from_slice(&self.len.to_le_bytes());
         buf.extend_from_slice(self.data);
@@ -90,9 +105,31 @@ impl<'a> WirePacket<'a> {
     pub fn write(&self, buf: &mut Vec<u8>, write: &mut dyn io::Write) -> io::Result<()> {
         buf.clear();
 
-        buf.reserve(128usize.saturating_sub(buf.capacity()));
+        buf.reserve(Self::HEADER_LEN + self.data.len());
         self.write_sansio(buf);
         write.write_all(buf.as_slice())?;
         Ok(())
     }
+
+    /// Same as [`Self::write`], but hands the header and the payload to `write` separately
+    /// instead of joining them in `buf` first.
+    ///
+    /// Peers write through a [`io::BufWriter`], which still copies frames that fit its buffer,
+    /// so a batch goes out in a single write. Only payloads larger than the buffer skip it and
+    /// reach the stream without being copied.
+    pub(crate) fn write_vectored(&self, write: &mut dyn io::Write) -> io::Result<()> {
+        let [lo, hi] = self.len.to_le_bytes();
+        let header = [self.flags, lo, hi];
+        let mut bufs = [io::IoSlice::new(&header), io::IoSlice::new(self.data)];
+        let mut bufs = &mut bufs[..];
+        while !bufs.is_empty() {
+            match write.write_vectored(bufs) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(written) => io::IoSlice::advance_slices(&mut bufs, written),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 }
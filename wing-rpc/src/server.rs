@@ -1,10 +1,17 @@
 //! This module provides very lightweight, small utilities to quickly create servers.
+#[cfg(feature = "tls")]
+use std::sync::Arc;
 use std::{
     io,
-    net::{TcpListener, ToSocketAddrs},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    time::Duration,
 };
 
-use crate::Peer;
+use crate::{Peer, PeerBuilder, WingResult, auth::Secret};
+
+/// How long accepted peers have to complete their handshakes, unless changed with
+/// [`TcpServer::with_handshake_timeout`].
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
 
 /// Possible modes of operation for sockets.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -23,31 +30,241 @@ pub enum Mode {
 /// let peer = server.accept(Mode::Blocking).expect("Failed to accept peer");
 /// ```
 #[derive(Debug)]
-pub struct TcpServer(TcpListener);
+pub struct TcpServer {
+    listener: TcpListener,
+    #[cfg(feature = "tls")]
+    tls: Option<Arc<rustls::ServerConfig>>,
+    secret: Option<Secret>,
+    builder: PeerBuilder,
+    handshake_timeout: Duration,
+}
 
 impl TcpServer {
     /// Creates a [`TcpServer`] that waits for connections on `addr`
     ///
     /// Use [`TcpServer::accept`] to turn incoming connections into a peer.
     pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
-        Ok(Self(TcpListener::bind(addr)?))
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            #[cfg(feature = "tls")]
+            tls: None,
+            secret: None,
+            builder: PeerBuilder::new(),
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        })
+    }
+
+    /// Creates a [`TcpServer`] that waits for TLS connections on `addr`.
+    ///
+    /// Every accepted peer must complete a TLS handshake using `config`.
+    /// To require client certificates, build `config` with a client certificate verifier:
+    /// ```no_run
+    /// use std::sync::Arc;
+    /// use wing_rpc::rustls::{self, server::WebPkiClientVerifier};
+    /// use wing_rpc::server::{Mode, TcpServer};
+    ///
+    /// # let (roots, certs, key): (rustls::RootCertStore, Vec<_>, _) = todo!();
+    /// let verifier = WebPkiClientVerifier::builder(Arc::new(roots)).build().unwrap();
+    /// let config = rustls::ServerConfig::builder()
+    ///     .with_client_cert_verifier(verifier)
+    ///     .with_single_cert(certs, key)
+    ///     .unwrap();
+    /// let server = TcpServer::bind_tls("localhost:6000", Arc::new(config)).unwrap();
+    /// let peer = server.accept(Mode::Blocking).unwrap();
+    /// ```
+    #[cfg(feature = "tls")]
+    pub fn bind_tls(
+        addr: impl ToSocketAddrs,
+        config: Arc<rustls::ServerConfig>,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            tls: Some(config),
+            secret: None,
+            builder: PeerBuilder::new(),
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        })
+    }
+
+    /// Requires every peer to prove it knows `secret` before it is accepted.
+    ///
+    /// Peers that fail are rejected by [`TcpServer::accept`] with [`crate::Error::AuthFailed`].
+    /// See [`crate::auth`] for details.
+    /// ```no_run
+    /// use wing_rpc::{auth::Secret, server::{Mode, TcpServer}};
+    ///
+    /// let server = TcpServer::bind("localhost:6000")
+    ///     .expect("Failed to bind")
+    ///     .with_auth(Secret::new("hunter2"));
+    /// let peer = server.accept(Mode::Blocking).expect("Failed to accept peer");
+    /// ```
+    pub fn with_auth(mut self, secret: Secret) -> Self {
+        self.secret = Some(secret);
+        self
+    }
+
+    /// Creates every accepted peer with `builder`, instead of the default settings.
+    /// ```no_run
+    /// use wing_rpc::{PeerBuilder, server::{Mode, TcpServer}};
+    ///
+    /// let server = TcpServer::bind("localhost:6000")
+    ///     .expect("Failed to bind")
+    ///     .with_peer_builder(PeerBuilder::new().nodelay(true));
+    /// let peer = server.accept(Mode::Blocking).expect("Failed to accept peer");
+    /// ```
+    pub fn with_peer_builder(mut self, builder: PeerBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    /// Sets how long accepted peers have to complete their TLS handshake and authenticate, see
+    /// [`TcpServer::bind_tls`] and [`TcpServer::with_auth`].
+    ///
+    /// Defaults to [`DEFAULT_HANDSHAKE_TIMEOUT`], so a peer that connects and then stays silent
+    /// can't hold up [`TcpServer::accept`] forever.
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
     }
 
     /// Return the inner [`std::net::TcpListener`].
     ///
     /// If you need to have more fine control over the connection lifecycle, consider implementing your own server.
     pub fn listener(&self) -> &TcpListener {
-        &self.0
+        &self.listener
     }
 
     /// Wait for [`crate::Peer`] to connect.
     ///
     /// This function will block the current thread while waiting for a connection.
-    pub fn accept(&self, mode: Mode) -> io::Result<Peer> {
-        let (sock, _) = self.0.accept()?;
+    ///
+    /// Returns a [`crate::Error`] rather than an [`io::Error`], since peers can also be rejected
+    /// with [`crate::Error::AuthFailed`]. Peers that don't complete their handshake in time are
+    /// rejected with [`io::ErrorKind::TimedOut`].
+    pub fn accept(&self, mode: Mode) -> WingResult<Peer> {
+        let (sock, _) = self.listener.accept()?;
+        let handle = sock.try_clone()?;
+        let peer = with_deadline(&handle, self.handshake_timeout, || {
+            #[cfg(feature = "tls")]
+            let mut peer = match &self.tls {
+                Some(config) => {
+                    sock.set_nodelay(self.builder.config().nodelay)?;
+                    let conn =
+                        rustls::ServerConnection::new(config.clone()).map_err(io::Error::other)?;
+                    self.builder.stream(crate::tls::handshake(conn, sock)?)
+                }
+                None => self.builder.socket(sock)?,
+            };
+            #[cfg(not(feature = "tls"))]
+            let mut peer = self.builder.socket(sock)?;
+            if let Some(secret) = &self.secret {
+                peer.require_auth_within(secret, self.handshake_timeout.into())?;
+            }
+            Ok(peer)
+        })?;
+        if let Mode::NonBlocking = mode {
+            handle.set_nonblocking(true)?;
+        }
+        Ok(peer)
+    }
+}
+
+/// A server that waits for peers connecting through WebSockets, such as browsers.
+///
+/// Each binary WebSocket message carries exactly one frame, which is all a browser needs to know
+/// about the wire format. Other than that, accepted peers work like any other.
+/// ```no_run
+/// use wing_rpc::server::{Mode, WsServer};
+///
+/// let server = WsServer::bind("localhost:6000").expect("Failed to bind");
+/// let peer = server.accept(Mode::Blocking).expect("Failed to accept peer");
+/// ```
+#[cfg(feature = "websocket")]
+#[derive(Debug)]
+pub struct WsServer {
+    listener: TcpListener,
+    secret: Option<Secret>,
+    builder: PeerBuilder,
+    handshake_timeout: Duration,
+}
+
+#[cfg(feature = "websocket")]
+impl WsServer {
+    /// Creates a [`WsServer`] that waits for connections on `addr`.
+    ///
+    /// Use [`WsServer::accept`] to turn incoming connections into a peer.
+    pub fn bind(addr: impl ToSocketAddrs) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            secret: None,
+            builder: PeerBuilder::new(),
+            handshake_timeout: DEFAULT_HANDSHAKE_TIMEOUT,
+        })
+    }
+
+    /// Requires every peer to prove it knows `secret` before it is accepted.
+    ///
+    /// See [`TcpServer::with_auth`].
+    pub fn with_auth(mut self, secret: Secret) -> Self {
+        self.secret = Some(secret);
+        self
+    }
+
+    /// Creates every accepted peer with `builder`, instead of the default settings.
+    pub fn with_peer_builder(mut self, builder: PeerBuilder) -> Self {
+        self.builder = builder;
+        self
+    }
+
+    /// Sets how long accepted peers have to complete their handshakes.
+    ///
+    /// See [`TcpServer::with_handshake_timeout`].
+    pub fn with_handshake_timeout(mut self, timeout: Duration) -> Self {
+        self.handshake_timeout = timeout;
+        self
+    }
+
+    /// Return the inner [`std::net::TcpListener`].
+    pub fn listener(&self) -> &TcpListener {
+        &self.listener
+    }
+
+    /// Wait for [`crate::Peer`] to connect and complete the WebSocket handshake.
+    ///
+    /// This function will block the current thread while waiting for a connection.
+    pub fn accept(&self, mode: Mode) -> WingResult<Peer> {
+        let (sock, _) = self.listener.accept()?;
+        sock.set_nodelay(self.builder.config().nodelay)?;
+        let handle = sock.try_clone()?;
+        let peer = with_deadline(&handle, self.handshake_timeout, || {
+            let ws = tungstenite::accept(sock).map_err(crate::ws::handshake_error)?;
+            let mut peer = self.builder.stream(crate::ws::WsStream::new(ws));
+            if let Some(secret) = &self.secret {
+                peer.require_auth_within(secret, self.handshake_timeout.into())?;
+            }
+            Ok(peer)
+        })?;
         if let Mode::NonBlocking = mode {
-            sock.set_nonblocking(true)?;
+            handle.set_nonblocking(true)?;
         }
-        Ok(Peer::from_socket(sock))
+        Ok(peer)
     }
 }
+
+/// Runs `handshake` while reads from `sock` time out after `timeout`, so a peer that stops
+/// answering can't block the server. The previous read timeout is restored afterwards.
+fn with_deadline<T>(
+    sock: &TcpStream,
+    timeout: Duration,
+    handshake: impl FnOnce() -> WingResult<T>,
+) -> WingResult<T> {
+    let previous = sock.read_timeout()?;
+    sock.set_read_timeout(Some(timeout))?;
+    let result = handshake().map_err(|e| match e {
+        // That's how reads that time out fail on Unix.
+        crate::Error::Io(e) if e.kind() == io::ErrorKind::WouldBlock => crate::timed_out(),
+        e => e,
+    });
+    sock.set_read_timeout(previous)?;
+    result
+}
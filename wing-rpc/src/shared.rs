@@ -0,0 +1,37 @@
+use std::{
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+};
+
+/// A handle to a bidirectional stream that can be split into a reading and a writing half.
+///
+/// Both halves lock the same stream, which is fine because a [`crate::Peer`] never reads and
+/// writes at the same time.
+pub(crate) struct SharedStream<S>(Arc<Mutex<S>>);
+
+impl<S> SharedStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self(Arc::new(Mutex::new(stream)))
+    }
+}
+
+impl<S> Clone for SharedStream<S> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<S: Read> Read for SharedStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().read(buf)
+    }
+}
+
+impl<S: Write> Write for SharedStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.lock().unwrap().flush()
+    }
+}
@@ -0,0 +1,500 @@
+//! A transport for peers on the same Linux machine, through shared memory.
+//!
+//! Frames are written into a pair of ring buffers that live in a memfd, one for each direction,
+//! so sending a message doesn't involve the kernel unless the other peer is waiting for it, in
+//! which case it is woken through an eventfd.
+//!
+//! Both ends are created together by [`SharedMemory::pair`], and one of them is usually handed to
+//! another process through a Unix socket:
+//! ```no_run
+//! use std::os::unix::net::UnixStream;
+//! use wing_rpc::{PeerBuilder, shm::SharedMemory};
+//!
+//! // In the backend
+//! let sock = UnixStream::connect("/tmp/sensors.sock").unwrap();
+//! let (ours, theirs) = SharedMemory::pair(1 << 20).unwrap();
+//! theirs.send(&sock).unwrap();
+//! let peer = PeerBuilder::new().shared_memory(ours).unwrap();
+//!
+//! // In the frontend, after accepting `sock`
+//! # let sock = sock;
+//! let peer = PeerBuilder::new()
+//!     .shared_memory(SharedMemory::recv(&sock).unwrap())
+//!     .unwrap();
+//! ```
+//! Shared memory can't be polled, so the peer polls the eventfd it is woken through instead, and
+//! timeouts work just like they do with sockets. Likewise, a writer waiting for room gives up after
+//! [`crate::PeerBuilder::send_timeout`].
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+        unix::net::UnixStream,
+    },
+    ptr::NonNull,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+    time::Instant,
+};
+
+use crate::Timeout;
+
+/// Positions are padded to their own cache line, so both peers don't fight over it.
+#[repr(C, align(64))]
+struct Padded<T>(T);
+
+/// The start of each ring buffer, followed by its data.
+#[repr(C)]
+struct RingHeader {
+    /// How many bytes were ever written.
+    head: Padded<AtomicU64>,
+    /// How many bytes were ever read.
+    tail: Padded<AtomicU64>,
+    /// Whether the reader asked to be signalled once [`RingHeader::head`] moves.
+    reader_waiting: AtomicU32,
+    /// Whether the writer is about to wait for [`RingHeader::tail`] to move.
+    writer_waiting: AtomicU32,
+    reader_closed: AtomicU32,
+    writer_closed: AtomicU32,
+}
+
+/// The memory mapped segment holding both rings, unmapped once both halves are gone.
+struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// SAFETY: The mapping is only accessed through atomics and the ring protocol.
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: `ptr` was returned by `mmap` with `len`, and nothing points into it anymore.
+        unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+    }
+}
+
+/// One direction of the connection, as seen by either of its ends.
+struct Ring {
+    map: Arc<Mapping>,
+    offset: usize,
+    capacity: usize,
+    /// Signalled by the writer when data is available.
+    readable: OwnedFd,
+    /// Signalled by the reader when space is available.
+    writable: OwnedFd,
+}
+
+impl Ring {
+    fn header(&self) -> &RingHeader {
+        // SAFETY: The header lives at `offset`, which is aligned, within the mapping.
+        unsafe { &*self.map.ptr.as_ptr().add(self.offset).cast::<RingHeader>() }
+    }
+    fn data(&self) -> *mut u8 {
+        // SAFETY: The data follows the header within the mapping.
+        unsafe {
+            self.map
+                .ptr
+                .as_ptr()
+                .add(self.offset + size_of::<RingHeader>())
+        }
+    }
+    /// How many bytes are between `tail` and `head`.
+    ///
+    /// Both positions are written by the other process, so they can't be trusted to make sense.
+    fn used(&self, head: u64, tail: u64) -> io::Result<usize> {
+        match head.wrapping_sub(tail) {
+            used if used > self.capacity as u64 => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The shared memory ring is corrupted",
+            )),
+            used => Ok(used as usize),
+        }
+    }
+    /// Copies between `buf` and the data at `pos`, wrapping around the end of the ring.
+    ///
+    /// # Safety
+    /// The range must be owned by the caller according to the ring protocol, and `len` must not
+    /// be over the capacity of the ring.
+    unsafe fn copy(&self, pos: u64, len: usize, mut copy: impl FnMut(*mut u8, usize, usize)) {
+        debug_assert!(len <= self.capacity);
+        let start = pos as usize & (self.capacity - 1);
+        let first = len.min(self.capacity - start);
+        // SAFETY: `start + first` is within the ring's data.
+        copy(unsafe { self.data().add(start) }, 0, first);
+        if first < len {
+            copy(self.data(), first, len - first);
+        }
+    }
+}
+
+fn signal(event: &OwnedFd) -> io::Result<()> {
+    let one = 1u64;
+    // SAFETY: `one` is valid for reads of 8 bytes.
+    match unsafe { libc::write(event.as_raw_fd(), (&raw const one).cast(), 8) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// Resets `event`, which must be non-blocking.
+fn drain(event: &OwnedFd) -> io::Result<()> {
+    match wait(event) {
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+        drained => drained,
+    }
+}
+
+fn wait(event: &OwnedFd) -> io::Result<()> {
+    let mut count = 0u64;
+    // SAFETY: `count` is valid for writes of 8 bytes.
+    match unsafe { libc::read(event.as_raw_fd(), (&raw mut count).cast(), 8) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(()),
+    }
+}
+
+/// The reading half of a shared memory connection.
+///
+/// Reading never blocks. Instead, [`ShmReader::readable`] stays readable from the moment
+/// something is written until the reader finds the ring empty.
+pub(crate) struct ShmReader(Ring);
+
+impl ShmReader {
+    /// The eventfd to poll for something to read.
+    pub(crate) fn readable(&self) -> RawFd {
+        self.0.readable.as_raw_fd()
+    }
+    /// Asks the writer to signal its next write, returning `false` if it already wrote meanwhile.
+    fn arm(&self, tail: u64) -> io::Result<bool> {
+        let header = self.0.header();
+        header.reader_waiting.store(1, Ordering::SeqCst);
+        // The writer may have written just before noticing we are waiting.
+        if header.head.0.load(Ordering::SeqCst) == tail
+            && header.writer_closed.load(Ordering::SeqCst) == 0
+        {
+            return Ok(true);
+        }
+        // Unless the writer took the request, nobody will signal what was written.
+        if header.reader_waiting.swap(0, Ordering::SeqCst) != 0 {
+            signal(&self.0.readable)?;
+        }
+        Ok(false)
+    }
+}
+
+impl Read for ShmReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let ring = &self.0;
+        let header = ring.header();
+        loop {
+            let tail = header.tail.0.load(Ordering::Relaxed);
+            let head = header.head.0.load(Ordering::Acquire);
+            let used = ring.used(head, tail)?;
+            if used > 0 {
+                let len = buf.len().min(used).min(ring.capacity);
+                // SAFETY: Bytes between the tail and the head are only touched by the reader.
+                unsafe {
+                    ring.copy(tail, len, |data, at, n| {
+                        buf[at..at + n].copy_from_slice(std::slice::from_raw_parts(data, n))
+                    })
+                };
+                header
+                    .tail
+                    .0
+                    .store(tail.wrapping_add(len as u64), Ordering::SeqCst);
+                if header.writer_waiting.load(Ordering::SeqCst) != 0 {
+                    signal(&ring.writable)?;
+                }
+                return Ok(len);
+            }
+            if header.writer_closed.load(Ordering::Acquire) != 0 {
+                return Ok(0);
+            }
+            drain(&ring.readable)?;
+            if self.arm(tail)? {
+                return Err(ErrorKind::WouldBlock.into());
+            }
+        }
+    }
+}
+
+impl Drop for ShmReader {
+    fn drop(&mut self) {
+        self.0.header().reader_closed.store(1, Ordering::SeqCst);
+        let _ = signal(&self.0.writable);
+    }
+}
+
+/// The writing half of a shared memory connection.
+///
+/// Writing waits up to its timeout for the reader to make room.
+pub(crate) struct ShmWriter(Ring, Timeout);
+
+impl Write for ShmWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let ring = &self.0;
+        let header = ring.header();
+        let start = Instant::now();
+        loop {
+            if header.reader_closed.load(Ordering::Acquire) != 0 {
+                return Err(ErrorKind::BrokenPipe.into());
+            }
+            let head = header.head.0.load(Ordering::Relaxed);
+            let tail = header.tail.0.load(Ordering::Acquire);
+            let free = ring.capacity - ring.used(head, tail)?;
+            if free > 0 {
+                let len = buf.len().min(free);
+                // SAFETY: Bytes between the head and the tail plus capacity are only touched by
+                // the writer.
+                unsafe {
+                    ring.copy(head, len, |data, at, n| {
+                        std::ptr::copy_nonoverlapping(buf[at..].as_ptr(), data, n)
+                    })
+                };
+                header
+                    .head
+                    .0
+                    .store(head.wrapping_add(len as u64), Ordering::SeqCst);
+                if header.reader_waiting.swap(0, Ordering::SeqCst) != 0 {
+                    signal(&ring.readable)?;
+                }
+                return Ok(len);
+            }
+            header.writer_waiting.store(1, Ordering::SeqCst);
+            // The reader may have read just before noticing we are waiting.
+            let waited = if header.tail.0.load(Ordering::SeqCst) == tail
+                && header.reader_closed.load(Ordering::SeqCst) == 0
+            {
+                let timeout = self.1.remaining(start);
+                match timeout.poll(ring.writable.as_raw_fd(), libc::POLLIN) {
+                    Ok(0) => Err(ErrorKind::TimedOut.into()),
+                    Ok(_) => wait(&ring.writable),
+                    Err(e) => Err(e),
+                }
+            } else {
+                Ok(())
+            };
+            header.writer_waiting.store(0, Ordering::SeqCst);
+            waited?;
+        }
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ShmWriter {
+    fn drop(&mut self) {
+        self.0.header().writer_closed.store(1, Ordering::SeqCst);
+        let _ = signal(&self.0.readable);
+    }
+}
+
+/// One end of a shared memory connection, to be turned into a [`crate::Peer`] by
+/// [`crate::PeerBuilder::shared_memory`].
+#[derive(Debug)]
+pub struct SharedMemory {
+    memfd: OwnedFd,
+    /// The size of each ring buffer.
+    capacity: usize,
+    /// Which ring this end writes to, `0` or `1`.
+    side: u8,
+    /// The `readable` and `writable` eventfds of both rings.
+    events: [OwnedFd; 4],
+}
+
+impl SharedMemory {
+    /// Creates both ends of a connection, whose ring buffers can hold `capacity` bytes each.
+    ///
+    /// `capacity` is rounded up to a power of two.
+    pub fn pair(capacity: usize) -> io::Result<(Self, Self)> {
+        let capacity = capacity.max(64).next_power_of_two();
+        // SAFETY: The name is a valid C string.
+        let memfd = unsafe {
+            libc::memfd_create(
+                c"wing-rpc".as_ptr(),
+                libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+            )
+        };
+        let memfd = checked(memfd)?;
+        let len = mapping_len(capacity).ok_or(ErrorKind::InvalidInput)?;
+        // SAFETY: `memfd` is a valid descriptor. Its new bytes are zeroed, which is an empty ring.
+        if unsafe { libc::ftruncate(memfd.as_raw_fd(), len as libc::off_t) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // The other process relies on the size never changing.
+        let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_SEAL;
+        // SAFETY: `memfd` is a valid descriptor.
+        if unsafe { libc::fcntl(memfd.as_raw_fd(), libc::F_ADD_SEALS, seals) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `eventfd` has no preconditions.
+        let event = || checked(unsafe { libc::eventfd(0, libc::EFD_CLOEXEC) });
+        let events = [event()?, event()?, event()?, event()?];
+        let theirs = Self {
+            memfd: memfd.try_clone()?,
+            capacity,
+            side: 1,
+            events: [
+                events[0].try_clone()?,
+                events[1].try_clone()?,
+                events[2].try_clone()?,
+                events[3].try_clone()?,
+            ],
+        };
+        let ours = Self {
+            memfd,
+            capacity,
+            side: 0,
+            events,
+        };
+        Ok((ours, theirs))
+    }
+
+    /// Hands this end over to the process on the other side of `sock`, see [`Self::recv`].
+    pub fn send(self, sock: &UnixStream) -> io::Result<()> {
+        let mut info = [0; 9];
+        info[0] = self.side;
+        info[1..].copy_from_slice(&(self.capacity as u64).to_le_bytes());
+        let [e0, e1, e2, e3] = self.events;
+        let fds = [self.memfd, e0, e1, e2, e3];
+        let mut sent = 0;
+        while sent < info.len() {
+            sent += match sent {
+                0 => crate::fd::send(sock.as_raw_fd(), &info, &fds),
+                _ => crate::fd::send(sock.as_raw_fd(), &info[sent..], &[]),
+            }
+            .or_else(|e| match e.kind() {
+                ErrorKind::WouldBlock | ErrorKind::Interrupted => Ok(0),
+                _ => Err(e),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Receives the end sent by the process on the other side of `sock` with [`Self::send`].
+    pub fn recv(sock: &UnixStream) -> io::Result<Self> {
+        let mut info = [0; 9];
+        let (mut read, mut fds) = crate::fd::recv(sock.as_raw_fd(), &mut info)?;
+        while read < info.len() {
+            match crate::fd::recv(sock.as_raw_fd(), &mut info[read..])? {
+                (0, _) => return Err(ErrorKind::UnexpectedEof.into()),
+                (more, _) => read += more,
+            }
+        }
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "Not a shared memory handshake");
+        let capacity = u64::from_le_bytes(info[1..].try_into().unwrap()) as usize;
+        // Rings smaller than a cache line would leave the second header misaligned.
+        if fds.len() != 5 || info[0] > 1 || !capacity.is_power_of_two() || capacity < 64 {
+            return Err(invalid());
+        }
+        let events = fds.split_off(1);
+        Ok(Self {
+            memfd: fds.pop().unwrap(),
+            capacity,
+            side: info[0],
+            events: events.try_into().map_err(|_| invalid())?,
+        })
+    }
+
+    /// Checks that the memfd is big enough for both rings, and that it can't be shrunk later.
+    ///
+    /// Accessing a mapping past the end of its file raises `SIGBUS`, so this must hold before
+    /// mapping a memfd that came from another process.
+    fn check_memfd(&self) -> io::Result<usize> {
+        let invalid = |msg| io::Error::new(ErrorKind::InvalidData, msg);
+        let len = mapping_len(self.capacity).ok_or_else(|| invalid("The rings are too large"))?;
+        // SAFETY: `stat` is plain old data, for which all zeroes is a valid value.
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        // SAFETY: `memfd` is a valid descriptor, and `stat` is valid for writes.
+        if unsafe { libc::fstat(self.memfd.as_raw_fd(), &mut stat) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        if u64::try_from(stat.st_size).is_ok_and(|size| size < len as u64) {
+            return Err(invalid("The shared memory is smaller than its rings"));
+        }
+        // SAFETY: `memfd` is a valid descriptor.
+        let seals = unsafe { libc::fcntl(self.memfd.as_raw_fd(), libc::F_GET_SEALS) };
+        if seals == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        if seals & libc::F_SEAL_SHRINK == 0 {
+            return Err(invalid(
+                "The shared memory can be shrunk by the other process",
+            ));
+        }
+        Ok(len)
+    }
+
+    /// Maps the shared memory, returning the halves used by a peer.
+    ///
+    /// Writes wait up to `send_timeout` for the other peer to read.
+    pub(crate) fn split(self, send_timeout: Timeout) -> io::Result<(ShmReader, ShmWriter)> {
+        let len = self.check_memfd()?;
+        let ring_len = len / 2;
+        // SAFETY: The memfd is at least `len` bytes long and can't shrink, which was checked
+        // above, and the mapping is owned by `Mapping`.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                self.memfd.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let map = Arc::new(Mapping {
+            ptr: NonNull::new(ptr.cast()).expect("mmap succeeded"),
+            len,
+        });
+        let [e0, e1, e2, e3] = self.events;
+        let readable = match self.side {
+            0 => &e2,
+            _ => &e0,
+        };
+        // SAFETY: `readable` is a valid descriptor. Only the reader ever reads from it.
+        if unsafe { libc::fcntl(readable.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let rings = [(0, e0, e1), (ring_len, e2, e3)];
+        let [first, second] = rings.map(|(offset, readable, writable)| Ring {
+            map: map.clone(),
+            offset,
+            capacity: self.capacity,
+            readable,
+            writable,
+        });
+        let (ours, theirs) = match self.side {
+            0 => (first, second),
+            _ => (second, first),
+        };
+        let reader = ShmReader(theirs);
+        // Anything written before we were around must be noticed too.
+        reader.arm(reader.0.header().tail.0.load(Ordering::SeqCst))?;
+        Ok((reader, ShmWriter(ours, send_timeout)))
+    }
+}
+
+/// The length of the memfd holding two rings of `capacity` bytes each.
+fn mapping_len(capacity: usize) -> Option<usize> {
+    size_of::<RingHeader>()
+        .checked_add(capacity)?
+        .checked_mul(2)
+}
+
+/// Turns the return value of a libc function that creates a descriptor into a result.
+fn checked(fd: libc::c_int) -> io::Result<OwnedFd> {
+    match fd {
+        -1 => Err(io::Error::last_os_error()),
+        // SAFETY: `fd` was just created and isn't owned by anything else.
+        fd => Ok(unsafe { OwnedFd::from_raw_fd(fd) }),
+    }
+}
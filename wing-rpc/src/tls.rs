@@ -0,0 +1,19 @@
+use std::{io, net::TcpStream, ops::DerefMut};
+
+use rustls::{ConnectionCommon, StreamOwned};
+
+/// Drives the TLS handshake to completion, so that errors surface while connecting instead of on
+/// the first message.
+pub(crate) fn handshake<C, S>(
+    mut conn: C,
+    mut sock: TcpStream,
+) -> io::Result<StreamOwned<C, TcpStream>>
+where
+    C: DerefMut<Target = ConnectionCommon<S>>,
+    S: rustls::SideData,
+{
+    while conn.is_handshaking() {
+        conn.complete_io(&mut sock)?;
+    }
+    Ok(StreamOwned::new(conn, sock))
+}
@@ -0,0 +1,103 @@
+//! Peers restricted to the messages of a declared protocol.
+use std::marker::PhantomData;
+
+use crate::{Error, Message, Peer, Timeout, WingResult, WrappedData, inbox::Frame, timed_out};
+
+/// A [`Peer`] that can only send `Out` and only receive `In`.
+///
+/// `In` and `Out` are usually enums listing every message each side may send, so sending a
+/// message meant for the other side is caught by the compiler. The wing compiler generates
+/// aliases for both sides of a `protocol`:
+/// ```ignore
+/// protocol Chat {
+///     client: ClientMessage,
+///     server: ServerMessage,
+/// }
+/// ```
+/// becomes `ChatClientPeer` and `ChatServerPeer`.
+/// ```no_run
+/// # use serde::{Deserialize, Serialize};
+/// # use wing_rpc::{Message, TypedPeer, client};
+/// # #[derive(Serialize, Deserialize)]
+/// # enum ClientMessage { Join(String) }
+/// # impl Message<'_> for ClientMessage { const NAME: &'static str = "ClientMessage"; }
+/// # #[derive(Serialize, Deserialize)]
+/// # enum ServerMessage { Welcome(u32) }
+/// # impl Message<'_> for ServerMessage { const NAME: &'static str = "ServerMessage"; }
+/// let peer = client::tcp("localhost:6000").unwrap();
+/// let mut peer = TypedPeer::<ServerMessage, ClientMessage>::new(peer);
+/// peer.send(ClientMessage::Join("general".into())).unwrap();
+/// let ServerMessage::Welcome(id) = peer.recv().unwrap();
+/// ```
+pub struct TypedPeer<In, Out> {
+    peer: Peer,
+    protocol: PhantomData<fn(Out) -> In>,
+}
+
+impl<In, Out> TypedPeer<In, Out>
+where
+    In: for<'a> Message<'a>,
+    Out: for<'a> Message<'a>,
+{
+    pub fn new(peer: Peer) -> Self {
+        Self {
+            peer,
+            protocol: PhantomData,
+        }
+    }
+    /// Returns the underlying [`Peer`], which can send and receive anything.
+    pub fn into_inner(self) -> Peer {
+        self.peer
+    }
+    /// The underlying [`Peer`], to receive a message that isn't `In`, see [`TypedPeer::recv`].
+    pub fn peer_mut(&mut self) -> &mut Peer {
+        &mut self.peer
+    }
+    /// See [`Peer::send`].
+    pub fn send(&mut self, message: Out) -> WingResult<()> {
+        self.peer.send(message)
+    }
+    /// See [`Peer::feed`].
+    pub fn feed(&mut self, message: Out) -> WingResult<()> {
+        self.peer.feed(message)
+    }
+    /// See [`Peer::flush`].
+    pub fn flush(&mut self) -> WingResult<()> {
+        self.peer.flush()
+    }
+    /// Waits for the next message, see [`Peer::recv`].
+    ///
+    /// Fails with [`Error::UnexpectedMessage`] if the other peer sent anything but `In`. That
+    /// message is kept, so it can still be received through [`TypedPeer::peer_mut`].
+    pub fn recv(&mut self) -> WingResult<In> {
+        let timeout = self.peer.config.recv_timeout.clone();
+        self.try_recv(timeout)?.ok_or_else(timed_out)
+    }
+    /// Waits for the next message with a timeout, see [`Peer::try_recv`].
+    pub fn try_recv(&mut self, timeout: Timeout) -> WingResult<Option<In>> {
+        let frame = try_harder!(self.peer.next_frame_serving(timeout, Frame::is_plain));
+        if frame.header.typ != In::NAME {
+            let got = frame.header.typ.clone();
+            self.peer.frames.push_front(frame);
+            return Err(Error::UnexpectedMessage {
+                expected: In::NAME,
+                got,
+            });
+        }
+        let fds = frame.take_fds();
+        let codec = self.peer.config.codec;
+        Ok(Some(
+            codec.decode_with::<WrappedData<In>>(&frame.data, fds)?.data,
+        ))
+    }
+}
+
+impl<In, Out> From<Peer> for TypedPeer<In, Out>
+where
+    In: for<'a> Message<'a>,
+    Out: for<'a> Message<'a>,
+{
+    fn from(peer: Peer) -> Self {
+        Self::new(peer)
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use tungstenite::{HandshakeError, Message, WebSocket, handshake::HandshakeRole};
+
+use crate::WirePacket;
+
+/// Carries frames over a WebSocket connection, one binary message per frame.
+///
+/// This lets browsers speak `Wing RPC` without having to reassemble frames themselves.
+pub(crate) struct WsStream<S> {
+    ws: WebSocket<S>,
+    /// The last message received, and how much of it was read.
+    incoming: (tungstenite::Bytes, usize),
+    /// Bytes written that don't make up a whole frame yet.
+    outgoing: Vec<u8>,
+}
+
+impl<S: Read + Write> WsStream<S> {
+    pub fn new(ws: WebSocket<S>) -> Self {
+        Self {
+            ws,
+            incoming: Default::default(),
+            outgoing: Vec::new(),
+        }
+    }
+}
+
+impl<S: Read + Write> Read for WsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.incoming.1 == self.incoming.0.len() {
+            match self.ws.read() {
+                Ok(Message::Binary(data)) => {
+                    let frame_len = WirePacket::decode(&data)
+                        .map(|packet| WirePacket::HEADER_LEN + packet.data().len());
+                    if frame_len != Some(data.len()) {
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            "WebSocket message isn't exactly one frame",
+                        ));
+                    }
+                    self.incoming = (data, 0);
+                }
+                Ok(Message::Text(_)) => {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "Frames must be sent as binary WebSocket messages",
+                    ));
+                }
+                Ok(Message::Close(_)) => return Ok(0),
+                // Pings are answered by `tungstenite` itself.
+                Ok(_) => {}
+                Err(tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed) => {
+                    return Ok(0);
+                }
+                Err(e) => return Err(into_io(e)),
+            }
+        }
+        let (data, read) = &mut self.incoming;
+        let len = buf.len().min(data.len() - *read);
+        buf[..len].copy_from_slice(&data[*read..*read + len]);
+        *read += len;
+        Ok(len)
+    }
+}
+
+impl<S: Read + Write> Write for WsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.outgoing.extend_from_slice(buf);
+        while let Some(packet) = WirePacket::decode(&self.outgoing) {
+            let len = WirePacket::HEADER_LEN + packet.data().len();
+            let frame: Vec<u8> = self.outgoing.drain(..len).collect();
+            match self.ws.write(Message::binary(frame)) {
+                // The message is queued, and will be written by the next flush.
+                Err(tungstenite::Error::Io(e)) if e.kind() == ErrorKind::WouldBlock => {}
+                sent => sent.map_err(into_io)?,
+            }
+        }
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.ws.flush().map_err(into_io)
+    }
+}
+
+pub(crate) fn into_io(error: tungstenite::Error) -> io::Error {
+    match error {
+        tungstenite::Error::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+/// Turns a failed handshake into an [`io::Error`].
+pub(crate) fn handshake_error<R: HandshakeRole>(error: HandshakeError<R>) -> io::Error {
+    match error {
+        HandshakeError::Failure(e) => into_io(e),
+        HandshakeError::Interrupted(_) => ErrorKind::WouldBlock.into(),
+    }
+}
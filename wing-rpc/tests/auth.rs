@@ -0,0 +1,103 @@
+use std::{
+    io::{ErrorKind, Write},
+    net::TcpStream,
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Error, Message,
+    auth::Secret,
+    client,
+    server::{Mode, TcpServer},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Ping {
+    text: String,
+}
+
+impl Message<'_> for Ping {
+    const NAME: &'static str = "Ping";
+}
+
+#[test]
+fn auth_accepts_shared_secret() {
+    let server = TcpServer::bind("localhost:0")
+        .unwrap()
+        .with_auth(Secret::new("hunter2"));
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let mut peer = server.accept(Mode::Blocking).unwrap();
+        let ping: Ping = peer.recv().unwrap();
+        peer.send(ping).unwrap();
+    });
+    let mut peer = client::tcp_with_auth(("localhost", port), &Secret::new("hunter2")).unwrap();
+    let ping = Ping {
+        text: "hello".into(),
+    };
+    peer.send(ping.clone()).unwrap();
+    assert_eq!(peer.recv::<Ping>().unwrap(), ping);
+    handle.join().unwrap();
+}
+
+#[test]
+fn auth_rejects_wrong_secret() {
+    let server = TcpServer::bind("localhost:0")
+        .unwrap()
+        .with_auth(Secret::new("hunter2"));
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || server.accept(Mode::Blocking).err());
+    let client = client::tcp_with_auth(("localhost", port), &Secret::new("*******")).err();
+    assert!(matches!(client, Some(Error::AuthFailed)));
+    assert!(matches!(handle.join().unwrap(), Some(Error::AuthFailed)));
+}
+
+#[test]
+fn auth_rejects_plain_messages() {
+    let server = TcpServer::bind("localhost:0")
+        .unwrap()
+        .with_auth(Secret::new("hunter2"));
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || server.accept(Mode::Blocking).err());
+    let mut peer = client::tcp(("localhost", port)).unwrap();
+    peer.send(Ping {
+        text: "let me in".into(),
+    })
+    .unwrap();
+    assert!(matches!(handle.join().unwrap(), Some(Error::AuthFailed)));
+}
+
+#[test]
+fn auth_times_out_silent_peers() {
+    let server = TcpServer::bind("localhost:0")
+        .unwrap()
+        .with_auth(Secret::new("hunter2"))
+        .with_handshake_timeout(Duration::from_millis(100));
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let silent = server.accept(Mode::Blocking).err();
+        (silent, server.accept(Mode::Blocking).map(|_| ()).err())
+    });
+    let _silent = TcpStream::connect(("localhost", port)).unwrap();
+    let peer = client::tcp_with_auth(("localhost", port), &Secret::new("hunter2"));
+    let (silent, next) = handle.join().unwrap();
+    assert!(matches!(silent, Some(Error::Io(e)) if e.kind() == ErrorKind::TimedOut));
+    assert!(next.is_none());
+    assert!(peer.is_ok());
+}
+
+#[test]
+fn auth_rejects_garbage() {
+    let server = TcpServer::bind("localhost:0")
+        .unwrap()
+        .with_auth(Secret::new("hunter2"));
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || server.accept(Mode::Blocking).err());
+    let mut sock = TcpStream::connect(("localhost", port)).unwrap();
+    // A control frame of four bytes that don't decode to anything.
+    sock.write_all(&[0b1, 4, 0, 0xc1, 0xff, 0x00, 0x93])
+        .unwrap();
+    assert!(matches!(handle.join().unwrap(), Some(Error::AuthFailed)));
+}
@@ -0,0 +1,106 @@
+use std::{io::Write, thread};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Message, Peer, client,
+    server::{Mode, TcpServer},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+impl Message<'_> for Point {
+    const NAME: &'static str = "Point";
+}
+
+#[derive(Serialize, Deserialize)]
+struct Blob(String);
+
+impl Message<'_> for Blob {
+    const NAME: &'static str = "Blob";
+}
+
+fn connected(serve: impl FnOnce(Peer) + Send + 'static) -> (Peer, thread::JoinHandle<()>) {
+    let server = TcpServer::bind("localhost:0").unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || serve(server.accept(Mode::Blocking).unwrap()));
+    (client::tcp(("localhost", port)).unwrap(), handle)
+}
+
+fn point(x: i32) -> Point {
+    Point { x, y: -x }
+}
+
+#[test]
+fn send_batch() {
+    let (mut peer, handle) = connected(|mut peer| {
+        for x in 0..1000 {
+            assert_eq!(peer.recv::<Point>().unwrap(), point(x));
+        }
+    });
+    peer.send_batch((0..1000).map(point)).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn feed_then_flush() {
+    let (mut peer, handle) = connected(|mut peer| {
+        for x in 0..10 {
+            assert_eq!(peer.recv::<Point>().unwrap(), point(x));
+        }
+        peer.send(point(10)).unwrap();
+    });
+    for x in 0..10 {
+        peer.feed(point(x)).unwrap();
+    }
+    peer.flush().unwrap();
+    assert_eq!(peer.recv::<Point>().unwrap(), point(10));
+    handle.join().unwrap();
+}
+
+/// Counts the writes that reach the underlying stream.
+#[derive(Clone, Default)]
+struct Counting(std::sync::Arc<std::sync::Mutex<(usize, Vec<u8>)>>);
+
+impl Write for Counting {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut inner = self.0.lock().unwrap();
+        inner.0 += 1;
+        inner.1.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn batch_is_written_at_once() {
+    let out = Counting::default();
+    let mut peer = Peer::builder().build(std::io::empty(), out.clone());
+    peer.send_batch((0..100).map(point)).unwrap();
+    let (writes, bytes) = out.0.lock().unwrap().clone();
+    assert_eq!(writes, 1);
+
+    let mut peer = Peer::new(std::io::Cursor::new(bytes), std::io::sink());
+    for x in 0..100 {
+        assert_eq!(peer.recv::<Point>().unwrap(), point(x));
+    }
+}
+
+#[test]
+fn large_payloads_skip_the_buffer() {
+    let out = Counting::default();
+    let mut peer = Peer::builder()
+        .write_buffer(64)
+        .build(std::io::empty(), out.clone());
+    peer.send(Blob("x".repeat(1000))).unwrap();
+    let (writes, bytes) = out.0.lock().unwrap().clone();
+    // The header goes through the buffer, while the payload is written straight from the message.
+    assert_eq!(writes, 2);
+    let mut peer = Peer::new(std::io::Cursor::new(bytes), std::io::sink());
+    assert_eq!(peer.recv::<Blob>().unwrap().0.len(), 1000);
+}
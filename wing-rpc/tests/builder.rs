@@ -0,0 +1,163 @@
+use std::{io::ErrorKind, net::TcpStream, thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Error, Message, Peer, PeerBuilder,
+    server::{Mode, TcpServer},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Item {
+    n: u32,
+    padding: String,
+}
+
+impl Message<'_> for Item {
+    const NAME: &'static str = "Item";
+}
+
+fn connected(
+    client: PeerBuilder,
+    server: PeerBuilder,
+    serve: impl FnOnce(Peer) + Send + 'static,
+) -> (Peer, thread::JoinHandle<()>) {
+    let server = TcpServer::bind("localhost:0")
+        .unwrap()
+        .with_peer_builder(server);
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || serve(server.accept(Mode::Blocking).unwrap()));
+    let sock = TcpStream::connect(("localhost", port)).unwrap();
+    (client.socket(sock).unwrap(), handle)
+}
+
+#[test]
+fn skip_oversized_frames() {
+    let (mut peer, handle) = connected(
+        PeerBuilder::new(),
+        PeerBuilder::new().max_frame_size(64),
+        |mut peer| {
+            let err = peer.recv::<Item>().unwrap_err();
+            assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::InvalidData));
+            assert_eq!(peer.recv::<Item>().unwrap().n, 2);
+        },
+    );
+    let padding = "x".repeat(1024);
+    peer.send(Item { n: 1, padding }).unwrap();
+    peer.send(Item {
+        n: 2,
+        padding: String::new(),
+    })
+    .unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn refuse_to_send_oversized_frames() {
+    let (mut peer, handle) = connected(
+        PeerBuilder::new().max_frame_size(64),
+        PeerBuilder::new(),
+        |mut peer| assert_eq!(peer.recv::<Item>().unwrap().n, 2),
+    );
+    let padding = "x".repeat(1024);
+    let err = peer.send(Item { n: 1, padding }).unwrap_err();
+    assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::InvalidInput));
+    peer.send(Item {
+        n: 2,
+        padding: String::new(),
+    })
+    .unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn recv_timeout() {
+    let (mut peer, handle) = connected(
+        PeerBuilder::new().recv_timeout(Duration::from_millis(50)),
+        PeerBuilder::new(),
+        |mut peer| {
+            peer.recv::<Item>().unwrap();
+        },
+    );
+    let err = peer.recv::<Item>().unwrap_err();
+    assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::TimedOut));
+    peer.send(Item {
+        n: 1,
+        padding: String::new(),
+    })
+    .unwrap();
+    handle.join().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn recv_survives_signals() {
+    extern "C" fn ignore(_: libc::c_int) {}
+    // SAFETY: the handler does nothing, and leaving out `SA_RESTART` makes `poll` fail with
+    // `EINTR` instead of being restarted.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = ignore as extern "C" fn(libc::c_int) as usize;
+        assert_eq!(
+            libc::sigaction(libc::SIGUSR1, &action, std::ptr::null_mut()),
+            0
+        );
+    }
+    let (mut peer, handle) = connected(PeerBuilder::new(), PeerBuilder::new(), |mut peer| {
+        thread::sleep(Duration::from_millis(100));
+        peer.send(Item {
+            n: 1,
+            padding: String::new(),
+        })
+        .unwrap();
+    });
+    let receiver = unsafe { libc::pthread_self() };
+    let signal = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        // SAFETY: the receiver outlives the signal, as it waits for the message sent after it.
+        unsafe { libc::pthread_kill(receiver, libc::SIGUSR1) };
+    });
+    assert_eq!(peer.recv::<Item>().unwrap().n, 1);
+    signal.join().unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn manual_flush() {
+    let (mut peer, handle) = connected(
+        PeerBuilder::new().auto_flush(false),
+        PeerBuilder::new(),
+        |mut peer| {
+            for n in 0..10 {
+                assert_eq!(peer.recv::<Item>().unwrap().n, n);
+            }
+        },
+    );
+    for n in 0..10 {
+        peer.send(Item {
+            n,
+            padding: String::new(),
+        })
+        .unwrap();
+    }
+    peer.flush().unwrap();
+    handle.join().unwrap();
+}
+
+#[cfg(feature = "msgpack")]
+#[test]
+fn msgpack() {
+    use wing_rpc::Codec;
+
+    let builder = PeerBuilder::new().codec(Codec::MessagePack);
+    let (mut peer, handle) = connected(builder.clone(), builder, |mut peer| {
+        let item = peer.recv::<Item>().unwrap();
+        peer.send(item).unwrap();
+    });
+    let item = Item {
+        n: 7,
+        padding: "packed".into(),
+    };
+    peer.send(item.clone()).unwrap();
+    assert_eq!(peer.recv::<Item>().unwrap(), item);
+    handle.join().unwrap();
+}
@@ -0,0 +1,137 @@
+use std::{thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Error, Message, Peer, client,
+    server::{Mode, TcpServer},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Search {
+    limit: u32,
+}
+
+impl Message<'_> for Search {
+    const NAME: &'static str = "Search";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Item {
+    n: u32,
+}
+
+impl Message<'_> for Item {
+    const NAME: &'static str = "Item";
+}
+
+fn connected(serve: impl FnOnce(Peer) + Send + 'static) -> (Peer, thread::JoinHandle<()>) {
+    let server = TcpServer::bind("localhost:0").unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || serve(server.accept(Mode::Blocking).unwrap()));
+    (client::tcp(("localhost", port)).unwrap(), handle)
+}
+
+#[test]
+fn call_reply() {
+    let (mut peer, handle) = connected(|mut peer| {
+        let call = peer.next_call().unwrap();
+        assert_eq!(call.name(), "Search");
+        let search: Search = call.decode().unwrap();
+        peer.send(Item { n: 0 }).unwrap();
+        peer.reply(&call, Item { n: search.limit }).unwrap();
+        let call = peer.next_call().unwrap();
+        peer.reply_error(&call, "no results").unwrap();
+    });
+    let item: Item = peer.call(Search { limit: 7 }).unwrap();
+    assert_eq!(item, Item { n: 7 });
+    // Plain messages are kept while waiting for the reply.
+    assert_eq!(peer.recv::<Item>().unwrap(), Item { n: 0 });
+    let err = peer.call::<Item>(Search { limit: 0 }).unwrap_err();
+    assert!(matches!(err, Error::Remote(msg) if msg == "no results"));
+    handle.join().unwrap();
+}
+
+#[test]
+fn stream_to_end() {
+    let (mut peer, handle) = connected(|mut peer| {
+        let call = peer.next_call().unwrap();
+        let search: Search = call.decode().unwrap();
+        let mut items = peer.reply_stream(&call);
+        for n in 0..search.limit {
+            items.send(Item { n }).unwrap();
+        }
+        items.finish().unwrap();
+    });
+    let items = peer.call_stream::<Item>(Search { limit: 50 }).unwrap();
+    let items: Vec<u32> = items.map(|item| item.unwrap().n).collect();
+    assert_eq!(items, (0..50).collect::<Vec<_>>());
+    handle.join().unwrap();
+}
+
+#[test]
+fn stream_cancel() {
+    let (mut peer, handle) = connected(|mut peer| {
+        let call = peer.next_call().unwrap();
+        let mut items = peer.reply_stream(&call);
+        let mut n = 0;
+        loop {
+            match items.send(Item { n }) {
+                Ok(()) => n += 1,
+                Err(Error::Cancelled) => break,
+                Err(e) => panic!("{e}"),
+            }
+            assert!(n < 10_000, "Stream was never cancelled");
+            thread::sleep(Duration::from_millis(1));
+        }
+        items.finish().unwrap();
+        let item: Item = peer.recv().unwrap();
+        peer.send(item).unwrap();
+    });
+    let mut items = peer.call_stream::<Item>(Search { limit: 0 }).unwrap();
+    for n in 0..3 {
+        assert_eq!(items.next().unwrap().unwrap(), Item { n });
+    }
+    items.cancel().unwrap();
+    // Items sent before the cancellation arrived must not show up here.
+    peer.send(Item { n: 42 }).unwrap();
+    assert_eq!(peer.recv::<Item>().unwrap(), Item { n: 42 });
+    handle.join().unwrap();
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Confirm {
+    question: String,
+}
+
+impl Message<'_> for Confirm {
+    const NAME: &'static str = "Confirm";
+}
+
+#[test]
+fn call_back_while_waiting() {
+    let (mut peer, handle) = connected(|mut peer| {
+        let call = peer.next_call().unwrap();
+        let search: Search = call.decode().unwrap();
+        // Asks the caller something before replying.
+        let answer: Item = peer
+            .call(Confirm {
+                question: "really?".into(),
+            })
+            .unwrap();
+        let mut items = peer.reply_stream(&call);
+        for n in 0..search.limit + answer.n {
+            items.send(Item { n }).unwrap();
+        }
+        items.finish().unwrap();
+    });
+    peer.handle(|confirm: Confirm| {
+        assert_eq!(confirm.question, "really?");
+        Ok(Item { n: 2 })
+    });
+    let items = peer.call_stream::<Item>(Search { limit: 1 }).unwrap();
+    assert_eq!(
+        items.map(|item| item.unwrap().n).collect::<Vec<_>>(),
+        [0, 1, 2]
+    );
+    handle.join().unwrap();
+}
@@ -0,0 +1,143 @@
+#![cfg(unix)]
+use std::{io::ErrorKind, os::unix::net::UnixStream, thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Message, Peer, PeerSource, Timeout,
+    calloop::EventLoop,
+    mio::{Events, Interest, Poll, Token},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Ping(u32);
+
+impl Message<'_> for Ping {
+    const NAME: &'static str = "Ping";
+}
+
+#[test]
+fn mio_source() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut peer = Peer::from_unix_socket(ours);
+    let sender = thread::spawn(move || {
+        let mut peer = Peer::from_unix_socket(theirs);
+        for n in 0..10 {
+            peer.send(Ping(n)).unwrap();
+            thread::sleep(Duration::from_millis(1));
+        }
+    });
+
+    let mut poll = Poll::new().unwrap();
+    (poll.registry())
+        .register(&mut peer, Token(0), Interest::READABLE)
+        .unwrap();
+    let mut events = Events::with_capacity(4);
+    let mut received = vec![];
+    while received.len() < 10 {
+        poll.poll(&mut events, Some(Duration::from_secs(5)))
+            .unwrap();
+        assert!(!events.is_empty(), "Timed out");
+        while let Some(Ping(n)) = peer.try_recv(Timeout::DontBlock).unwrap() {
+            received.push(n);
+        }
+    }
+    assert_eq!(received, (0..10).collect::<Vec<_>>());
+    poll.registry().deregister(&mut peer).unwrap();
+    sender.join().unwrap();
+}
+
+#[test]
+fn calloop_source() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let echo = thread::spawn(move || {
+        let mut peer = Peer::from_unix_socket(theirs);
+        peer.send(Ping(0)).unwrap();
+        loop {
+            match peer.recv::<Ping>() {
+                Ok(Ping(n)) if n < 19 => peer.send(Ping(n + 1)).unwrap(),
+                Ok(_) => return,
+                Err(e) => panic!("{e}"),
+            }
+        }
+    });
+
+    let mut event_loop = EventLoop::<Vec<u32>>::try_new().unwrap();
+    let source = PeerSource::<Ping>::new(Peer::from_unix_socket(ours));
+    (event_loop.handle())
+        .insert_source(source, |Ping(n), peer, received| {
+            received.push(n);
+            peer.send(Ping(n + 1)).unwrap();
+        })
+        .unwrap();
+    let mut received = vec![];
+    while received.len() < 10 {
+        (event_loop.dispatch(Duration::from_secs(5), &mut received)).unwrap();
+    }
+    echo.join().unwrap();
+    assert_eq!(received, [0, 2, 4, 6, 8, 10, 12, 14, 16, 18]);
+}
+
+#[test]
+fn calloop_source_removes_itself() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut event_loop = EventLoop::<()>::try_new().unwrap();
+    let source = PeerSource::<Ping>::new(Peer::from_unix_socket(ours));
+    (event_loop.handle())
+        .insert_source(source, |_, _, _| {})
+        .unwrap();
+    drop(theirs);
+    // A source that is still registered would fail every time with an unexpected EOF.
+    for _ in 0..3 {
+        (event_loop.dispatch(Duration::from_millis(10), &mut ())).unwrap();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Hello<'a> {
+    name: &'a str,
+}
+
+impl<'a> Message<'a> for Hello<'a> {
+    const NAME: &'static str = "Hello";
+}
+
+#[test]
+fn calloop_source_skips_other_messages() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut other = Peer::from_unix_socket(theirs);
+    other.send(Hello { name: "other" }).unwrap();
+    other.send(Ping(2)).unwrap();
+    let mut event_loop = EventLoop::<Vec<u32>>::try_new().unwrap();
+    let source = PeerSource::<Ping>::new(Peer::from_unix_socket(ours));
+    (event_loop.handle())
+        .insert_source(source, |Ping(n), _, received| received.push(n))
+        .unwrap();
+    let mut received = vec![];
+    while received.is_empty() {
+        (event_loop.dispatch(Duration::from_secs(5), &mut received)).unwrap();
+    }
+    assert_eq!(received, [2]);
+}
+
+#[test]
+fn only_some_peers_have_fds() {
+    let (ours, _theirs) = UnixStream::pair().unwrap();
+    assert!(Peer::from_unix_socket(ours).raw_fd().is_some());
+    let mut peer = Peer::new(std::io::empty(), std::io::sink());
+    assert_eq!(peer.raw_fd(), None);
+
+    let poll = Poll::new().unwrap();
+    let err = (poll.registry())
+        .register(&mut peer, Token(0), Interest::READABLE)
+        .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Unsupported);
+
+    let event_loop = EventLoop::<()>::try_new().unwrap();
+    let source = PeerSource::<Ping>::new(peer);
+    assert!(
+        event_loop
+            .handle()
+            .insert_source(source, |_, _, _| {})
+            .is_err()
+    );
+}
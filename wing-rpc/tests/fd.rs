@@ -0,0 +1,201 @@
+#![cfg(unix)]
+use std::{
+    collections::HashMap,
+    io::{ErrorKind, Read, Write},
+    os::{fd::OwnedFd, unix::net::UnixStream},
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Error, Message, Peer, client,
+    server::{Mode, TcpServer},
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Shared {
+    #[serde(with = "wing_rpc::fd")]
+    file: OwnedFd,
+    name: String,
+}
+
+impl Message<'_> for Shared {
+    const NAME: &'static str = "Shared";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Bundle {
+    #[serde(with = "wing_rpc::fd")]
+    files: Vec<OwnedFd>,
+}
+
+impl Message<'_> for Bundle {
+    const NAME: &'static str = "Bundle";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Maybe {
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "wing_rpc::fd"
+    )]
+    file: Option<OwnedFd>,
+}
+
+impl Message<'_> for Maybe {
+    const NAME: &'static str = "Maybe";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Named {
+    #[serde(with = "wing_rpc::fd")]
+    files: HashMap<String, OwnedFd>,
+}
+
+impl Message<'_> for Named {
+    const NAME: &'static str = "Named";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Ack(String);
+
+impl Message<'_> for Ack {
+    const NAME: &'static str = "Ack";
+}
+
+fn pair() -> (Peer, Peer) {
+    let (a, b) = UnixStream::pair().unwrap();
+    (Peer::from_unix_socket(a), Peer::from_unix_socket(b))
+}
+
+/// Writes `text` into the descriptor, which is one end of a [`UnixStream`].
+fn write_to(fd: OwnedFd, text: &str) {
+    UnixStream::from(fd).write_all(text.as_bytes()).unwrap();
+}
+
+fn read_from(mut stream: UnixStream, len: usize) -> String {
+    let mut text = vec![0; len];
+    stream.read_exact(&mut text).unwrap();
+    String::from_utf8(text).unwrap()
+}
+
+#[test]
+fn pass_descriptor() {
+    let (mut sender, mut receiver) = pair();
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    sender
+        .send(Shared {
+            file: theirs.into(),
+            name: "pipe".into(),
+        })
+        .unwrap();
+    let shared = receiver.recv::<Shared>().unwrap();
+    assert_eq!(shared.name, "pipe");
+    write_to(shared.file, "hello");
+    assert_eq!(read_from(ours, 5), "hello");
+}
+
+#[test]
+fn pass_many_descriptors() {
+    let (mut sender, mut receiver) = pair();
+    let (ours, theirs): (Vec<_>, Vec<_>) = (0..3).map(|_| UnixStream::pair().unwrap()).unzip();
+    // Frames without descriptors in between must not get them mixed up.
+    sender.send(Ack("before".into())).unwrap();
+    sender
+        .send(Bundle {
+            files: theirs.into_iter().map(OwnedFd::from).collect(),
+        })
+        .unwrap();
+    sender.send(Ack("after".into())).unwrap();
+
+    assert_eq!(receiver.recv::<Ack>().unwrap().0, "before");
+    let bundle = receiver.recv::<Bundle>().unwrap();
+    assert_eq!(receiver.recv::<Ack>().unwrap().0, "after");
+    for (i, (file, ours)) in bundle.files.into_iter().zip(ours).enumerate() {
+        write_to(file, &i.to_string());
+        assert_eq!(read_from(ours, 1), i.to_string());
+    }
+}
+
+#[test]
+fn pass_optional_descriptor() {
+    let (mut sender, mut receiver) = pair();
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    sender.send(Maybe { file: None }).unwrap();
+    sender
+        .send(Maybe {
+            file: Some(theirs.into()),
+        })
+        .unwrap();
+    assert!(receiver.recv::<Maybe>().unwrap().file.is_none());
+    write_to(receiver.recv::<Maybe>().unwrap().file.unwrap(), "hi");
+    assert_eq!(read_from(ours, 2), "hi");
+}
+
+#[test]
+fn pass_descriptor_map() {
+    let (mut sender, mut receiver) = pair();
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let files = HashMap::from([("pipe".to_owned(), theirs.into())]);
+    sender.send(Named { files }).unwrap();
+    let mut named = receiver.recv::<Named>().unwrap();
+    write_to(named.files.remove("pipe").unwrap(), "map");
+    assert_eq!(read_from(ours, 3), "map");
+}
+
+#[test]
+fn call_with_descriptor() {
+    let (mut caller, mut callee) = pair();
+    let handle = thread::spawn(move || {
+        let call = callee.next_call().unwrap();
+        callee
+            .answer(&call, |shared: Shared| {
+                write_to(shared.file, "called");
+                Ok(Ack(shared.name))
+            })
+            .unwrap();
+    });
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let ack: Ack = caller
+        .call(Shared {
+            file: theirs.into(),
+            name: "call".into(),
+        })
+        .unwrap();
+    assert_eq!(ack.0, "call");
+    assert_eq!(read_from(ours, 6), "called");
+    handle.join().unwrap();
+}
+
+#[test]
+fn only_over_unix_sockets() {
+    let server = TcpServer::bind("localhost:0").unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let mut peer = server.accept(Mode::Blocking).unwrap();
+        assert_eq!(peer.recv::<Ack>().unwrap().0, "still works");
+    });
+    let mut peer = client::tcp(("localhost", port)).unwrap();
+    let (_, theirs) = UnixStream::pair().unwrap();
+    let err = peer
+        .send(Shared {
+            file: theirs.into(),
+            name: "tcp".into(),
+        })
+        .unwrap_err();
+    assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::Unsupported));
+    peer.send(Ack("still works".into())).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn not_outside_of_a_peer() {
+    let (_, theirs) = UnixStream::pair().unwrap();
+    let shared = Shared {
+        file: theirs.into(),
+        name: "json".into(),
+    };
+    let err = serde_json::to_string(&shared).unwrap_err();
+    assert!(err.to_string().contains("through a Peer"));
+}
@@ -0,0 +1,143 @@
+use std::{thread, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Error, Message, Overflow, Peer, PeerBuilder, client,
+    server::{Mode, TcpServer},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Item {
+    n: u32,
+    padding: String,
+}
+
+impl Item {
+    fn new(n: u32) -> Self {
+        Self {
+            n,
+            padding: String::new(),
+        }
+    }
+}
+
+impl Message<'_> for Item {
+    const NAME: &'static str = "Item";
+}
+
+fn connected(serve: impl FnOnce(Peer) + Send + 'static) -> (Peer, thread::JoinHandle<()>) {
+    let server = TcpServer::bind("localhost:0").unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || serve(server.accept(Mode::Blocking).unwrap()));
+    (client::tcp(("localhost", port)).unwrap(), handle)
+}
+
+/// Grants `credit` to the other peer and lets it know.
+fn grant(peer: &mut Peer, credit: u32) {
+    peer.grant(credit).unwrap();
+    peer.send(Item::new(u32::MAX)).unwrap();
+}
+
+/// Waits until the other peer calls [`grant`].
+fn wait_grant(peer: &mut Peer) {
+    assert_eq!(peer.recv::<Item>().unwrap().n, u32::MAX);
+}
+
+#[test]
+fn fail_when_full() {
+    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    let (mut peer, handle) = connected(move |mut peer| {
+        // Don't read anything until the sender gives up.
+        rx.recv().unwrap();
+        while peer.recv::<Item>().unwrap().n != u32::MAX {}
+    });
+    peer.set_send_queue(4, Overflow::Fail);
+    let mut item = Item::new(0);
+    item.padding = "x".repeat(60_000);
+    let err = loop {
+        if let Err(e) = peer.send(item.clone()) {
+            break e;
+        }
+    };
+    assert!(matches!(err, Error::WouldOverflow));
+    assert_eq!(peer.queued(), 4);
+    tx.send(()).unwrap();
+    peer.flush().unwrap();
+    peer.send(Item::new(u32::MAX)).unwrap();
+    peer.flush().unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn block_until_send_timeout() {
+    let (tx, rx) = std::sync::mpsc::channel::<()>();
+    let server = TcpServer::bind("localhost:0").unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        // Keep the connection open without reading anything.
+        let _peer = server.accept(Mode::Blocking).unwrap();
+        rx.recv().unwrap();
+    });
+    let sock = std::net::TcpStream::connect(("localhost", port)).unwrap();
+    let mut peer = PeerBuilder::new()
+        .send_timeout(Duration::from_millis(100))
+        .socket(sock)
+        .unwrap();
+    peer.set_send_queue(4, Overflow::Block);
+    let mut item = Item::new(0);
+    item.padding = "x".repeat(60_000);
+    let err = loop {
+        if let Err(e) = peer.send(item.clone()) {
+            break e;
+        }
+    };
+    assert!(matches!(err, Error::Io(e) if e.kind() == std::io::ErrorKind::TimedOut));
+    assert_eq!(peer.queued(), 4);
+    tx.send(()).unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn credit() {
+    let (mut peer, handle) = connected(|mut peer| {
+        grant(&mut peer, 2);
+        for n in 0..2 {
+            assert_eq!(peer.recv::<Item>().unwrap().n, n);
+        }
+        peer.grant(3).unwrap();
+        for n in 2..5 {
+            assert_eq!(peer.recv::<Item>().unwrap().n, n);
+        }
+    });
+    wait_grant(&mut peer);
+    assert_eq!(peer.credit(), Some(2));
+    peer.set_send_queue(16, Overflow::Fail);
+    for n in 0..5 {
+        peer.send(Item::new(n)).unwrap();
+    }
+    assert_eq!(peer.credit(), Some(0));
+    assert_eq!(peer.queued(), 3);
+    peer.flush().unwrap();
+    assert_eq!(peer.credit(), Some(0));
+    handle.join().unwrap();
+}
+
+#[test]
+fn drop_oldest() {
+    let (mut peer, handle) = connected(|mut peer| {
+        grant(&mut peer, 0);
+        thread::sleep(Duration::from_millis(200));
+        peer.grant(10).unwrap();
+        for n in 3..5 {
+            assert_eq!(peer.recv::<Item>().unwrap().n, n);
+        }
+    });
+    wait_grant(&mut peer);
+    peer.set_send_queue(2, Overflow::DropOldest);
+    for n in 0..5 {
+        peer.send(Item::new(n)).unwrap();
+    }
+    assert_eq!(peer.queued(), 2);
+    peer.flush().unwrap();
+    handle.join().unwrap();
+}
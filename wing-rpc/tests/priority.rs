@@ -0,0 +1,133 @@
+#![cfg(unix)]
+use std::{os::unix::net::UnixStream, thread};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{Message, Overflow, Peer, Priority};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum Transfer {
+    Chunk { index: u32, payload: String },
+    Stop,
+}
+
+impl Message<'_> for Transfer {
+    const NAME: &'static str = "Transfer";
+}
+
+fn chunk(index: u32) -> Transfer {
+    Transfer::Chunk {
+        index,
+        payload: "x".repeat(60_000),
+    }
+}
+
+fn small(index: u32) -> Transfer {
+    Transfer::Chunk {
+        index,
+        payload: String::new(),
+    }
+}
+
+fn index(message: &Transfer) -> Option<u32> {
+    match message {
+        Transfer::Chunk { index, .. } => Some(*index),
+        Transfer::Stop => None,
+    }
+}
+
+#[test]
+fn urgent_jumps_the_queue() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut sender = Peer::from_unix_socket(ours);
+    sender.set_send_queue(1000, Overflow::Block);
+    for i in 0..100 {
+        sender.send(chunk(i)).unwrap();
+    }
+    assert!(sender.queued() > 1, "Socket buffers took every chunk");
+    sender
+        .send_with_priority(Transfer::Stop, Priority::Urgent)
+        .unwrap();
+
+    let receiver = thread::spawn(move || {
+        let mut peer = Peer::from_unix_socket(theirs);
+        (0..101)
+            .map(|_| index(&peer.recv::<Transfer>().unwrap()))
+            .collect::<Vec<_>>()
+    });
+    sender.flush().unwrap();
+    let received = receiver.join().unwrap();
+    let stop = received.iter().position(Option::is_none).unwrap();
+    assert!(stop < 100, "Stop was sent after every chunk");
+    // Chunks are still received in order around it.
+    let chunks: Vec<_> = received.into_iter().flatten().collect();
+    assert_eq!(chunks, (0..100).collect::<Vec<_>>());
+}
+
+#[test]
+fn urgent_skips_overflow() {
+    let (ours, _theirs) = UnixStream::pair().unwrap();
+    let mut sender = Peer::from_unix_socket(ours);
+    sender.set_send_queue(2, Overflow::Fail);
+    let mut sent = 0;
+    while sender.send(chunk(sent)).is_ok() {
+        sent += 1;
+    }
+    let queued = sender.queued();
+    sender
+        .send_with_priority(Transfer::Stop, Priority::Urgent)
+        .unwrap();
+    assert_eq!(sender.queued(), queued + 1);
+}
+
+#[test]
+fn recv_highest_priority_first() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut sender = Peer::from_unix_socket(ours);
+    let mut receiver = Peer::from_unix_socket(theirs);
+    sender.send(small(0)).unwrap();
+    sender.send_with_priority(small(1), Priority::High).unwrap();
+    sender
+        .send_with_priority(Transfer::Stop, Priority::Urgent)
+        .unwrap();
+    sender.send_with_priority(small(2), Priority::High).unwrap();
+
+    let mut next = || index(&receiver.recv_prioritized::<Transfer>().unwrap());
+    assert_eq!(next(), None);
+    assert_eq!(next(), Some(1));
+    assert_eq!(next(), Some(2));
+    assert_eq!(next(), Some(0));
+}
+
+#[test]
+fn plain_recv_keeps_order() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut sender = Peer::from_unix_socket(ours);
+    let mut receiver = Peer::from_unix_socket(theirs);
+    sender.send(small(0)).unwrap();
+    sender
+        .send_with_priority(Transfer::Stop, Priority::Urgent)
+        .unwrap();
+    assert_eq!(index(&receiver.recv::<Transfer>().unwrap()), Some(0));
+    assert_eq!(receiver.recv::<Transfer>().unwrap(), Transfer::Stop);
+}
+
+#[test]
+fn drop_oldest_of_the_lowest_priority() {
+    let (ours, theirs) = UnixStream::pair().unwrap();
+    let mut sender = Peer::from_unix_socket(ours);
+    let mut receiver = Peer::from_unix_socket(theirs);
+    // Without credit, everything stays queued.
+    receiver.grant(0).unwrap();
+    receiver.send(Transfer::Stop).unwrap();
+    assert_eq!(sender.recv::<Transfer>().unwrap(), Transfer::Stop);
+    sender.set_send_queue(2, Overflow::DropOldest);
+    sender.send_with_priority(small(0), Priority::High).unwrap();
+    sender.send(small(1)).unwrap();
+    sender.send(small(2)).unwrap();
+    assert_eq!(sender.queued(), 2);
+
+    receiver.grant(10).unwrap();
+    sender.flush().unwrap();
+    assert_eq!(index(&receiver.recv::<Transfer>().unwrap()), Some(0));
+    assert_eq!(index(&receiver.recv::<Transfer>().unwrap()), Some(2));
+}
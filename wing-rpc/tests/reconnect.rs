@@ -0,0 +1,202 @@
+use std::{
+    io,
+    net::TcpListener,
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Error, Message, PeerBuilder, Priority,
+    client::{Backoff, ReconnectingPeer},
+    server::{Mode, TcpServer},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Ping {
+    text: String,
+}
+
+impl Message<'_> for Ping {
+    const NAME: &'static str = "Ping";
+}
+
+fn ping(text: &str) -> Ping {
+    Ping { text: text.into() }
+}
+
+fn fast_backoff() -> Backoff {
+    Backoff {
+        initial: Duration::from_millis(5),
+        max: Duration::from_millis(50),
+        ..Default::default()
+    }
+}
+
+fn unused_port() -> u16 {
+    TcpListener::bind("localhost:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[test]
+fn reconnects_after_server_drops() {
+    let server = TcpServer::bind("localhost:0").unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let mut first = server.accept(Mode::Blocking).unwrap();
+        assert_eq!(first.recv::<Ping>().unwrap(), ping("first"));
+        drop(first);
+        let mut second = server.accept(Mode::Blocking).unwrap();
+        assert_eq!(second.recv::<Ping>().unwrap(), ping("reconnected"));
+        second.send(ping("welcome back")).unwrap();
+    });
+    let mut peer = ReconnectingPeer::tcp(("localhost", port))
+        .unwrap()
+        .backoff(fast_backoff())
+        .on_reconnect(|peer| peer.send(ping("reconnected")));
+    peer.send(ping("first")).unwrap();
+    assert_eq!(peer.recv::<Ping>().unwrap(), ping("welcome back"));
+    handle.join().unwrap();
+}
+
+#[test]
+fn buffers_while_disconnected() {
+    // Reserve a port nobody listens on yet.
+    let port = unused_port();
+    let mut peer = ReconnectingPeer::tcp(("localhost", port))
+        .unwrap()
+        .backoff(fast_backoff())
+        .max_pending(2);
+    peer.send(ping("one")).unwrap();
+    peer.send(ping("two")).unwrap();
+    assert!(matches!(
+        peer.send(ping("three")),
+        Err(Error::WouldOverflow)
+    ));
+    assert!(!peer.is_connected());
+
+    let server = TcpServer::bind(("localhost", port)).unwrap();
+    let handle = thread::spawn(move || {
+        let mut peer = server.accept(Mode::Blocking).unwrap();
+        assert_eq!(peer.recv::<Ping>().unwrap(), ping("one"));
+        assert_eq!(peer.recv::<Ping>().unwrap(), ping("two"));
+        peer.send(ping("done")).unwrap();
+    });
+    assert_eq!(peer.recv::<Ping>().unwrap(), ping("done"));
+    handle.join().unwrap();
+}
+
+#[test]
+fn buffered_messages_keep_their_priority() {
+    let port = unused_port();
+    let mut peer = ReconnectingPeer::tcp(("localhost", port))
+        .unwrap()
+        .backoff(Backoff {
+            initial: Duration::ZERO,
+            max: Duration::ZERO,
+            ..Default::default()
+        });
+    peer.send(ping("normal")).unwrap();
+    peer.send_with_priority(ping("urgent"), Priority::Urgent)
+        .unwrap();
+    assert!(!peer.is_connected());
+
+    let server = TcpServer::bind(("localhost", port)).unwrap();
+    // Both buffered messages are delivered as soon as the connection is made.
+    peer.send(ping("last")).unwrap();
+    assert!(peer.is_connected());
+    let mut server = server.accept(Mode::Blocking).unwrap();
+    assert_eq!(server.recv_prioritized::<Ping>().unwrap(), ping("urgent"));
+    assert_eq!(server.recv::<Ping>().unwrap(), ping("normal"));
+}
+
+#[test]
+fn recv_times_out_while_disconnected() {
+    let mut peer = ReconnectingPeer::tcp(("localhost", unused_port()))
+        .unwrap()
+        .backoff(fast_backoff())
+        .peer_builder(PeerBuilder::new().recv_timeout(Duration::from_millis(100)));
+    let start = Instant::now();
+    let Err(Error::Io(e)) = peer.recv::<Ping>() else {
+        panic!("Received a message without a server");
+    };
+    assert_eq!(e.kind(), io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[test]
+fn backoff_grows_up_to_max() {
+    let backoff = Backoff {
+        initial: Duration::from_millis(100),
+        max: Duration::from_secs(1),
+        multiplier: 2.0,
+        jitter: 0.0,
+    };
+    let delays: Vec<_> = backoff.delays().take(6).map(|d| d.as_millis()).collect();
+    assert_eq!(delays, [100, 200, 400, 800, 1000, 1000]);
+}
+
+#[test]
+fn jitter_stays_within_bounds() {
+    let backoff = Backoff {
+        initial: Duration::from_millis(100),
+        max: Duration::from_secs(1),
+        multiplier: 2.0,
+        jitter: 0.5,
+    };
+    let unjittered = Backoff {
+        jitter: 0.0,
+        ..backoff.clone()
+    };
+    for _ in 0..100 {
+        for (delay, base) in backoff.delays().zip(unjittered.delays()).take(6) {
+            assert!(
+                delay >= base.mul_f64(0.5),
+                "{delay:?} is too short for {base:?}"
+            );
+            assert!(
+                delay <= base.mul_f64(1.5),
+                "{delay:?} is too long for {base:?}"
+            );
+        }
+    }
+    // Jitter larger than the delay itself shrinks it to zero at most, instead of panicking.
+    let wild = Backoff {
+        jitter: 3.0,
+        ..backoff
+    };
+    assert!(
+        wild.delays()
+            .take(100)
+            .all(|delay| delay <= Duration::from_secs(4))
+    );
+}
+
+#[test]
+fn oversized_message_keeps_the_connection() {
+    let server = TcpServer::bind("localhost:0").unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let mut peer = server.accept(Mode::Blocking).unwrap();
+        assert_eq!(peer.recv::<Ping>().unwrap(), ping("before"));
+        assert_eq!(peer.recv::<Ping>().unwrap(), ping("after"));
+        peer.send(ping("done")).unwrap();
+    });
+    let mut peer = ReconnectingPeer::tcp(("localhost", port))
+        .unwrap()
+        .backoff(fast_backoff())
+        .peer_builder(PeerBuilder::new().max_frame_size(100));
+    peer.send(ping("before")).unwrap();
+    assert!(peer.is_connected());
+    let Err(Error::Io(e)) = peer.send(ping(&"x".repeat(100))) else {
+        panic!("Sent a message over the limit");
+    };
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    assert!(peer.is_connected());
+    peer.send(ping("after")).unwrap();
+    assert_eq!(peer.recv::<Ping>().unwrap(), ping("done"));
+    handle.join().unwrap();
+}
@@ -0,0 +1,271 @@
+#![cfg(target_os = "linux")]
+use std::{
+    io::ErrorKind,
+    os::{
+        fd::{AsRawFd, FromRawFd, OwnedFd},
+        unix::net::UnixStream,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{Error, Message, PeerBuilder, Timeout, shm::SharedMemory};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Reading {
+    sensor: u32,
+    values: Vec<f64>,
+}
+
+impl Message<'_> for Reading {
+    const NAME: &'static str = "Reading";
+}
+
+fn reading(sensor: u32, len: usize) -> Reading {
+    Reading {
+        sensor,
+        values: (0..len).map(|v| v as f64 / 4.0).collect(),
+    }
+}
+
+#[test]
+fn echo() {
+    let (ours, theirs) = SharedMemory::pair(4096).unwrap();
+    let builder = PeerBuilder::new();
+    let mut peer = builder.shared_memory(ours).unwrap();
+    let mut other = builder.shared_memory(theirs).unwrap();
+    let handle = thread::spawn(move || {
+        for _ in 0..100 {
+            let reading = other.recv::<Reading>().unwrap();
+            other.send(reading).unwrap();
+        }
+    });
+    for sensor in 0..100 {
+        peer.send(reading(sensor, 3)).unwrap();
+        assert_eq!(peer.recv::<Reading>().unwrap(), reading(sensor, 3));
+    }
+    handle.join().unwrap();
+}
+
+#[test]
+fn frames_larger_than_the_ring() {
+    // Every frame has to wrap around, and the writer has to wait for the reader.
+    let (ours, theirs) = SharedMemory::pair(64).unwrap();
+    let builder = PeerBuilder::new();
+    let mut peer = builder.shared_memory(ours).unwrap();
+    let mut other = builder.shared_memory(theirs).unwrap();
+    let handle = thread::spawn(move || {
+        for sensor in 0..50 {
+            assert_eq!(other.recv::<Reading>().unwrap(), reading(sensor, 100));
+        }
+    });
+    peer.send_batch((0..50).map(|sensor| reading(sensor, 100)))
+        .unwrap();
+    handle.join().unwrap();
+}
+
+#[test]
+fn handed_over_a_unix_socket() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let handle = thread::spawn(move || {
+        let shm = SharedMemory::recv(&b).unwrap();
+        let mut peer = PeerBuilder::new().shared_memory(shm).unwrap();
+        let reading = peer.recv::<Reading>().unwrap();
+        peer.send(reading).unwrap();
+    });
+    let (ours, theirs) = SharedMemory::pair(1 << 16).unwrap();
+    theirs.send(&a).unwrap();
+    let mut peer = PeerBuilder::new().shared_memory(ours).unwrap();
+    peer.send(reading(7, 10)).unwrap();
+    assert_eq!(peer.recv::<Reading>().unwrap(), reading(7, 10));
+    handle.join().unwrap();
+}
+
+#[test]
+fn notices_when_closed() {
+    let (ours, theirs) = SharedMemory::pair(4096).unwrap();
+    let mut peer = PeerBuilder::new().shared_memory(ours).unwrap();
+    let other = PeerBuilder::new().shared_memory(theirs).unwrap();
+    thread::spawn(move || drop(other)).join().unwrap();
+    let err = peer.recv::<Reading>().unwrap_err();
+    assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::UnexpectedEof));
+    let err = peer.send(reading(1, 1)).unwrap_err();
+    assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::BrokenPipe));
+}
+
+#[test]
+fn follows_timeouts() {
+    let (ours, theirs) = SharedMemory::pair(4096).unwrap();
+    let mut peer = PeerBuilder::new().shared_memory(ours).unwrap();
+    let mut other = PeerBuilder::new().shared_memory(theirs).unwrap();
+    assert!(
+        peer.try_recv::<Reading>(Timeout::DontBlock)
+            .unwrap()
+            .is_none()
+    );
+    let start = Instant::now();
+    let timeout = Timeout::WaitFor(Duration::from_millis(50));
+    assert!(peer.try_recv::<Reading>(timeout).unwrap().is_none());
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    other.send(reading(3, 2)).unwrap();
+    let received = peer.try_recv::<Reading>(Timeout::DontBlock).unwrap();
+    assert_eq!(received, Some(reading(3, 2)));
+    assert!(
+        peer.try_recv::<Reading>(Timeout::DontBlock)
+            .unwrap()
+            .is_none()
+    );
+}
+
+#[test]
+fn writer_gives_up_on_a_stalled_reader() {
+    let (ours, theirs) = SharedMemory::pair(64).unwrap();
+    let mut peer = PeerBuilder::new()
+        .send_timeout(Duration::from_millis(50))
+        .shared_memory(ours)
+        .unwrap();
+    // Never reads anything.
+    let _other = PeerBuilder::new().shared_memory(theirs).unwrap();
+    let start = Instant::now();
+    let Err(Error::Io(e)) = peer.send(reading(5, 100)) else {
+        panic!("A frame larger than the ring was sent without a reader");
+    };
+    assert_eq!(e.kind(), std::io::ErrorKind::TimedOut);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+}
+
+#[test]
+fn wakes_up_a_waiting_reader() {
+    let (ours, theirs) = SharedMemory::pair(4096).unwrap();
+    let mut peer = PeerBuilder::new().shared_memory(ours).unwrap();
+    let handle = thread::spawn(move || {
+        let mut other = PeerBuilder::new().shared_memory(theirs).unwrap();
+        thread::sleep(Duration::from_millis(20));
+        other.send(reading(4, 1)).unwrap();
+        other
+    });
+    let timeout = Timeout::WaitFor(Duration::from_secs(5));
+    assert_eq!(
+        peer.try_recv::<Reading>(timeout).unwrap(),
+        Some(reading(4, 1))
+    );
+    handle.join().unwrap();
+}
+
+/// The size of a ring's header, which comes before its data.
+const HEADER_LEN: usize = 192;
+
+/// Sends a handshake for `memfd` the way [`SharedMemory::send`] does, but without any checks.
+fn handshake(sock: &UnixStream, capacity: u64, memfd: &OwnedFd) -> [OwnedFd; 4] {
+    // SAFETY: `eventfd` has no preconditions, and the new descriptors are owned by us.
+    let events = [(); 4].map(|_| unsafe { OwnedFd::from_raw_fd(libc::eventfd(0, 0)) });
+    let mut info = [1u8; 9];
+    info[1..].copy_from_slice(&capacity.to_le_bytes());
+    let fds = [memfd.as_raw_fd()]
+        .into_iter()
+        .chain(events.iter().map(AsRawFd::as_raw_fd))
+        .collect::<Vec<_>>();
+    let mut iov = libc::iovec {
+        iov_base: info.as_mut_ptr().cast(),
+        iov_len: info.len(),
+    };
+    let mut control = [0u64; 8];
+    // SAFETY: All zeroes is a valid `msghdr`, which only points to memory that outlives it, and
+    // `control` is big enough for five descriptors.
+    unsafe {
+        let mut msg: libc::msghdr = std::mem::zeroed();
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr().cast();
+        msg.msg_controllen = libc::CMSG_SPACE(size_of_val(&fds[..]) as u32) as _;
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(size_of_val(&fds[..]) as u32) as _;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg).cast(), fds.len());
+        assert_eq!(
+            libc::sendmsg(sock.as_raw_fd(), &msg, 0),
+            info.len() as isize
+        );
+    }
+    events
+}
+
+/// Creates a memfd of `len` bytes, sealing it against shrinking if `seal` is set.
+fn memfd(len: usize, seal: bool) -> OwnedFd {
+    // SAFETY: The name is a valid C string, and the new descriptor is owned by us.
+    let memfd = unsafe {
+        OwnedFd::from_raw_fd(libc::memfd_create(
+            c"test".as_ptr(),
+            libc::MFD_ALLOW_SEALING,
+        ))
+    };
+    // SAFETY: `memfd` is a valid descriptor.
+    unsafe {
+        assert_eq!(libc::ftruncate(memfd.as_raw_fd(), len as libc::off_t), 0);
+        if seal {
+            assert_eq!(
+                libc::fcntl(memfd.as_raw_fd(), libc::F_ADD_SEALS, libc::F_SEAL_SHRINK),
+                0
+            );
+        }
+    }
+    memfd
+}
+
+fn assert_invalid_data(error: std::io::Error) {
+    assert_eq!(error.kind(), ErrorKind::InvalidData, "{error}");
+}
+
+#[test]
+fn rejects_a_memfd_smaller_than_its_rings() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let _events = handshake(&a, 1 << 20, &memfd(4096, true));
+    let shm = SharedMemory::recv(&b).unwrap();
+    assert_invalid_data(PeerBuilder::new().shared_memory(shm).err().unwrap());
+}
+
+#[test]
+fn rejects_rings_too_large_to_map() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let _events = handshake(&a, 1 << 63, &memfd(4096, true));
+    let shm = SharedMemory::recv(&b).unwrap();
+    assert_invalid_data(PeerBuilder::new().shared_memory(shm).err().unwrap());
+}
+
+#[test]
+fn rejects_a_memfd_that_can_shrink() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let _events = handshake(&a, 64, &memfd(2 * (HEADER_LEN + 64), false));
+    let shm = SharedMemory::recv(&b).unwrap();
+    assert_invalid_data(PeerBuilder::new().shared_memory(shm).err().unwrap());
+}
+
+#[test]
+fn rejects_a_corrupted_ring() {
+    let (a, b) = UnixStream::pair().unwrap();
+    let memfd = memfd(2 * (HEADER_LEN + 64), true);
+    let events = handshake(&a, 64, &memfd);
+    let mut peer = PeerBuilder::new()
+        .shared_memory(SharedMemory::recv(&b).unwrap())
+        .unwrap();
+    // The handshake picked the second side, so the peer reads the first ring, whose head comes
+    // first. Move it way past the capacity of the ring, and wake the peer up.
+    let head = u64::MAX / 2;
+    // SAFETY: `head` is valid for reads of 8 bytes, and both descriptors are valid.
+    unsafe {
+        assert_eq!(
+            libc::pwrite(memfd.as_raw_fd(), (&raw const head).cast(), 8, 0),
+            8
+        );
+        assert_eq!(
+            libc::write(events[0].as_raw_fd(), (&raw const head).cast(), 8),
+            8
+        );
+    }
+    match peer.recv::<Reading>().unwrap_err() {
+        Error::Io(e) => assert_invalid_data(e),
+        e => panic!("Unexpected error: {e}"),
+    }
+}
@@ -0,0 +1,122 @@
+use std::{io::ErrorKind, net::TcpStream, sync::Arc, thread, time::Duration};
+
+use rcgen::{CertifiedKey, generate_simple_self_signed};
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Error, Message, client,
+    rustls::{
+        self, RootCertStore,
+        pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer},
+        server::WebPkiClientVerifier,
+    },
+    server::{Mode, TcpServer},
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Ping {
+    text: String,
+}
+
+impl Message<'_> for Ping {
+    const NAME: &'static str = "Ping";
+}
+
+fn self_signed() -> (CertificateDer<'static>, PrivateKeyDer<'static>) {
+    let CertifiedKey { cert, signing_key } =
+        generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let key = PrivatePkcs8KeyDer::from(signing_key.serialize_der());
+    (cert.der().clone(), key.into())
+}
+
+fn roots(cert: &CertificateDer<'static>) -> RootCertStore {
+    let mut roots = RootCertStore::empty();
+    roots.add(cert.clone()).unwrap();
+    roots
+}
+
+fn ping_pong(server: TcpServer, client_config: rustls::ClientConfig) {
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || {
+        let mut peer = server.accept(Mode::Blocking).unwrap();
+        let ping: Ping = peer.recv().unwrap();
+        peer.send(ping).unwrap();
+    });
+    let mut peer = client::tls(&format!("localhost:{port}"), Arc::new(client_config)).unwrap();
+    let ping = Ping {
+        text: "hello".into(),
+    };
+    peer.send(ping.clone()).unwrap();
+    assert_eq!(peer.recv::<Ping>().unwrap(), ping);
+    handle.join().unwrap();
+}
+
+#[test]
+fn tls_roundtrip() {
+    let (cert, key) = self_signed();
+    let server_config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.clone()], key)
+        .unwrap();
+    let server = TcpServer::bind_tls("localhost:0", Arc::new(server_config)).unwrap();
+    let client_config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots(&cert))
+        .with_no_client_auth();
+    ping_pong(server, client_config);
+}
+
+#[test]
+fn tls_client_auth() {
+    let (server_cert, server_key) = self_signed();
+    let (client_cert, client_key) = self_signed();
+    let verifier = WebPkiClientVerifier::builder(Arc::new(roots(&client_cert)))
+        .build()
+        .unwrap();
+    let server_config = rustls::ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(vec![server_cert.clone()], server_key)
+        .unwrap();
+    let server = TcpServer::bind_tls("localhost:0", Arc::new(server_config)).unwrap();
+    let client_config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots(&server_cert))
+        .with_client_auth_cert(vec![client_cert], client_key)
+        .unwrap();
+    ping_pong(server, client_config);
+}
+
+#[test]
+fn tls_rejects_missing_client_cert() {
+    let (server_cert, server_key) = self_signed();
+    let (client_cert, _) = self_signed();
+    let verifier = WebPkiClientVerifier::builder(Arc::new(roots(&client_cert)))
+        .build()
+        .unwrap();
+    let server_config = rustls::ServerConfig::builder()
+        .with_client_cert_verifier(verifier)
+        .with_single_cert(vec![server_cert.clone()], server_key)
+        .unwrap();
+    let server = TcpServer::bind_tls("localhost:0", Arc::new(server_config)).unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || server.accept(Mode::Blocking).is_err());
+    let client_config = rustls::ClientConfig::builder()
+        .with_root_certificates(roots(&server_cert))
+        .with_no_client_auth();
+    let _ = client::tls(&format!("localhost:{port}"), Arc::new(client_config));
+    assert!(handle.join().unwrap());
+}
+
+#[test]
+fn tls_handshake_times_out() {
+    let (cert, key) = self_signed();
+    let server_config = rustls::ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(vec![cert.clone()], key)
+        .unwrap();
+    let server = TcpServer::bind_tls("localhost:0", Arc::new(server_config))
+        .unwrap()
+        .with_handshake_timeout(Duration::from_millis(100));
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || server.accept(Mode::Blocking).err());
+    let _silent = TcpStream::connect(("localhost", port)).unwrap();
+    let error = handle.join().unwrap();
+    assert!(matches!(error, Some(Error::Io(e)) if e.kind() == ErrorKind::TimedOut));
+}
@@ -0,0 +1,78 @@
+#![cfg(unix)]
+use std::os::unix::net::UnixStream;
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{Error, Message, Peer, TypedPeer};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ClientMessage {
+    Join(String),
+    Say { text: String },
+}
+
+impl Message<'_> for ClientMessage {
+    const NAME: &'static str = "ClientMessage";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum ServerMessage {
+    Welcome(u32),
+    Said { from: u32, text: String },
+}
+
+impl Message<'_> for ServerMessage {
+    const NAME: &'static str = "ServerMessage";
+}
+
+fn pair() -> (
+    TypedPeer<ServerMessage, ClientMessage>,
+    TypedPeer<ClientMessage, ServerMessage>,
+) {
+    let (client, server) = UnixStream::pair().unwrap();
+    (
+        Peer::from_unix_socket(client).into(),
+        Peer::from_unix_socket(server).into(),
+    )
+}
+
+#[test]
+fn exchange() {
+    let (mut client, mut server) = pair();
+    client.send(ClientMessage::Join("general".into())).unwrap();
+    assert_eq!(
+        server.recv().unwrap(),
+        ClientMessage::Join("general".into())
+    );
+    server.send(ServerMessage::Welcome(3)).unwrap();
+    assert_eq!(client.recv().unwrap(), ServerMessage::Welcome(3));
+
+    client
+        .feed(ClientMessage::Say { text: "hi".into() })
+        .unwrap();
+    client.flush().unwrap();
+    let ClientMessage::Say { text } = server.recv().unwrap() else {
+        panic!("expected Say");
+    };
+    server.send(ServerMessage::Said { from: 3, text }).unwrap();
+    assert_eq!(
+        client.recv().unwrap(),
+        ServerMessage::Said {
+            from: 3,
+            text: "hi".into()
+        }
+    );
+}
+
+#[test]
+fn rejects_other_messages() {
+    let (client, mut server) = pair();
+    let mut client = client.into_inner();
+    client.send(ServerMessage::Welcome(1)).unwrap();
+    let Err(Error::UnexpectedMessage { expected, got }) = server.recv() else {
+        panic!("expected UnexpectedMessage");
+    };
+    assert_eq!(expected, "ClientMessage");
+    assert_eq!(got, "ServerMessage");
+    let message = server.peer_mut().recv::<ServerMessage>().unwrap();
+    assert_eq!(message, ServerMessage::Welcome(1));
+}
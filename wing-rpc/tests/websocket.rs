@@ -0,0 +1,106 @@
+use std::{io::ErrorKind, net::TcpStream, thread};
+
+use serde::{Deserialize, Serialize};
+use wing_rpc::{
+    Error, Message, Peer, client,
+    server::{Mode, WsServer},
+    tungstenite,
+};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+impl Message<'_> for Point {
+    const NAME: &'static str = "Point";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Distance(f64);
+
+impl Message<'_> for Distance {
+    const NAME: &'static str = "Distance";
+}
+
+fn serve(serve: impl FnOnce(Peer) + Send + 'static) -> (String, thread::JoinHandle<()>) {
+    let server = WsServer::bind("localhost:0").unwrap();
+    let port = server.listener().local_addr().unwrap().port();
+    let handle = thread::spawn(move || serve(server.accept(Mode::Blocking).unwrap()));
+    (format!("ws://localhost:{port}"), handle)
+}
+
+#[test]
+fn echo() {
+    let (url, handle) = serve(|mut peer| {
+        for _ in 0..10 {
+            let point = peer.recv::<Point>().unwrap();
+            peer.send(point).unwrap();
+        }
+    });
+    let mut peer = client::websocket(&url).unwrap();
+    for x in 0..10 {
+        peer.send(Point { x, y: -x }).unwrap();
+        assert_eq!(peer.recv::<Point>().unwrap(), Point { x, y: -x });
+    }
+    handle.join().unwrap();
+}
+
+#[test]
+fn calls() {
+    let (url, handle) = serve(|mut peer| {
+        let call = peer.next_call().unwrap();
+        peer.answer(&call, |p: Point| {
+            Ok(Distance(f64::from(p.x * p.x + p.y * p.y).sqrt()))
+        })
+        .unwrap();
+    });
+    let mut peer = client::websocket(&url).unwrap();
+    let distance: Distance = peer.call(Point { x: 3, y: 4 }).unwrap();
+    assert_eq!(distance, Distance(5.0));
+    handle.join().unwrap();
+}
+
+/// Frames a message the way a browser would, without any help from the runtime.
+fn frame(json: &str) -> Vec<u8> {
+    let mut frame = vec![0];
+    frame.extend_from_slice(&(json.len() as u16).to_le_bytes());
+    frame.extend_from_slice(json.as_bytes());
+    frame
+}
+
+#[test]
+fn one_frame_per_message() {
+    let (url, handle) = serve(|mut peer| {
+        let point = peer.recv::<Point>().unwrap();
+        peer.send(Point {
+            x: point.y,
+            y: point.x,
+        })
+        .unwrap();
+    });
+    let sock = TcpStream::connect(url.trim_start_matches("ws://")).unwrap();
+    let (mut ws, _) = tungstenite::client(url.as_str(), sock).unwrap();
+    let json = r#"{"type":"Point","data":{"x":1,"y":2}}"#;
+    ws.send(tungstenite::Message::binary(frame(json))).unwrap();
+    let reply = ws.read().unwrap();
+    assert_eq!(
+        reply.into_data(),
+        frame(r#"{"type":"Point","data":{"x":2,"y":1}}"#)
+    );
+    handle.join().unwrap();
+}
+
+#[test]
+fn reject_text_messages() {
+    let (url, handle) = serve(|mut peer| {
+        let err = peer.recv::<Point>().unwrap_err();
+        assert!(matches!(err, Error::Io(e) if e.kind() == ErrorKind::InvalidData));
+    });
+    let sock = TcpStream::connect(url.trim_start_matches("ws://")).unwrap();
+    let (mut ws, _) = tungstenite::client(url.as_str(), sock).unwrap();
+    ws.send(tungstenite::Message::text(r#"{"type":"Point"}"#))
+        .unwrap();
+    handle.join().unwrap();
+}
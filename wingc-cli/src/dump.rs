@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    path::PathBuf,
+};
+
+use argh::FromArgs;
+use miette::{Context, IntoDiagnostic, Result as R, bail};
+use serde_json::Value;
+use wing_rpc::WirePacket;
+use wingc::{
+    loader,
+    parser::{self, Attributes, Builtin, Struct, StructField, Type, UserType},
+};
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "dump",
+    description = "Decodes a stream of wing frames, such as a capture file or bytes piped into stdin"
+)]
+pub struct DumpArgs {
+    #[argh(option, short = 's')]
+    #[argh(description = "a wing file used to validate the payload of each frame.")]
+    schema: Option<PathBuf>,
+    #[argh(positional)]
+    #[argh(description = "the file to read frames from. Reads from stdin if not specified.")]
+    input: Option<PathBuf>,
+}
+
+/// All user types of a schema, indexed by name.
+struct Schema {
+    types: HashMap<String, UserType>,
+}
+
+impl Schema {
+    fn new(schema: &loader::Schema) -> Self {
+        let mut types = HashMap::new();
+        for document in schema.files.iter().map(|file| &file.document) {
+            for ut in (document.user_types.iter()).flat_map(|ut| ut.children_user_types()) {
+                // Messages may be sent with a name qualified by their package.
+                if let Some(package) = &document.package {
+                    types.insert(format!("{package}.{}", ut.wire_name()), ut.clone());
+                }
+                // Types are used by name in schemas, but may be sent with another one.
+                types.insert(ut.wire_name().to_owned(), ut.clone());
+                types.insert(ut.name().to_owned(), ut.clone());
+            }
+        }
+        Self { types }
+    }
+
+    fn validate(&self, name: &str, value: &Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        match self.types.get(name) {
+            Some(ut) => self.validate_user(ut, value, "data", &mut errors),
+            // Instantiations of generic types are sent with their type arguments.
+            None => match parser::parse_type(name) {
+                Ok(typ @ Type::Generic(..)) => self.validate_type(&typ, value, "data", &mut errors),
+                _ => errors.push(format!("type '{name}' is not defined in the schema")),
+            },
+        }
+        errors
+    }
+
+    fn validate_user(&self, ut: &UserType, value: &Value, path: &str, errors: &mut Vec<String>) {
+        match ut {
+            UserType::Struct(st) => {
+                let Some(object) = value.as_object() else {
+                    errors.push(format!("{path}: expected struct '{}'", st.name));
+                    return;
+                };
+                let fields = self.wire_fields(st);
+                for (key, field) in fields.iter() {
+                    let path = format!("{path}.{key}");
+                    match object.get(key) {
+                        Some(value) => self.validate_type(&field.typ, value, &path, errors),
+                        None if matches!(field.typ, Type::Optional(_)) => {}
+                        None if field.default.is_some() => {}
+                        None => errors.push(format!("{path}: missing field")),
+                    }
+                }
+                for key in object.keys() {
+                    if !fields.iter().any(|(name, _)| name == key) {
+                        errors.push(format!("{path}.{key}: unknown field"));
+                    }
+                }
+            }
+            UserType::Enum(en) => {
+                // Unit variants are sent as their bare tag.
+                if let Some(tag) = value.as_str() {
+                    if !en.units().any(|unit| unit.tag() == tag) {
+                        errors.push(format!(
+                            "{path}: unknown unit variant '{tag}' of enum '{}'",
+                            en.name
+                        ));
+                    }
+                    return;
+                }
+                let variant = value
+                    .as_object()
+                    .filter(|object| object.len() == 1)
+                    .and_then(|object| object.iter().next());
+                let Some((tag, value)) = variant else {
+                    errors.push(format!(
+                        "{path}: expected enum '{}' with a single variant",
+                        en.name
+                    ));
+                    return;
+                };
+                match en.variants().find(|var| var.wire_name() == tag) {
+                    Some(var) => {
+                        self.validate_type(&var.typ, value, &format!("{path}.{tag}"), errors)
+                    }
+                    None => errors.push(format!(
+                        "{path}: unknown variant '{tag}' of enum '{}'",
+                        en.name
+                    )),
+                }
+            }
+            UserType::Newtype(nt) => self.validate_type(&nt.typ, value, path, errors),
+        }
+    }
+
+    /// The fields sent for `st`, along with their key. Skipped fields are left out, and flattened
+    /// ones are replaced by the fields of the struct they hold.
+    fn wire_fields(&self, st: &Struct) -> Vec<(String, StructField)> {
+        let mut fields = vec![];
+        for field in st.fields.iter() {
+            if field.attrs.has_attr("skip") {
+                continue;
+            }
+            let inner = match &field.typ {
+                Type::User(name) => self.types.get(name).cloned(),
+                Type::Generic(name, args) => self.types.get(name).map(|ut| ut.instantiate(args)),
+                Type::UserInline(ut) => Some(ut.clone()),
+                _ => None,
+            };
+            match inner {
+                Some(UserType::Struct(inner)) if field.attrs.has_attr("flatten") => {
+                    fields.extend(self.wire_fields(&inner))
+                }
+                _ => fields.push((field.wire_name().to_owned(), field.value.clone())),
+            }
+        }
+        fields
+    }
+
+    fn validate_type(&self, typ: &Type, value: &Value, path: &str, errors: &mut Vec<String>) {
+        match typ {
+            Type::Builtin(builtin) => {
+                if !builtin_matches(builtin, value) {
+                    errors.push(format!("{path}: expected {typ}, got {value}"));
+                }
+            }
+            Type::List(inner) => match value.as_array() {
+                Some(items) => {
+                    for (i, item) in items.iter().enumerate() {
+                        self.validate_type(inner, item, &format!("{path}[{i}]"), errors);
+                    }
+                }
+                None => errors.push(format!("{path}: expected {typ}, got {value}")),
+            },
+            Type::Map(key, inner) => match value.as_object() {
+                Some(entries) => {
+                    for (name, item) in entries {
+                        let path = format!("{path}.{name}");
+                        // Keys are always strings in JSON, integers included.
+                        let key_value = match key.as_ref() {
+                            Type::Builtin(Builtin::String) => Value::String(name.clone()),
+                            _ => name.parse().unwrap_or_else(|_| Value::String(name.clone())),
+                        };
+                        self.validate_type(key, &key_value, &path, errors);
+                        self.validate_type(inner, item, &path, errors);
+                    }
+                }
+                None => errors.push(format!("{path}: expected {typ}, got {value}")),
+            },
+            Type::Optional(inner) => {
+                if !value.is_null() {
+                    self.validate_type(inner, value, path, errors);
+                }
+            }
+            Type::User(name) => match self.types.get(name) {
+                Some(ut) => self.validate_user(ut, value, path, errors),
+                None => errors.push(format!(
+                    "{path}: type '{name}' is not defined in the schema"
+                )),
+            },
+            Type::UserInline(ut) => self.validate_user(ut, value, path, errors),
+            Type::Generic(name, args) => match self.types.get(name) {
+                Some(ut) => self.validate_user(&ut.instantiate(args), value, path, errors),
+                None => errors.push(format!(
+                    "{path}: type '{name}' is not defined in the schema"
+                )),
+            },
+        }
+    }
+}
+
+fn builtin_matches(builtin: &Builtin, value: &Value) -> bool {
+    fn in_range(value: &Value, min: i128, max: i128) -> bool {
+        let n = value
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| value.as_u64().map(i128::from));
+        n.is_some_and(|n| (min..=max).contains(&n))
+    }
+    match builtin {
+        Builtin::U8 => in_range(value, 0, u8::MAX.into()),
+        Builtin::U16 => in_range(value, 0, u16::MAX.into()),
+        Builtin::U32 | Builtin::UInt => in_range(value, 0, u32::MAX.into()),
+        Builtin::U64 | Builtin::USize => in_range(value, 0, u64::MAX.into()),
+        Builtin::I8 => in_range(value, i8::MIN.into(), i8::MAX.into()),
+        Builtin::I16 => in_range(value, i16::MIN.into(), i16::MAX.into()),
+        Builtin::I32 | Builtin::Int => in_range(value, i32::MIN.into(), i32::MAX.into()),
+        Builtin::I64 | Builtin::ISize => in_range(value, i64::MIN.into(), i64::MAX.into()),
+        Builtin::F32 | Builtin::F64 => value.is_number(),
+        Builtin::Bool => value.is_boolean(),
+        Builtin::String => value.is_string(),
+        // Rust peers send bytes as a list of numbers, while python peers send a string.
+        Builtin::Binary => {
+            value.is_string()
+                || value
+                    .as_array()
+                    .is_some_and(|items| items.iter().all(|item| in_range(item, 0, 255)))
+        }
+        // Descriptors travel beside the message, which only holds their position.
+        Builtin::Fd => in_range(value, 0, u32::MAX.into()),
+    }
+}
+
+/// Returns `true` if there is nothing else left to read.
+fn at_eof(reader: &mut impl BufRead) -> R<bool> {
+    Ok(reader.fill_buf().into_diagnostic()?.is_empty())
+}
+
+fn load_schema(path: &Path) -> R<Schema> {
+    let mut schema = loader::load(path).context("Failed to load schema")?;
+    if let Err(err) = wingc::semantic_analyzer::analyze_schema(&mut schema) {
+        bail!("{:?}", err);
+    }
+    Ok(Schema::new(&schema))
+}
+
+pub fn dump(args: DumpArgs) -> R<()> {
+    let schema = args.schema.as_deref().map(load_schema).transpose()?;
+    let input: Box<dyn Read> = match &args.input {
+        Some(path) => Box::new(
+            std::fs::File::open(path)
+                .into_diagnostic()
+                .context("Failed to open input file")?,
+        ),
+        None => Box::new(std::io::stdin()),
+    };
+    dump_frames(
+        BufReader::new(input),
+        schema.as_ref(),
+        &mut std::io::stdout().lock(),
+    )
+}
+
+/// Prints every frame read from `reader` to `out`, validating them against `schema` if given.
+fn dump_frames(mut reader: impl BufRead, schema: Option<&Schema>, out: &mut impl Write) -> R<()> {
+    let mut buf = Vec::new();
+    let mut index = 0;
+    while !at_eof(&mut reader)? {
+        let packet = WirePacket::read(&mut buf, &mut reader)
+            .into_diagnostic()
+            .with_context(|| format!("Failed to read frame #{index}"))?;
+        let data = packet.data();
+        let envelope = serde_json::from_slice::<Value>(data).ok();
+        let typ = envelope
+            .as_ref()
+            .and_then(|env| env.get("type"))
+            .and_then(Value::as_str);
+        writeln!(
+            out,
+            "#{index} flags=0x{:02x} len={} type={}",
+            packet.flags(),
+            data.len(),
+            typ.unwrap_or("<none>")
+        )
+        .into_diagnostic()?;
+        match &envelope {
+            Some(envelope) => {
+                let payload = envelope.get("data").unwrap_or(envelope);
+                let pretty = serde_json::to_string_pretty(payload).into_diagnostic()?;
+                writeln!(out, "{pretty}").into_diagnostic()?;
+            }
+            None => writeln!(out, "{}", String::from_utf8_lossy(data)).into_diagnostic()?,
+        }
+        if let Some(schema) = schema {
+            let errors = match (typ, envelope.as_ref().and_then(|env| env.get("data"))) {
+                (Some(typ), Some(payload)) => schema.validate(typ, payload),
+                _ => vec!["payload is not a wing message".to_owned()],
+            };
+            if errors.is_empty() {
+                writeln!(out, "valid").into_diagnostic()?;
+            } else {
+                for error in errors {
+                    writeln!(out, "invalid: {error}").into_diagnostic()?;
+                }
+            }
+        }
+        writeln!(out).into_diagnostic()?;
+        index += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A frame as it would be captured off the wire.
+    fn frame(flags: u8, data: &str) -> Vec<u8> {
+        let mut frame = vec![flags];
+        frame.extend((data.len() as u16).to_le_bytes());
+        frame.extend(data.as_bytes());
+        frame
+    }
+
+    fn dump_capture(capture: &[u8], schema: Option<&Schema>) -> R<String> {
+        let mut out = Vec::new();
+        dump_frames(capture, schema, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    fn protocol() -> Schema {
+        load_schema(Path::new("../wingc/test-files/protocol.wing")).unwrap()
+    }
+
+    #[test]
+    fn decodes_frames() {
+        let capture = [
+            frame(0, r#"{"type":"ServerMessage","data":{"Welcome":{"id":7}}}"#),
+            frame(0b0000_1100, "not json"),
+        ]
+        .concat();
+        let out = dump_capture(&capture, None).unwrap();
+        let frames: Vec<_> = out.split("\n\n").collect();
+        assert_eq!(
+            frames[0],
+            "#0 flags=0x00 len=52 type=ServerMessage\n{\n  \"Welcome\": {\n    \"id\": 7\n  }\n}"
+        );
+        assert_eq!(frames[1], "#1 flags=0x0c len=8 type=<none>\nnot json");
+    }
+
+    #[test]
+    fn validates_against_schema() {
+        let capture = [
+            frame(
+                0,
+                r#"{"type":"ClientMessage","data":{"Join":{"room":"general"}}}"#,
+            ),
+            frame(0, r#"{"type":"ClientMessage","data":{"Say":{"txt":"hi"}}}"#),
+            frame(
+                0,
+                r#"{"type":"ServerMessage","data":{"Welcome":{"id":-1}}}"#,
+            ),
+            frame(0, r#"{"type":"Kick","data":{}}"#),
+        ]
+        .concat();
+        let out = dump_capture(&capture, Some(&protocol())).unwrap();
+        let verdicts: Vec<_> = (out.lines())
+            .filter(|line| line.starts_with("valid") || line.starts_with("invalid"))
+            .collect();
+        assert_eq!(
+            verdicts,
+            [
+                "valid",
+                "invalid: data.Say.text: missing field",
+                "invalid: data.Say.txt: unknown field",
+                "invalid: data.Welcome.id: expected u32, got -1",
+                "invalid: type 'Kick' is not defined in the schema",
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_capture() {
+        let mut capture = frame(0, r#"{"type":"Leave","data":{}}"#);
+        capture.truncate(capture.len() - 1);
+        let err = dump_capture(&capture, None).unwrap_err();
+        assert_eq!(err.to_string(), "Failed to read frame #0");
+    }
+}
@@ -1,38 +1,54 @@
-use std::{io::BufWriter, path::PathBuf};
+use std::{
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
 
-use wingc::{emitter, parser, semantic_analyzer};
+use wingc::{emitter, loader, semantic_analyzer};
 
 use argh::FromArgs;
-use emitter::{Emitter, PyEmitter, RustEmitter};
-use parser::parse_document;
-use semantic_analyzer::analyze_errors;
+use emitter::{Emitter, MapType, PyEmitter, RustEmitter};
+use loader::load;
+use semantic_analyzer::analyze_schema;
 
 use miette::{Context, Result as R};
 use miette::{IntoDiagnostic, bail};
 
+mod dump;
+
 #[derive(FromArgs)]
 #[argh(
     description = "Given a wing file, generates a the corresponding definitions in the target programming language"
 )]
 pub struct Args {
+    #[argh(subcommand)]
+    command: Option<Command>,
     #[argh(positional)]
-    input: PathBuf,
+    #[argh(description = "the wing file to compile, optionally followed by the output file.")]
+    files: Vec<PathBuf>,
     #[argh(option, short = 'l')]
     #[argh(
         description = "specifies the language to be emitted. If not specified, will try to look into the file extension."
     )]
     language: Option<String>,
-    #[argh(positional)]
-    output: Option<PathBuf>,
+    #[argh(option)]
+    #[argh(description = "the map type emitted for Rust: 'hash' (default) or 'btree'.")]
+    map: Option<String>,
+    #[argh(switch)]
+    #[argh(description = "send types as 'package.Name' instead of just 'Name'.")]
+    qualified_names: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Dump(dump::DumpArgs),
 }
 
 impl Args {
-    fn get_emitter(&self) -> R<Box<dyn Emitter>> {
+    fn get_emitter(&self, output: Option<&Path>) -> R<Box<dyn Emitter>> {
         let language = match self.language {
             Some(ref o) => o,
-            None => self
-                .output
-                .as_ref()
+            None => output
                 .context("Expected output file to be specified when no language is set")?
                 .extension()
                 .context("Expected output file to have an extension")?
@@ -40,8 +56,21 @@ impl Args {
                 .context("Can't parse extension")?,
         };
         Ok(match language {
-            "py" | "python" => Box::new(PyEmitter::new()),
-            "rs" | "rust" => Box::new(RustEmitter::new()),
+            "py" | "python" => {
+                Box::new(PyEmitter::new().with_qualified_names(self.qualified_names))
+            }
+            "rs" | "rust" => {
+                let map_type = match self.map.as_deref() {
+                    None | Some("hash") => MapType::Hash,
+                    Some("btree") => MapType::BTree,
+                    Some(map) => bail!("Unknown map type '{map}'."),
+                };
+                Box::new(
+                    RustEmitter::new()
+                        .with_map_type(map_type)
+                        .with_qualified_names(self.qualified_names),
+                )
+            }
             _ => {
                 bail!("No emitter available for '{language}'.");
             }
@@ -51,21 +80,28 @@ impl Args {
 
 fn main() -> R<()> {
     let args: Args = argh::from_env();
-    let input = &args.input;
+    if let Some(Command::Dump(args)) = args.command {
+        return dump::dump(args);
+    }
+    let (input, output) = match args.files.as_slice() {
+        [input] => (input, None),
+        [input, output] => (input, Some(output.as_path())),
+        _ => bail!("Expected an input file, optionally followed by an output file"),
+    };
     if input.extension().map(|ext| ext != "wing").unwrap_or(true) {
         bail!("Input file is not a .wing file");
     }
-    let input_data = std::fs::read_to_string(input).expect("Failed to read input file");
-    let document = parse_document(&*input_data).expect("Failed to parse document");
+    let mut schema = load(input)?;
     if args.language.as_deref() == Some("tree") {
-        println!("{:#?}", document);
+        println!("{:#?}", schema.merged());
         return Ok(());
     }
-    if let Err(err) = analyze_errors(&document) {
-        bail!("{:?}", err.with_source_code(input_data));
+    if let Err(err) = analyze_schema(&mut schema) {
+        bail!("{:?}", err);
     }
-    let mut emitter: Box<dyn Emitter> = args.get_emitter()?;
-    let mut output: &mut dyn std::io::Write = if let Some(output) = args.output {
+    let document = schema.merged();
+    let mut emitter: Box<dyn Emitter> = args.get_emitter(output)?;
+    let mut output: &mut dyn std::io::Write = if let Some(output) = output {
         &mut std::fs::File::create(output)
             .map(BufWriter::new)
             .into_diagnostic()
@@ -73,6 +109,9 @@ fn main() -> R<()> {
     } else {
         &mut std::io::stdout()
     };
-    emitter.emit(&document, &mut output).unwrap();
+    emitter
+        .emit(&document, &mut output)
+        .into_diagnostic()
+        .context("Failed to emit code")?;
     Ok(())
 }
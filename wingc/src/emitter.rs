@@ -1,13 +1,74 @@
 use std::io::Write;
 
-use crate::parser::Document;
+use crate::parser::{Attribute, Attributes, Document, S, Type};
 
 mod python;
 mod rust;
 
 pub use python::PyEmitter;
-pub use rust::RustEmitter;
+pub use rust::{MapType, RustEmitter};
 
 pub trait Emitter {
     fn emit(&mut self, document: &Document, writer: &mut dyn Write) -> std::io::Result<()>;
 }
+
+/// `docs` followed by the version given by `@since`, which is only kept as documentation.
+fn documented(docs: &[String], attrs: &[S<Attribute>]) -> Vec<String> {
+    let mut docs = docs.to_vec();
+    if let Some(version) = attrs.since() {
+        if !docs.is_empty() {
+            docs.push(String::new());
+        }
+        docs.push(format!("Added in version {version}."));
+    }
+    docs
+}
+
+/// The name `typ` is sent as, written like in schemas, such as `Page<User>`.
+///
+/// User types are sent as the name given by `wire_name`.
+fn wire_type_name(typ: &Type, wire_name: &dyn Fn(&str) -> String) -> String {
+    match typ {
+        Type::Builtin(builtin) => <&str>::from(builtin).to_owned(),
+        Type::List(inner) => format!("[{}]", wire_type_name(inner, wire_name)),
+        Type::Optional(inner) => format!("{}?", wire_type_name(inner, wire_name)),
+        Type::Map(key, value) => format!(
+            "{{{}: {}}}",
+            wire_type_name(key, wire_name),
+            wire_type_name(value, wire_name)
+        ),
+        Type::User(name) => wire_name(name),
+        Type::UserInline(ut) => wire_name(ut.name()),
+        Type::Generic(name, args) => {
+            let args: Vec<_> = (args.iter())
+                .map(|arg| wire_type_name(arg, wire_name))
+                .collect();
+            format!("{}<{}>", wire_name(name), args.join(", "))
+        }
+    }
+}
+
+/// The instantiations of generic types used by `document`, each listed once.
+///
+/// Those using type parameters are left out, as they only exist once the parameters are known.
+fn instantiations(document: &Document) -> Vec<Type> {
+    let field_types = (document.user_types.iter())
+        .flat_map(|ut| ut.children_user_types())
+        .flat_map(|ut| ut.children_types().map(|tp| (tp.value, ut.params())));
+    let method_types = (document.services.iter())
+        .flat_map(|sv| sv.methods.iter())
+        .flat_map(|method| [method.input.value.clone(), method.output.value.clone()])
+        .map(|typ| (typ, &[] as &[String]));
+    let mut found = vec![];
+    for (typ, params) in field_types.chain(method_types) {
+        for typ in typ.nested_types() {
+            let concrete = (typ.nested_types().into_iter())
+                .filter_map(Type::as_user)
+                .all(|name| !params.iter().any(|param| param == name));
+            if matches!(typ, Type::Generic(..)) && concrete && !found.contains(typ) {
+                found.push(typ.clone());
+            }
+        }
+    }
+    found
+}
@@ -1,42 +1,268 @@
-use std::{collections::HashSet, io::Write};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+};
 
-use crate::parser::{Builtin, StructField, Type, UserType};
+use crate::parser::{
+    Attribute, Attributes, Builtin, Document, Literal, Newtype, Package, S, Service, Struct,
+    StructField, Type, UserType,
+};
 
-use super::Emitter;
+use super::{Emitter, documented, instantiations, wire_type_name};
 
+/// Emits a document as a Python module.
+///
+/// The code of a document with a `package foo.bar;` has to end up in the `foo.bar` module, as
+/// types of other packages are only referred to, by importing the module of their own package.
 #[derive(Debug, PartialEq, Clone)]
 pub struct PyEmitter {
     seen: HashSet<String>,
+    /// The type parameters of the generic type being emitted.
+    params: Vec<String>,
     indent: usize,
+    qualified_names: bool,
+    package: Option<Package>,
+    /// The package of each type that is used, but not emitted.
+    foreign: HashMap<String, Package>,
+    user_types: HashMap<String, UserType>,
 }
 
 type R = std::io::Result<()>;
 
+/// Unit variants are sent as their tag, which is also what their Python value is made from.
+fn unit_tag(ut: &UserType, name: &str) -> String {
+    let UserType::Enum(en) = ut else {
+        unreachable!("Only enums have unit variants")
+    };
+    quote(en.units().find(|unit| unit.name == name).unwrap().tag())
+}
+
+/// Tags have to be validated into enum members, or pydantic warns when sending them.
+fn mentions_variant(value: &Literal) -> bool {
+    match value {
+        Literal::Variant(_) => true,
+        Literal::List(items) => items.iter().any(mentions_variant),
+        Literal::Map(entries) => {
+            (entries.iter()).any(|(k, v)| mentions_variant(k) || mentions_variant(v))
+        }
+        _ => false,
+    }
+}
+
+/// Fields declared with `Field(...)`, as pydantic needs more than their default value.
+fn needs_field(field: &StructField) -> bool {
+    field
+        .default
+        .as_ref()
+        .is_some_and(|default| mentions_variant(default))
+        || field.attrs.rename().is_some()
+        || field.attrs.deprecated().is_some()
+        || field.attrs.has_attr("skip")
+}
+
+/// Newtypes and enum members can't be marked deprecated in Python, so they only say so in
+/// their docs.
+fn notes(docs: &[String], attrs: &[S<Attribute>]) -> Vec<String> {
+    let mut docs = documented(docs, attrs);
+    if let Some(reason) = attrs.deprecated() {
+        if !docs.is_empty() {
+            docs.push(String::new());
+        }
+        match reason {
+            Some(reason) => docs.extend(format!("Deprecated: {reason}").lines().map(Into::into)),
+            None => docs.push("Deprecated.".into()),
+        }
+    }
+    docs
+}
+
+fn quote(text: &str) -> String {
+    let text = (text.replace('\\', "\\\\").replace('\'', "\\'")).replace('\n', "\\n");
+    format!("'{text}'")
+}
+
+/// Returns `true` for the builtins that share a single Python type with other builtins.
+fn is_number(builtin: &Builtin) -> bool {
+    !matches!(
+        builtin,
+        Builtin::Bool | Builtin::String | Builtin::Binary | Builtin::Fd
+    )
+}
+
+/// Joins `types` into a union. Strings can't be or'ed with types, so the whole union becomes a
+/// forward reference as soon as one of its members is.
+fn union(types: &[String]) -> String {
+    let union = types.join(" | ");
+    match union.contains('\'') {
+        true => format!("'{}'", union.replace('\'', "")),
+        false => union,
+    }
+}
+
+impl Default for PyEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PyEmitter {
     pub fn new() -> Self {
         Self {
             indent: 0,
             seen: Default::default(),
+            params: vec![],
+            qualified_names: false,
+            package: None,
+            foreign: Default::default(),
+            user_types: Default::default(),
+        }
+    }
+    /// Sends types as `package.Name`, so types of different packages can share a name.
+    pub fn with_qualified_names(self, qualified_names: bool) -> Self {
+        Self {
+            qualified_names,
+            ..self
         }
     }
-    fn emit_header(&self, f: &mut dyn Write) -> R {
-        write!(f, "from wing_rpc import Schema, Enum\n")?;
-        write!(f, "from typing import ClassVar\n")?;
-        write!(f, "from enum import StrEnum\n")?;
+    /// The name a type is sent as.
+    fn wire_name(&self, name: &str) -> String {
+        let wire_name = (self.user_types.get(name)).map_or(name, UserType::wire_name);
+        match self.foreign.get(name).or(self.package.as_ref()) {
+            Some(package) if self.qualified_names => format!("{package}.{wire_name}"),
+            _ => wire_name.to_owned(),
+        }
+    }
+    /// The name of a user type, which lives in the module of its package if it isn't ours.
+    fn user_type_path(&self, name: &str) -> String {
+        match self.foreign.get(name) {
+            Some(package) => format!("{package}.{name}"),
+            None => name.to_owned(),
+        }
+    }
+    fn emit_header(&self, f: &mut dyn Write, document: &Document) -> R {
+        let fields = || {
+            (document.user_types.iter())
+                .flat_map(|ut| ut.children_user_types())
+                .filter_map(|ut| match ut {
+                    UserType::Struct(st) => Some(st),
+                    UserType::Enum(_) | UserType::Newtype(_) => None,
+                })
+                .flat_map(|st| st.fields.iter())
+        };
+        match fields().any(|field| field.attrs.has_attr("flatten")) {
+            true => writeln!(f, "from wing_rpc import Schema, Enum, FlatSchema")?,
+            false => writeln!(f, "from wing_rpc import Schema, Enum")?,
+        }
+        if !document.services.is_empty() {
+            writeln!(f, "from wing_rpc.peer import Peer, CallStream, ReplyStream")?;
+        }
+        let mut pydantic = vec![];
+        if fields().any(|field| field.attrs.rename().is_some()) {
+            pydantic.push("ConfigDict");
+        }
+        if fields().any(|field| needs_field(field)) {
+            pydantic.push("Field");
+        }
+        if !pydantic.is_empty() {
+            writeln!(f, "from pydantic import {}", pydantic.join(", "))?;
+        }
+        let mut packages: Vec<_> = self.foreign.values().map(ToString::to_string).collect();
+        packages.sort();
+        packages.dedup();
+        for package in packages {
+            writeln!(f, "import {package}")?;
+        }
+        let user_types = || (document.user_types.iter()).flat_map(|ut| ut.children_user_types());
+        let mut typing = vec!["ClassVar"];
+        if user_types().any(|ut| matches!(ut, UserType::Newtype(_))) {
+            typing.push("NewType");
+        }
+        let mut params: Vec<_> = user_types().flat_map(|ut| ut.params()).collect();
+        params.sort();
+        params.dedup();
+        if !params.is_empty() {
+            typing.extend(["Generic", "TypeVar"]);
+        }
+        let method_types = (document.services.iter())
+            .flat_map(|sv| sv.methods.iter())
+            .flat_map(|method| [&method.input.value, &method.output.value]);
+        let numbers_as_arguments = (user_types().flat_map(|ut| ut.children_types()))
+            .map(|typ| typ.value)
+            .chain(method_types.cloned())
+            .any(|typ| {
+                (typ.nested_types().into_iter()).any(|typ| match typ {
+                    Type::Generic(_, args) => (args.iter().flat_map(Type::nested_types))
+                        .any(|arg| matches!(arg, Type::Builtin(tp) if is_number(tp))),
+                    _ => false,
+                })
+            });
+        if numbers_as_arguments {
+            typing.push("Annotated");
+        }
+        writeln!(f, "from typing import {}", typing.join(", "))?;
+        let classes = || user_types().filter(|ut| !matches!(ut, UserType::Newtype(_)));
+        if classes().any(|ut| ut.attrs().deprecated().is_some()) {
+            writeln!(f, "from typing_extensions import deprecated")?;
+        }
+        writeln!(f, "from enum import StrEnum")?;
+        if !params.is_empty() {
+            writeln!(f)?;
+            for param in params {
+                writeln!(f, "{param} = TypeVar({})", quote(param))?;
+            }
+        }
         write!(f, "\n\n")
     }
+    /// Returns `true` if `name` can be used without quotes, as it was already defined.
+    fn is_defined(&self, name: &str) -> bool {
+        self.seen.contains(name)
+            || self.foreign.contains_key(name)
+            || self.params.contains(&name.to_owned())
+    }
     fn get_type_name(&self, typ: &Type) -> String {
+        self.type_name(typ, false)
+    }
+    /// The name of `typ`, which is used as a type argument of a generic type if `argument` is set.
+    ///
+    /// Instantiations are told apart by their type arguments, see `emit_instantiation`, so numbers
+    /// are annotated with their wing type there. Otherwise `Page<u32>` and `Page<u64>` would both
+    /// be `Page[int]`.
+    fn type_name(&self, typ: &Type, argument: bool) -> String {
         match typ {
             Type::User(name) => {
-                if self.seen.contains(name) {
-                    name.clone()
+                if self.is_defined(name) {
+                    self.user_type_path(name)
                 } else {
                     format!(r"'{name}'")
                 }
             }
             Type::UserInline(user) => user.name().to_owned(),
+            Type::Generic(name, args) => {
+                let args: Vec<_> = args.iter().map(|arg| self.type_name(arg, true)).collect();
+                let typ = format!("{}[{}]", self.user_type_path(name), args.join(", "));
+                match self.is_defined(name) {
+                    true => typ,
+                    // The whole type is a forward reference, so its arguments don't need quotes.
+                    false => format!("'{}'", typ.replace('\'', "")),
+                }
+            }
             Type::List(inner) => {
-                format!("list[{}]", self.get_type_name(inner))
+                format!("list[{}]", self.type_name(inner, argument))
+            }
+            Type::Optional(inner) => union(&[self.type_name(inner, argument), "None".into()]),
+            Type::Map(key, value) => {
+                let (key, value) = (
+                    self.type_name(key, argument),
+                    self.type_name(value, argument),
+                );
+                format!("dict[{key}, {value}]")
+            }
+            Type::Builtin(tp) if argument && is_number(tp) => {
+                format!(
+                    "Annotated[{}, \"{}\"]",
+                    self.type_name(typ, false),
+                    <&str>::from(tp)
+                )
             }
             Type::Builtin(tp) => match tp {
                 Builtin::U8
@@ -55,10 +281,64 @@ impl PyEmitter {
                 Builtin::Bool => "bool",
                 Builtin::String => "str",
                 Builtin::Binary => "bytes",
+                Builtin::Fd => unreachable!("Documents holding an fd are rejected by emit"),
             }
             .to_string(),
         }
     }
+    fn as_newtype<'a>(&'a self, typ: &'a Type) -> Option<&'a Newtype> {
+        let ut = match typ {
+            Type::User(name) => &self.user_types[name],
+            Type::UserInline(ut) => ut,
+            _ => return None,
+        };
+        match ut {
+            UserType::Newtype(nt) => Some(nt),
+            _ => None,
+        }
+    }
+    /// Writes `value`, which was checked to be a valid `typ`, as a Python expression.
+    fn get_literal(&self, value: &Literal, typ: &Type) -> String {
+        // Newtypes are the type they wrap at runtime.
+        if let Some(nt) = self.as_newtype(typ) {
+            return self.get_literal(value, &nt.typ);
+        }
+        let join = |items: Vec<String>| items.join(", ");
+        match (value, typ) {
+            (_, Type::Optional(inner)) => self.get_literal(value, inner),
+            (Literal::Int(n), Type::Builtin(Builtin::F32 | Builtin::F64)) => format!("{n}.0"),
+            (Literal::Int(n), _) => n.to_string(),
+            (Literal::Float(n), _) => format!("{n:?}"),
+            (Literal::Bool(true), _) => "True".into(),
+            (Literal::Bool(false), _) => "False".into(),
+            (Literal::String(text), _) => quote(text),
+            (Literal::List(items), Type::Builtin(Builtin::Binary)) => {
+                format!(
+                    "bytes([{}])",
+                    join(items.iter().map(ToString::to_string).collect())
+                )
+            }
+            (Literal::List(items), Type::List(inner)) => {
+                let items = items.iter().map(|item| self.get_literal(item, inner));
+                format!("[{}]", join(items.collect()))
+            }
+            (Literal::Map(entries), Type::Map(key, inner)) => {
+                let entries = entries.iter().map(|(k, v)| {
+                    format!(
+                        "{}: {}",
+                        self.get_literal(k, key),
+                        self.get_literal(v, inner)
+                    )
+                });
+                format!("{{{}}}", join(entries.collect()))
+            }
+            (Literal::Variant(name), Type::User(ut) | Type::Generic(ut, _)) => {
+                unit_tag(&self.user_types[ut], name)
+            }
+            (Literal::Variant(name), Type::UserInline(ut)) => unit_tag(ut, name),
+            _ => unreachable!("'{value}' is not a valid '{typ}'"),
+        }
+    }
     fn ident(&self, f: &mut dyn Write) -> R {
         write!(f, "{}", " ".repeat(self.indent * 4))
     }
@@ -77,33 +357,66 @@ impl PyEmitter {
         if let Some(value) = value.into() {
             write!(f, " = {}", value)?;
         }
-        write!(f, "\n")?;
+        writeln!(f)?;
         Ok(())
     }
-    fn emit_python_strenum<'a>(
+    /// Emits `docs` as a docstring, which goes right after the class or field it documents.
+    fn emit_docstring(&self, f: &mut dyn Write, docs: &[String]) -> R {
+        let Some((first, rest)) = docs.split_first() else {
+            return Ok(());
+        };
+        let escape = |line: &str| line.replace('\\', "\\\\").replace('"', "\\\"");
+        self.ident(f)?;
+        write!(f, "\"\"\"{}", escape(first))?;
+        if !rest.is_empty() {
+            writeln!(f)?;
+            for line in rest {
+                match line.is_empty() {
+                    true => writeln!(f)?,
+                    false => {
+                        self.ident(f)?;
+                        writeln!(f, "{}", escape(line))?;
+                    }
+                }
+            }
+            self.ident(f)?;
+        }
+        writeln!(f, "\"\"\"")
+    }
+    fn emit_python_strenum(
         &mut self,
         f: &mut dyn Write,
         name: &str,
-        variants: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<str>)>,
+        variants: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<str>, Vec<String>)>,
     ) -> R {
         self.ident(f)?;
-        write!(f, "class {}(StrEnum):\n", name)?;
+        writeln!(f, "class {}(StrEnum):", name)?;
         self.indent += 1;
-        for (name, value) in variants {
-            self.emit_field(
-                f,
-                name.as_ref(),
-                None,
-                format!("'{}'", value.as_ref()).as_str(),
-            )?;
-        }
+        self.emit_strenum_members(f, variants)?;
         self.indent -= 1;
         Ok(())
     }
+    fn emit_strenum_members(
+        &mut self,
+        f: &mut dyn Write,
+        variants: impl IntoIterator<Item = (impl AsRef<str>, impl AsRef<str>, Vec<String>)>,
+    ) -> R {
+        for (name, value, docs) in variants {
+            self.emit_field(f, name.as_ref(), None, quote(value.as_ref()).as_str())?;
+            self.emit_docstring(f, &docs)?;
+        }
+        Ok(())
+    }
     fn get_base_class(&self, utype: &UserType) -> &str {
         match utype {
+            // Flattened fields are sent as part of their parent, see `wing_rpc.flatten`.
+            UserType::Struct(st) if st.fields.iter().any(|fd| fd.attrs.has_attr("flatten")) => {
+                "FlatSchema"
+            }
             UserType::Struct(_) => "Schema",
+            UserType::Enum(en) if en.has_only_units() => "StrEnum",
             UserType::Enum(_) => "Enum",
+            UserType::Newtype(_) => unreachable!("Newtypes are emitted by emit_newtype"),
         }
     }
     fn emit_match_args<'a>(&self, fields: impl Iterator<Item = &'a str>, f: &mut dyn Write) -> R {
@@ -117,57 +430,346 @@ impl PyEmitter {
         self.emit_field(f, "__match_args__", "ClassVar[tuple]", match_args.as_ref())?;
         Ok(())
     }
-    fn emit_user_type(&mut self, f: &mut dyn Write, utype: &UserType) -> R {
-        // Emit inner children types
+    /// The value a field is declared with, which is its default value unless pydantic needs to
+    /// know more about it.
+    fn get_field_value(&self, field: &StructField) -> Option<String> {
+        let default = match &field.default {
+            Some(default) => Some(self.get_literal(default, &field.typ)),
+            // Optional fields can be left out when creating the message.
+            None => matches!(field.typ, Type::Optional(_)).then(|| "None".into()),
+        };
+        if !needs_field(field) {
+            return default;
+        }
+        let mut args: Vec<_> = default.into_iter().collect();
+        if field
+            .default
+            .as_ref()
+            .is_some_and(|default| mentions_variant(default))
+        {
+            args.push("validate_default=True".into());
+        }
+        if let Some(name) = field.attrs.rename() {
+            args.push(format!("alias={}", quote(name)));
+        }
+        match field.attrs.deprecated() {
+            Some(Some(reason)) => args.push(format!("deprecated={}", quote(reason))),
+            Some(None) => args.push("deprecated=True".into()),
+            None => {}
+        }
+        if field.attrs.has_attr("skip") {
+            args.push("exclude=True".into());
+        }
+        Some(format!("Field({})", args.join(", ")))
+    }
+    /// Lists the keys each flattened field of `st` takes from the message.
+    fn emit_flatten(&self, f: &mut dyn Write, st: &Struct) -> R {
+        let lookup = |name: &str| self.user_types.get(name);
+        let mut flattened = vec![];
+        for field in st.fields.iter() {
+            let inner = match &field.typ {
+                Type::UserInline(ut) => Some(ut),
+                typ => typ.as_user().and_then(lookup),
+            };
+            if let (true, Some(UserType::Struct(inner))) = (field.attrs.has_attr("flatten"), inner)
+            {
+                let keys: Vec<_> = (inner.wire_keys(&lookup).iter())
+                    .map(|key| quote(key))
+                    .collect();
+                flattened.push(format!("{}: ({},)", quote(&field.name), keys.join(", ")));
+            }
+        }
+        if flattened.is_empty() {
+            return Ok(());
+        }
+        let flattened = format!("{{{}}}", flattened.join(", "));
+        self.emit_field(f, "__wing_flatten__", "ClassVar[dict]", flattened.as_str())
+    }
+    /// Newtypes only exist for type checkers, pydantic handles them as the type they wrap.
+    fn emit_newtype(&mut self, f: &mut dyn Write, nt: &Newtype) -> R {
+        let name = &nt.name;
         self.ident(f)?;
-        write!(
+        writeln!(
             f,
-            "class {}({}):\n",
-            utype.name(),
-            self.get_base_class(utype)
+            "{name} = NewType({}, {})",
+            quote(name),
+            self.get_type_name(&nt.typ)
         )?;
+        self.emit_docstring(f, &notes(&nt.docs, &nt.attrs))?;
+        write!(f, "\n\n")?;
+        self.seen.insert(name.to_owned());
+        Ok(())
+    }
+    /// Like `#[deprecated]` in Rust, only type checkers warn about deprecated classes, as
+    /// pydantic creates them when validating messages too.
+    fn emit_deprecated(&self, f: &mut dyn Write, attrs: &[S<Attribute>]) -> R {
+        let Some(reason) = attrs.deprecated() else {
+            return Ok(());
+        };
+        self.ident(f)?;
+        let reason = quote(reason.unwrap_or("Deprecated."));
+        writeln!(f, "@deprecated({reason}, category=None)")
+    }
+    fn emit_user_type(&mut self, f: &mut dyn Write, utype: &UserType) -> R {
+        if let UserType::Newtype(nt) = utype {
+            return self.emit_newtype(f, nt);
+        }
+        // Emit inner children types
+        let mut bases = self.get_base_class(utype).to_owned();
+        if !utype.params().is_empty() {
+            bases = format!("{bases}, Generic[{}]", utype.params().join(", "));
+        }
+        self.emit_deprecated(f, utype.attrs())?;
+        self.ident(f)?;
+        writeln!(f, "class {}({bases}):", utype.name())?;
+        self.params = utype.params().to_vec();
         self.indent += 1;
+        self.emit_docstring(f, &documented(utype.docs(), utype.attrs()))?;
+        if !self.params.is_empty() {
+            // Filled with the wire names of its instantiations, see `emit_instantiation`.
+            self.emit_field(f, "__wing_instances__", "ClassVar[dict]", "{}")?;
+        }
+        let wire_name = self.wire_name(utype.name());
+        if wire_name != utype.name() && self.get_base_class(utype) != "StrEnum" {
+            self.emit_field(
+                f,
+                "__wing_name__",
+                "ClassVar[str]",
+                quote(&wire_name).as_str(),
+            )?;
+        }
         if utype.is_empty() {
             self.ident(f)?;
-            write!(f, "pass\n")?;
+            writeln!(f, "pass")?;
         } else {
             match utype {
                 UserType::Struct(st) => {
+                    if st.fields.iter().any(|field| field.attrs.rename().is_some()) {
+                        // Renamed fields are still created with their own name.
+                        self.emit_field(
+                            f,
+                            "model_config",
+                            None,
+                            "ConfigDict(validate_by_name=True, serialize_by_alias=True)",
+                        )?;
+                    }
+                    self.emit_flatten(f, st)?;
                     self.emit_match_args(st.fields.iter().map(|f| f.name.as_str()), f)?;
                     for field in &st.fields {
-                        let StructField { name, typ: type_ } = &field.value;
-                        self.emit_field(f, name.as_str(), self.get_type_name(type_).as_str(), None)?
+                        self.emit_field(
+                            f,
+                            field.name.as_str(),
+                            self.get_type_name(&field.typ).as_str(),
+                            self.get_field_value(field).as_deref(),
+                        )?;
+                        self.emit_docstring(f, &documented(&field.docs, &field.attrs))?;
                     }
                 }
+                UserType::Enum(en) if en.has_only_units() => {
+                    self.emit_strenum_members(
+                        f,
+                        en.definitions
+                            .iter()
+                            .map(|var| (var.name(), var.tag(), notes(var.docs(), var.attrs()))),
+                    )?;
+                }
                 UserType::Enum(en) => {
                     self.emit_match_args(["tag", "value"].into_iter(), f)?;
-                    let varnames: Vec<String> =
+                    let mut varnames: Vec<String> =
                         en.variants().map(|t| self.get_type_name(&t.typ)).collect();
+                    let units: Vec<_> = en.units().collect();
+                    if !units.is_empty() {
+                        // Unit variants are sent as their bare tag, without a value.
+                        let units: Vec<_> = units.iter().map(|unit| quote(unit.tag())).collect();
+                        let units = format!("({},)", units.join(", "));
+                        self.emit_field(f, "__units__", "ClassVar[tuple]", units.as_str())?;
+                        varnames.push("None".into());
+                    }
                     self.emit_python_strenum(
                         f,
                         "Tag",
-                        en.variants().map(|t| t.value.name).map(|t| (t.clone(), t)),
+                        en.definitions
+                            .iter()
+                            .map(|var| (var.name(), var.tag(), notes(var.docs(), var.attrs()))),
                     )?;
                     self.emit_field(f, "tag", "Tag", None)?;
-                    self.emit_field(f, "value", varnames.join(" | ").as_str(), None)?;
+                    let default = (!units.is_empty()).then_some("None");
+                    self.emit_field(f, "value", union(&varnames).as_str(), default)?;
                 }
+                UserType::Newtype(_) => unreachable!("Newtypes are emitted by emit_newtype"),
             }
         }
         self.indent -= 1;
         write!(f, "\n\n")?;
+        self.params.clear();
         self.seen.insert(utype.name().to_owned());
         Ok(())
     }
+    /// Instantiations of generic types are sent with the names of their type arguments, which
+    /// pydantic doesn't keep, so the generic type maps its arguments to the name to send.
+    fn emit_instantiation(&mut self, f: &mut dyn Write, typ: &Type) -> R {
+        let Type::Generic(name, args) = typ else {
+            unreachable!("Only generic types are instantiated")
+        };
+        let args: Vec<_> = args.iter().map(|arg| self.type_name(arg, true)).collect();
+        let wire_name = wire_type_name(typ, &|name| self.wire_name(name));
+        writeln!(
+            f,
+            "{}.__wing_instances__[({},)] = {}",
+            self.user_type_path(name),
+            args.join(", "),
+            quote(&wire_name)
+        )
+    }
+    fn emit_service(&mut self, f: &mut dyn Write, service: &Service) -> R {
+        let name = &service.name;
+        writeln!(f, "class {name}Client:")?;
+        self.indent += 1;
+        self.ident(f)?;
+        writeln!(f, "def __init__(self, peer: Peer):")?;
+        self.indent += 1;
+        self.ident(f)?;
+        writeln!(f, "self.peer = peer")?;
+        self.indent -= 1;
+        for method in service.methods.iter() {
+            let input = self.get_type_name(&method.input);
+            let output = self.get_type_name(&method.output);
+            writeln!(f)?;
+            self.ident(f)?;
+            if method.stream {
+                writeln!(
+                    f,
+                    "def {}(self, request: {input}) -> CallStream[{output}]:",
+                    method.name
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "def {}(self, request: {input}) -> {output}:",
+                    method.name
+                )?;
+            }
+            self.indent += 1;
+            self.ident(f)?;
+            match method.stream {
+                true => writeln!(f, "return self.peer.call_stream(request, {output})")?,
+                false => writeln!(f, "return self.peer.call(request, {output})")?,
+            }
+            self.indent -= 1;
+        }
+        self.indent -= 1;
+        write!(f, "\n\n")?;
+
+        writeln!(f, "class {name}:")?;
+        self.indent += 1;
+        for method in service.methods.iter() {
+            let input = self.get_type_name(&method.input);
+            let output = self.get_type_name(&method.output);
+            self.ident(f)?;
+            if method.stream {
+                writeln!(
+                    f,
+                    "def {}(self, request: {input}, reply: ReplyStream[{output}]):",
+                    method.name
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "def {}(self, request: {input}) -> {output}:",
+                    method.name
+                )?;
+            }
+            self.indent += 1;
+            self.ident(f)?;
+            write!(f, "raise NotImplementedError\n\n")?;
+            self.indent -= 1;
+        }
+        self.ident(f)?;
+        writeln!(f, "def serve(self, peer: Peer):")?;
+        self.indent += 1;
+        self.ident(f)?;
+        writeln!(f, "call = peer.next_call()")?;
+        self.ident(f)?;
+        writeln!(f, "match call.name:")?;
+        self.indent += 1;
+        for method in service.methods.iter() {
+            let input = self.get_type_name(&method.input);
+            let wire_name = wire_type_name(&method.input, &|name| self.wire_name(name));
+            self.ident(f)?;
+            writeln!(f, "case {}:", quote(&wire_name))?;
+            self.indent += 1;
+            self.ident(f)?;
+            match method.stream {
+                true => writeln!(f, "peer.answer_stream(call, {input}, self.{})", method.name)?,
+                false => writeln!(f, "peer.answer(call, {input}, self.{})", method.name)?,
+            }
+            self.indent -= 1;
+        }
+        self.ident(f)?;
+        writeln!(f, "case name:")?;
+        self.indent += 1;
+        self.ident(f)?;
+        writeln!(f, "peer.reply_error(call, f\"Unknown request '{{name}}'\")")?;
+        self.indent -= 4;
+        write!(f, "\n\n")?;
+        Ok(())
+    }
 }
 
 impl Emitter for PyEmitter {
+    /// Fails with [`std::io::ErrorKind::Unsupported`] if a type holds an `fd`, as the Python
+    /// runtime can't pass file descriptors.
     fn emit(&mut self, document: &crate::parser::Document, writer: &mut dyn std::io::Write) -> R {
-        self.emit_header(writer)?;
+        let with_fd = (document.user_types.iter())
+            .flat_map(|ut| ut.children_user_types())
+            .find(|ut| {
+                (ut.children_types())
+                    .any(|tp| tp.nested_types().contains(&&Type::Builtin(Builtin::Fd)))
+            });
+        if let Some(ut) = with_fd {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                format!(
+                    "'{}' holds a file descriptor, which the Python runtime can't pass.",
+                    ut.name()
+                ),
+            ));
+        }
+        self.foreign.clear();
+        self.user_types.clear();
+        self.package = document
+            .package
+            .as_ref()
+            .map(|package| package.value.clone());
+        for foreign in document.foreign_types.iter() {
+            for child in foreign.user_type.children_user_types() {
+                self.foreign
+                    .insert(child.name().into(), foreign.package.clone());
+                self.user_types.insert(child.name().into(), child.clone());
+            }
+        }
+        for ut in document.user_types.iter() {
+            for child in ut.children_user_types() {
+                self.foreign.remove(child.name());
+                self.user_types.insert(child.name().into(), child.clone());
+            }
+        }
+        self.emit_header(writer, document)?;
         for utype in document.user_types.iter() {
             for child in utype.children_user_types() {
                 self.emit_user_type(writer, child)?;
             }
         }
+        let instantiations = instantiations(document);
+        for typ in &instantiations {
+            self.emit_instantiation(writer, typ)?;
+        }
+        if !instantiations.is_empty() {
+            write!(writer, "\n\n")?;
+        }
+        for service in document.services.iter() {
+            self.emit_service(writer, service)?;
+        }
         Ok(())
     }
 }
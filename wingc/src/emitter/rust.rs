@@ -1,13 +1,42 @@
 use std::{collections::HashMap, io::Write};
 
-use crate::parser::{Builtin, EnumVariant, Type, UserType};
+use crate::parser::{
+    Attribute, Attributes, Builtin, EnumVariant, Literal, Newtype, Package, Protocol, S, Service,
+    Struct, StructField, Type, UserType,
+};
 
-use super::Emitter;
+use super::{Emitter, documented, instantiations, wire_type_name};
 
+/// The collection `{K: V}` maps are emitted as.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum MapType {
+    #[default]
+    Hash,
+    /// Keeps entries sorted by key, so messages are encoded the same every time.
+    BTree,
+}
+
+/// Emits a document as a Rust module.
+///
+/// The code of a document with a `package foo.bar;` has to end up in the `crate::foo::bar`
+/// module, as types of other packages are only referred to, by their path in the module of their
+/// own package. Each package is emitted on its own, and the `pub mod` hierarchy holding them is
+/// left to the crate using them.
 #[derive(Debug, PartialEq, Clone)]
 pub struct RustEmitter {
     indent: usize,
     user_types: HashMap<String, UserType>,
+    map_type: MapType,
+    qualified_names: bool,
+    package: Option<Package>,
+    /// The package of each type that is used, but not emitted.
+    foreign: HashMap<String, Package>,
+}
+
+impl Default for RustEmitter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl RustEmitter {
@@ -15,35 +44,192 @@ impl RustEmitter {
         Self {
             indent: 0,
             user_types: Default::default(),
+            map_type: MapType::default(),
+            qualified_names: false,
+            package: None,
+            foreign: Default::default(),
+        }
+    }
+    pub fn with_map_type(self, map_type: MapType) -> Self {
+        Self { map_type, ..self }
+    }
+    /// Sends types as `package.Name`, so types of different packages can share a name.
+    pub fn with_qualified_names(self, qualified_names: bool) -> Self {
+        Self {
+            qualified_names,
+            ..self
+        }
+    }
+    /// The name a type is sent as.
+    fn wire_name(&self, name: &str) -> String {
+        let wire_name = (self.user_types.get(name)).map_or(name, UserType::wire_name);
+        match self.foreign.get(name).or(self.package.as_ref()) {
+            Some(package) if self.qualified_names => format!("{package}.{wire_name}"),
+            _ => wire_name.to_owned(),
+        }
+    }
+    /// The path of a user type, which lives in the module of its package if it isn't ours.
+    fn user_type_path(&self, name: &str) -> String {
+        match self.foreign.get(name) {
+            Some(package) => format!("crate::{}::{name}", package.0.join("::")),
+            None => name.to_owned(),
         }
     }
     fn indent(&self, f: &mut dyn Write) -> R {
         write!(f, "{}", " ".repeat(self.indent * 4))
     }
     fn emit_header(&self, f: &mut dyn Write) -> R {
-        write!(f, "use serde::{{Serialize, Deserialize}};\n")?;
-        write!(f, "use wing_rpc::Message as WingMessage;\n")?;
+        writeln!(f, "use serde::{{Serialize, Deserialize}};")?;
+        writeln!(f, "use wing_rpc::Message as WingMessage;")?;
         write!(f, "\n\n")
     }
+    fn emit_docs(&self, f: &mut dyn Write, docs: &[String], attrs: &[S<Attribute>]) -> R {
+        for line in documented(docs, attrs) {
+            self.indent(f)?;
+            match line.is_empty() {
+                true => writeln!(f, "///")?,
+                false => writeln!(f, "/// {line}")?,
+            }
+        }
+        Ok(())
+    }
     fn is_ut_partialeq(&self, ut: &UserType) -> bool {
         ut.children_types().all(|tp| self.is_partialeq(&tp.value))
     }
     fn is_partialeq(&self, typ: &Type) -> bool {
         match typ {
-            Type::Builtin(Builtin::F32 | Builtin::F64) => false,
+            Type::Builtin(Builtin::F32 | Builtin::F64 | Builtin::Fd) => false,
             Type::Builtin(_) => true,
-            Type::List(tp) => self.is_partialeq(tp),
-            Type::User(ut) => self.is_ut_partialeq(&self.user_types[ut]),
+            Type::List(tp) | Type::Optional(tp) | Type::Map(_, tp) => self.is_partialeq(tp),
+            // Type parameters are bound by the derive itself.
+            Type::User(ut) => (self.user_types.get(ut)).is_none_or(|ut| self.is_ut_partialeq(ut)),
             Type::UserInline(user) => self.is_ut_partialeq(user),
+            Type::Generic(ut, args) => {
+                self.is_ut_partialeq(&self.user_types[ut])
+                    && args.iter().all(|arg| self.is_partialeq(arg))
+            }
+        }
+    }
+    fn is_ut_clone(&self, ut: &UserType) -> bool {
+        ut.children_types().all(|tp| self.is_clone(&tp.value))
+    }
+    fn is_clone(&self, typ: &Type) -> bool {
+        match typ {
+            Type::Builtin(Builtin::Fd) => false,
+            Type::Builtin(_) => true,
+            Type::List(tp) | Type::Optional(tp) | Type::Map(_, tp) => self.is_clone(tp),
+            Type::User(ut) => (self.user_types.get(ut)).is_none_or(|ut| self.is_ut_clone(ut)),
+            Type::UserInline(user) => self.is_ut_clone(user),
+            Type::Generic(ut, args) => {
+                self.is_ut_clone(&self.user_types[ut]) && args.iter().all(|arg| self.is_clone(arg))
+            }
+        }
+    }
+    /// Returns the attribute needed to (de)serialize `typ`, if any.
+    ///
+    /// Only struct fields can be left out or changed by attributes, so `field` is given when `typ`
+    /// is the type of one. Those with a default value also give the path of the function
+    /// returning it.
+    fn serde_attribute(
+        &self,
+        typ: &Type,
+        field: Option<&StructField>,
+        default: Option<String>,
+    ) -> Option<String> {
+        let mut args = vec![];
+        if let Some(field) = field {
+            if let Some(name) = field.attrs.rename() {
+                args.push(format!("rename = {name:?}"));
+            }
+            if field.attrs.has_attr("flatten") {
+                args.push("flatten".into());
+            }
+            // Skipped fields are never sent, so they are always filled with their default.
+            if field.attrs.has_attr("skip") {
+                args.push("skip".into());
+                args.extend(default.map(|path| format!("default = \"{path}\"")));
+                return Some(format!("#[serde({})]", args.join(", ")));
+            }
+        }
+        match default {
+            Some(path) => args.push(format!("default = \"{path}\"")),
+            None if field.is_some() && matches!(typ, Type::Optional(_)) => {
+                args.push("default, skip_serializing_if = \"Option::is_none\"".into())
+            }
+            None => {}
+        }
+        if self.has_fd(typ) {
+            args.push("with = \"wing_rpc::fd\"".into());
+        }
+        (!args.is_empty()).then(|| format!("#[serde({})]", args.join(", ")))
+    }
+    fn emit_deprecated(&self, f: &mut dyn Write, attrs: &[S<Attribute>]) -> R {
+        let Some(reason) = attrs.deprecated() else {
+            return Ok(());
+        };
+        self.indent(f)?;
+        match reason {
+            Some(reason) => writeln!(f, "#[deprecated = {reason:?}]"),
+            None => writeln!(f, "#[deprecated]"),
+        }
+    }
+    /// Generated code using deprecated items is allowed to, so only the user's code is warned.
+    fn emit_allow_deprecated(&self, f: &mut dyn Write, allow: bool) -> R {
+        if allow {
+            self.indent(f)?;
+            writeln!(f, "#[allow(deprecated)]")?;
+        }
+        Ok(())
+    }
+    fn mentions_deprecated(&self, typ: &Type) -> bool {
+        typ.nested_types().into_iter().any(|typ| match typ {
+            Type::UserInline(ut) => ut.attrs().deprecated().is_some(),
+            typ => (typ.as_user().and_then(|name| self.user_types.get(name)))
+                .is_some_and(|ut| ut.attrs().deprecated().is_some()),
+        })
+    }
+    /// Returns `true` if `ut`, its fields or variants, or the types it holds are deprecated.
+    fn uses_deprecated(&self, ut: &UserType) -> bool {
+        let members = match ut {
+            UserType::Struct(st) => st.fields.iter().any(|fd| fd.attrs.deprecated().is_some()),
+            UserType::Enum(en) => {
+                (en.definitions.iter()).any(|var| var.attrs().deprecated().is_some())
+            }
+            UserType::Newtype(_) => false,
+        };
+        ut.attrs().deprecated().is_some()
+            || members
+            || ut.children_types().any(|tp| self.mentions_deprecated(&tp))
+    }
+    fn has_fd(&self, typ: &Type) -> bool {
+        match typ {
+            Type::Builtin(Builtin::Fd) => true,
+            Type::List(tp) | Type::Optional(tp) | Type::Map(_, tp) => self.has_fd(tp),
+            _ => false,
         }
     }
     fn get_type_name(&self, typ: &Type) -> String {
         match typ {
-            Type::User(name) => name.to_string(),
+            Type::User(name) => self.user_type_path(name),
             Type::UserInline(user) => user.name().to_owned(),
+            Type::Generic(name, args) => {
+                let args: Vec<_> = args.iter().map(|arg| self.get_type_name(arg)).collect();
+                format!("{}<{}>", self.user_type_path(name), args.join(", "))
+            }
             Type::List(inner) => {
                 format!("Vec<{}>", self.get_type_name(inner))
             }
+            Type::Optional(inner) => {
+                format!("Option<{}>", self.get_type_name(inner))
+            }
+            Type::Map(key, value) => {
+                let map = match self.map_type {
+                    MapType::Hash => "std::collections::HashMap",
+                    MapType::BTree => "std::collections::BTreeMap",
+                };
+                let (key, value) = (self.get_type_name(key), self.get_type_name(value));
+                format!("{map}<{key}, {value}>")
+            }
             Type::Builtin(tp) => match tp {
                 Builtin::USize
                 | Builtin::ISize
@@ -62,67 +248,384 @@ impl RustEmitter {
                 Builtin::Int => "i32",
                 Builtin::String => "String",
                 Builtin::Binary => "Vec<u8>",
+                Builtin::Fd => "std::os::fd::OwnedFd",
             }
             .to_string(),
         }
     }
     fn emit_user_type(&mut self, f: &mut dyn Write, ut: &UserType) -> R {
-        let mut derives = vec!["Debug", "Clone"];
+        let mut derives = vec!["Debug"];
+        if self.is_ut_clone(ut) {
+            derives.push("Clone");
+        }
         if self.is_ut_partialeq(ut) {
             derives.push("PartialEq");
         }
+        // Whichever map type is used, keys have to be hashed or sorted.
+        let lookup = |name: &str| self.user_types.get(name);
+        if Type::User(ut.name().into()).is_map_key(&lookup) {
+            derives.extend(["Eq", "Hash", "PartialOrd", "Ord"]);
+        }
         derives.extend(["Serialize", "Deserialize"]);
+        self.emit_docs(f, ut.docs(), ut.attrs())?;
+        self.emit_deprecated(f, ut.attrs())?;
+        let holds_deprecated = ut.children_types().any(|tp| self.mentions_deprecated(&tp));
+        self.emit_allow_deprecated(f, holds_deprecated)?;
         self.indent(f)?;
-        write!(f, "#[derive({})]\n", derives.join(", "))?;
+        writeln!(f, "#[derive({})]", derives.join(", "))?;
         let name = ut.name();
+        let generics = generics(ut.params());
+        if let UserType::Newtype(_) = ut {
+            self.indent(f)?;
+            writeln!(f, "#[serde(transparent)]")?;
+        }
+        if let Some(wire_name) = ut.attrs().rename() {
+            self.indent(f)?;
+            writeln!(f, "#[serde(rename = {wire_name:?})]")?;
+        }
         self.indent(f)?;
         write!(f, "pub ")?;
         match ut {
             UserType::Struct(st) => {
-                write!(f, "struct {} {{\n", name)?;
+                writeln!(f, "struct {name}{generics} {{")?;
                 self.indent += 1;
                 for field in st.fields.iter() {
+                    self.emit_docs(f, &field.docs, &field.attrs)?;
+                    self.emit_deprecated(f, &field.attrs)?;
+                    let default = (field.default.as_ref()).map(|_| {
+                        // Type parameters have to be named, as the function doesn't use them.
+                        let turbofish = match generics.is_empty() {
+                            true => String::new(),
+                            false => format!("::{generics}"),
+                        };
+                        format!("{name}{turbofish}::default_{}", field.name)
+                    });
+                    if let Some(attribute) = self.serde_attribute(&field.typ, Some(field), default)
+                    {
+                        self.indent(f)?;
+                        writeln!(f, "{attribute}")?;
+                    }
                     self.indent(f)?;
-                    write!(
-                        f,
-                        "pub {}: {},\n",
-                        field.name,
-                        self.get_type_name(&field.typ)
-                    )?;
+                    writeln!(f, "pub {}: {},", field.name, self.get_type_name(&field.typ))?;
                 }
                 self.indent -= 1;
                 f.write_all(b"}\n\n")?;
+                let deprecated = self.uses_deprecated(ut);
+                self.emit_defaults(f, st, deprecated)?;
             }
             UserType::Enum(en) => {
-                write!(f, "enum {} {{\n", name)?;
+                writeln!(f, "enum {name}{generics} {{")?;
                 self.indent += 1;
                 for field in en.definitions.iter() {
+                    self.emit_docs(f, field.docs(), field.attrs())?;
+                    self.emit_deprecated(f, field.attrs())?;
+                    if field.tag() != field.name() {
+                        self.indent(f)?;
+                        writeln!(f, "#[serde(rename = {:?})]", field.tag())?;
+                    }
                     match &field.value {
                         EnumVariant::NamedVariant(field) => {
                             self.indent(f)?;
                             let name = &*field.name;
                             let tp = self.get_type_name(&field.typ);
-                            write!(f, "{name}({tp}),\n")?;
+                            match self.serde_attribute(&field.typ, None, None) {
+                                Some(attribute) => writeln!(f, "{name}({attribute} {tp}),")?,
+                                None => writeln!(f, "{name}({tp}),")?,
+                            }
                         }
                         EnumVariant::UserType(ut) => {
                             self.indent(f)?;
                             let name = ut.name();
-                            write!(f, "{name}({name}),\n")?;
+                            writeln!(f, "{name}({name}),")?;
+                        }
+                        EnumVariant::Unit(unit) => {
+                            self.indent(f)?;
+                            writeln!(f, "{},", unit.name)?;
                         }
                     }
                 }
                 self.indent -= 1;
                 f.write_all(b"}\n\n")?;
             }
+            UserType::Newtype(nt) => {
+                let tp = self.get_type_name(&nt.typ);
+                match self.serde_attribute(&nt.typ, None, None) {
+                    Some(attribute) => write!(f, "struct {name}({attribute} pub {tp});\n\n")?,
+                    None => write!(f, "struct {name}(pub {tp});\n\n")?,
+                }
+                // Newtypes are only sent as part of messages.
+                return Ok(());
+            }
+        }
+
+        // Generic types are only named once instantiated, see `emit_instantiation`.
+        if generics.is_empty() {
+            let wire_name = self.wire_name(name);
+            let deprecated = ut.attrs().deprecated().is_some();
+            self.emit_message_impl(f, name, &wire_name, deprecated)?;
         }
+        Ok(())
+    }
 
-        write!(f, "impl<'a> WingMessage<'a> for {name} {{\n")?;
+    fn emit_message_impl(
+        &mut self,
+        f: &mut dyn Write,
+        typ: &str,
+        wire_name: &str,
+        deprecated: bool,
+    ) -> R {
+        self.emit_allow_deprecated(f, deprecated)?;
+        writeln!(f, "impl<'a> WingMessage<'a> for {typ} {{")?;
         self.indent += 1;
         self.indent(f)?;
-        write!(f, "const NAME: &'static str = \"{name}\";\n")?;
+        writeln!(f, "const NAME: &'static str = {wire_name:?};")?;
+        self.indent -= 1;
+        write!(f, "}}\n\n")
+    }
+
+    /// Names an instantiation of a generic type, so it can be sent as a message.
+    fn emit_instantiation(&mut self, f: &mut dyn Write, typ: &Type) -> R {
+        let wire_name = wire_type_name(typ, &|name| self.wire_name(name));
+        let deprecated = self.mentions_deprecated(typ);
+        self.emit_message_impl(f, &self.get_type_name(typ), &wire_name, deprecated)
+    }
+
+    /// Emits the functions returning the default value of each field that has one, along with
+    /// a `Default` implementation if none of the fields is required.
+    fn emit_defaults(&mut self, f: &mut dyn Write, st: &Struct, deprecated: bool) -> R {
+        let name = &st.name;
+        let generics = generics(&st.params);
+        if st.fields.iter().all(|field| field.default.is_none()) {
+            return Ok(());
+        }
+        self.emit_allow_deprecated(f, deprecated)?;
+        writeln!(f, "impl{generics} {name}{generics} {{")?;
+        self.indent += 1;
+        for field in st.fields.iter() {
+            let Some(default) = &field.default else {
+                continue;
+            };
+            self.indent(f)?;
+            let typ = self.get_type_name(&field.typ);
+            writeln!(f, "fn default_{}() -> {typ} {{", field.name)?;
+            self.indent += 1;
+            self.indent(f)?;
+            writeln!(f, "{}", self.get_literal(default, &field.typ))?;
+            self.indent -= 1;
+            self.indent(f)?;
+            writeln!(f, "}}")?;
+        }
+        self.indent -= 1;
+        write!(f, "}}\n\n")?;
+
+        let required = |field: &StructField| {
+            field.default.is_none() && !matches!(field.typ, Type::Optional(_))
+        };
+        if st.fields.iter().any(|field| required(field)) {
+            return Ok(());
+        }
+        self.emit_allow_deprecated(f, deprecated)?;
+        writeln!(f, "impl{generics} Default for {name}{generics} {{")?;
+        self.indent += 1;
+        self.indent(f)?;
+        writeln!(f, "fn default() -> Self {{")?;
+        self.indent += 1;
+        self.indent(f)?;
+        writeln!(f, "Self {{")?;
+        self.indent += 1;
+        for field in st.fields.iter() {
+            self.indent(f)?;
+            match field.default {
+                Some(_) => writeln!(f, "{0}: Self::default_{0}(),", field.name)?,
+                None => writeln!(f, "{}: None,", field.name)?,
+            }
+        }
+        self.indent -= 1;
+        self.indent(f)?;
+        writeln!(f, "}}")?;
+        self.indent -= 1;
+        self.indent(f)?;
+        writeln!(f, "}}")?;
+        self.indent -= 1;
+        write!(f, "}}\n\n")?;
+        Ok(())
+    }
+    fn as_newtype<'a>(&'a self, typ: &'a Type) -> Option<&'a Newtype> {
+        let ut = match typ {
+            Type::User(name) => &self.user_types[name],
+            Type::UserInline(ut) => ut,
+            _ => return None,
+        };
+        match ut {
+            UserType::Newtype(nt) => Some(nt),
+            _ => None,
+        }
+    }
+    /// Writes `value`, which was checked to be a valid `typ`, as a Rust expression.
+    fn get_literal(&self, value: &Literal, typ: &Type) -> String {
+        if let Some(nt) = self.as_newtype(typ) {
+            let name = self.get_type_name(typ);
+            return format!("{name}({})", self.get_literal(value, &nt.typ));
+        }
+        let join = |items: Vec<String>| items.join(", ");
+        match (value, typ) {
+            (_, Type::Optional(inner)) => format!("Some({})", self.get_literal(value, inner)),
+            (Literal::Int(n), Type::Builtin(Builtin::F32 | Builtin::F64)) => format!("{n}.0"),
+            (Literal::Int(n), _) => n.to_string(),
+            (Literal::Float(n), _) => format!("{n:?}"),
+            (Literal::Bool(b), _) => b.to_string(),
+            (Literal::String(text), _) => format!("{text:?}.to_owned()"),
+            (Literal::List(items), Type::Builtin(Builtin::Binary)) => {
+                format!(
+                    "vec![{}]",
+                    join(items.iter().map(ToString::to_string).collect())
+                )
+            }
+            (Literal::List(items), Type::List(inner)) => {
+                let items = items.iter().map(|item| self.get_literal(item, inner));
+                format!("vec![{}]", join(items.collect()))
+            }
+            (Literal::Map(entries), _) if entries.is_empty() => "Default::default()".into(),
+            (Literal::Map(entries), Type::Map(key, inner)) => {
+                let entries = entries.iter().map(|(k, v)| {
+                    format!(
+                        "({}, {})",
+                        self.get_literal(k, key),
+                        self.get_literal(v, inner)
+                    )
+                });
+                format!("[{}].into_iter().collect()", join(entries.collect()))
+            }
+            (Literal::Variant(name), Type::User(ut) | Type::Generic(ut, _)) => {
+                format!("{}::{name}", self.user_type_path(ut))
+            }
+            (Literal::Variant(name), Type::UserInline(ut)) => format!("{}::{name}", ut.name()),
+            _ => unreachable!("'{value}' is not a valid '{typ}'"),
+        }
+    }
+
+    fn emit_protocol(&mut self, f: &mut dyn Write, protocol: &Protocol) -> R {
+        let name = &protocol.name;
+        let client = self.user_type_path(&protocol.client);
+        let server = self.user_type_path(&protocol.server);
+        let deprecated = [&protocol.client, &protocol.server]
+            .into_iter()
+            .any(|side| self.mentions_deprecated(&Type::User(side.value.clone())));
+        self.emit_allow_deprecated(f, deprecated)?;
+        writeln!(
+            f,
+            "pub type {name}ClientPeer = wing_rpc::TypedPeer<{server}, {client}>;"
+        )?;
+        self.emit_allow_deprecated(f, deprecated)?;
+        write!(
+            f,
+            "pub type {name}ServerPeer = wing_rpc::TypedPeer<{client}, {server}>;\n\n"
+        )?;
+        Ok(())
+    }
+    fn emit_service(&mut self, f: &mut dyn Write, service: &Service) -> R {
+        let name = &service.name;
+        let deprecated = (service.methods.iter()).any(|method| {
+            self.mentions_deprecated(&method.input) || self.mentions_deprecated(&method.output)
+        });
+        write!(
+            f,
+            "pub struct {name}Client<'p>(pub &'p mut wing_rpc::Peer);\n\n"
+        )?;
+        self.emit_allow_deprecated(f, deprecated)?;
+        writeln!(f, "impl {name}Client<'_> {{")?;
+        self.indent += 1;
+        for method in service.methods.iter() {
+            let input = self.get_type_name(&method.input);
+            let output = self.get_type_name(&method.output);
+            self.indent(f)?;
+            if method.stream {
+                writeln!(
+                    f,
+                    "pub fn {}(&mut self, request: {input}) -> wing_rpc::WingResult<wing_rpc::call::CallStream<'_, {output}>> {{",
+                    method.name
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "pub fn {}(&mut self, request: {input}) -> wing_rpc::WingResult<{output}> {{",
+                    method.name
+                )?;
+            }
+            self.indent += 1;
+            self.indent(f)?;
+            match method.stream {
+                true => writeln!(f, "self.0.call_stream(request)")?,
+                false => writeln!(f, "self.0.call(request)")?,
+            }
+            self.indent -= 1;
+            self.indent(f)?;
+            writeln!(f, "}}")?;
+        }
         self.indent -= 1;
         write!(f, "}}\n\n")?;
 
+        self.emit_allow_deprecated(f, deprecated)?;
+        writeln!(f, "pub trait {name} {{")?;
+        self.indent += 1;
+        for method in service.methods.iter() {
+            let input = self.get_type_name(&method.input);
+            let output = self.get_type_name(&method.output);
+            self.indent(f)?;
+            if method.stream {
+                writeln!(
+                    f,
+                    "fn {}(&mut self, request: {input}, reply: &mut wing_rpc::call::ReplyStream<'_, {output}>) -> wing_rpc::WingResult<()>;",
+                    method.name
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "fn {}(&mut self, request: {input}) -> wing_rpc::WingResult<{output}>;",
+                    method.name
+                )?;
+            }
+        }
+        self.indent(f)?;
+        writeln!(
+            f,
+            "fn serve(&mut self, peer: &mut wing_rpc::Peer) -> wing_rpc::WingResult<()> {{"
+        )?;
+        self.indent += 1;
+        self.indent(f)?;
+        writeln!(f, "let call = peer.next_call()?;")?;
+        self.indent(f)?;
+        writeln!(f, "match call.name() {{")?;
+        self.indent += 1;
+        for method in service.methods.iter() {
+            self.indent(f)?;
+            let input = wire_type_name(&method.input, &|name| self.wire_name(name));
+            let name = &method.name;
+            if method.stream {
+                writeln!(
+                    f,
+                    "{input:?} => peer.answer_stream(&call, |request, reply| self.{name}(request, reply)),"
+                )?;
+            } else {
+                writeln!(
+                    f,
+                    "{input:?} => peer.answer(&call, |request| self.{name}(request)),"
+                )?;
+            }
+        }
+        self.indent(f)?;
+        writeln!(
+            f,
+            "name => peer.reply_error(&call, format!(\"Unknown request '{{name}}'\")),"
+        )?;
+        self.indent -= 1;
+        self.indent(f)?;
+        writeln!(f, "}}")?;
+        self.indent -= 1;
+        self.indent(f)?;
+        writeln!(f, "}}")?;
+        self.indent -= 1;
+        write!(f, "}}\n\n")?;
         Ok(())
     }
 
@@ -135,12 +638,35 @@ impl RustEmitter {
 
 type R = std::io::Result<()>;
 
+/// The type parameters of a generic type, written `<T, U>`, or nothing for other types.
+fn generics(params: &[String]) -> String {
+    match params {
+        [] => String::new(),
+        params => format!("<{}>", params.join(", ")),
+    }
+}
+
 impl Emitter for RustEmitter {
     fn emit(&mut self, document: &crate::parser::Document, writer: &mut dyn std::io::Write) -> R {
         self.emit_header(writer)?;
         self.user_types.clear();
+        self.foreign.clear();
+        self.package = document
+            .package
+            .as_ref()
+            .map(|package| package.value.clone());
 
+        for foreign in document.foreign_types.iter() {
+            for child in foreign.user_type.children_user_types() {
+                self.foreign
+                    .insert(child.name().into(), foreign.package.clone());
+            }
+            self.register_ut(&foreign.user_type);
+        }
         for ut in document.user_types.iter() {
+            for child in ut.children_user_types() {
+                self.foreign.remove(child.name());
+            }
             self.register_ut(&ut.value);
         }
 
@@ -153,6 +679,15 @@ impl Emitter for RustEmitter {
                 self.emit_user_type(writer, child)?;
             }
         }
+        for typ in instantiations(document) {
+            self.emit_instantiation(writer, &typ)?;
+        }
+        for service in document.services.iter() {
+            self.emit_service(writer, service)?;
+        }
+        for protocol in document.protocols.iter() {
+            self.emit_protocol(writer, protocol)?;
+        }
         Ok(())
     }
 }
@@ -1,4 +1,5 @@
 pub mod emitter;
+pub mod loader;
 pub mod parser;
 pub mod semantic_analyzer;
 
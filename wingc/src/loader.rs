@@ -0,0 +1,200 @@
+//! Loads schemas spread over several files, following their imports.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use miette::{Context, Diagnostic, IntoDiagnostic, LabeledSpan, NamedSource};
+use thiserror::Error;
+
+use crate::parser::{self, Document, ForeignType, S};
+
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("Failed to read '{path}'.")]
+    Unreadable {
+        path: S<String>,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("'{path}' is imported in a cycle: {}.", .cycle.join(" -> "))]
+    Cycle { path: S<String>, cycle: Vec<String> },
+}
+
+impl Diagnostic for Error {
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        let path = match self {
+            Self::Unreadable { path, .. } | Self::Cycle { path, .. } => path,
+        };
+        Some(Box::new(std::iter::once(LabeledSpan::new_with_span(
+            Some("Imported here".into()),
+            path.span,
+        ))))
+    }
+}
+
+/// A file of a [`Schema`].
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub text: String,
+    pub document: Document,
+    /// The position in [`Schema::files`] of each of `document.imports`.
+    pub imports: Vec<usize>,
+}
+
+impl SourceFile {
+    /// The text of the file, to report errors against.
+    pub fn named_source(&self) -> NamedSource<String> {
+        NamedSource::new(self.path.display().to_string(), self.text.clone())
+    }
+}
+
+/// A root file along with every file it imports, directly or not.
+#[derive(Debug, Clone)]
+pub struct Schema {
+    /// Imported files come before the files importing them, so the root file is last.
+    pub files: Vec<SourceFile>,
+}
+
+impl Schema {
+    pub fn root(&self) -> &SourceFile {
+        self.files.last().unwrap()
+    }
+    /// The files of the root package merged into one document, to be emitted together.
+    ///
+    /// Files without a package belong to the package of the root file, while types of other
+    /// packages are kept as [`ForeignType`]s.
+    ///
+    /// Types are simply put side by side, as [`crate::semantic_analyzer::analyze_schema`] rejects
+    /// schemas defining a type more than once.
+    pub fn merged(&self) -> Document {
+        let package = self.root().document.package.clone();
+        let mut merged = Document {
+            package: package.clone(),
+            imports: vec![],
+            user_types: vec![],
+            aliases: vec![],
+            services: vec![],
+            protocols: vec![],
+            foreign_types: vec![],
+        };
+        for file in &self.files {
+            let document = file.document.clone();
+            match document.package {
+                Some(other) if Some(&other.value) != package.as_deref() => {
+                    let foreign = document.user_types.into_iter().map(|ut| ForeignType {
+                        package: other.value.clone(),
+                        user_type: ut.value,
+                    });
+                    merged.foreign_types.extend(foreign);
+                    merged.aliases.extend(document.aliases);
+                }
+                _ => {
+                    merged.user_types.extend(document.user_types);
+                    merged.aliases.extend(document.aliases);
+                    merged.services.extend(document.services);
+                    merged.protocols.extend(document.protocols);
+                }
+            }
+        }
+        merged
+    }
+}
+
+#[derive(Default)]
+struct Loader {
+    files: Vec<SourceFile>,
+    /// Files that were loaded, by their canonical path.
+    loaded: HashMap<PathBuf, usize>,
+    /// Files whose imports are being loaded, with the path they were imported as.
+    loading: Vec<(PathBuf, String)>,
+}
+
+impl Loader {
+    fn load(&mut self, path: PathBuf, canonical: PathBuf) -> miette::Result<usize> {
+        let text = std::fs::read_to_string(&path)
+            .into_diagnostic()
+            .with_context(|| format!("Failed to read '{}'", path.display()))?;
+        let document = parser::parse_document(&text)
+            .with_context(|| format!("Failed to parse '{}'", path.display()))?;
+        self.loading
+            .push((canonical.clone(), path.display().to_string()));
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut imports = vec![];
+        for import in &document.imports {
+            let named_source = || NamedSource::new(path.display().to_string(), text.clone());
+            let imported = dir.join(&import.path.value);
+            let imported_canonical = match imported.canonicalize() {
+                Ok(canonical) => canonical,
+                Err(source) => {
+                    let err = Error::Unreadable {
+                        path: import.path.clone(),
+                        source,
+                    };
+                    return Err(miette::Report::from(err).with_source_code(named_source()));
+                }
+            };
+            if let Some(start) = (self.loading.iter()).position(|(p, _)| *p == imported_canonical) {
+                let cycle = (self.loading[start..].iter())
+                    .map(|(_, name)| name.clone())
+                    .chain([imported.display().to_string()])
+                    .collect();
+                let err = Error::Cycle {
+                    path: import.path.clone(),
+                    cycle,
+                };
+                return Err(miette::Report::from(err).with_source_code(named_source()));
+            }
+            let index = match self.loaded.get(&imported_canonical) {
+                Some(&index) => index,
+                None => self.load(imported, imported_canonical)?,
+            };
+            imports.push(index);
+        }
+        self.loading.pop();
+        self.files.push(SourceFile {
+            path,
+            text,
+            document,
+            imports,
+        });
+        self.loaded.insert(canonical, self.files.len() - 1);
+        Ok(self.files.len() - 1)
+    }
+}
+
+/// Loads the file at `path` and everything it imports.
+pub fn load(path: impl AsRef<Path>) -> miette::Result<Schema> {
+    let path = path.as_ref().to_path_buf();
+    let canonical = path
+        .canonicalize()
+        .into_diagnostic()
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let mut loader = Loader::default();
+    loader.load(path, canonical)?;
+    Ok(Schema {
+        files: loader.files,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn load_imports_once() {
+        let schema = load("test-files/imports.wing").unwrap();
+        let names: Vec<_> = (schema.files.iter())
+            .map(|file| file.path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["geometry.wing", "style.wing", "imports.wing"]);
+        assert_eq!(schema.root().imports, [1, 0]);
+    }
+
+    #[test]
+    fn detect_cycles() {
+        let err = load("err_examples/import_cycle.wing").unwrap_err();
+        assert!(err.to_string().contains("is imported in a cycle"), "{err}");
+    }
+}
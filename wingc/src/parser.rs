@@ -44,11 +44,20 @@ pub fn parse_document(text: &str) -> miette::Result<Document> {
     let doc: S<Document> = tokens.next_item()?;
     let remaining = text[doc.span.end()..].trim();
     // Well, if there is remaining doc text, parsing failed.
-    if remaining.len() > 0 {
+    if !remaining.is_empty() {
         return parse_document(remaining);
     }
     Ok(doc.value)
 }
+/// Parses a type written like in schemas, such as the wire name `Page<User>`.
+pub fn parse_type(text: &str) -> miette::Result<Type> {
+    let mut tokens = WingParser::parse2(Rule::r#type, text)?;
+    let typ: S<Type> = tokens.next_item()?;
+    if typ.span.end() != text.len() {
+        miette::bail!("Unexpected '{}' after type", &text[typ.span.end()..]);
+    }
+    Ok(typ.value)
+}
 #[cfg(test)]
 mod test {
     use super::*;
@@ -83,6 +92,9 @@ mod test {
             Self {
                 name: name.into(),
                 typ: type_.into(),
+                docs: vec![],
+                default: None,
+                attrs: vec![],
             }
         }
     }
@@ -91,6 +103,12 @@ mod test {
         fn list(inner: impl Into<Type>) -> Self {
             Self::List(Box::new(inner.into()))
         }
+        fn optional(inner: impl Into<Type>) -> Self {
+            Self::Optional(Box::new(inner.into()))
+        }
+        fn map(key: impl Into<Type>, value: impl Into<Type>) -> Self {
+            Self::Map(Box::new(key.into()), Box::new(value.into()))
+        }
     }
 
     fn s(text: &'_ str) -> String {
@@ -130,13 +148,195 @@ mod test {
             }",
             Struct {
                 name: s("A"),
+                params: vec![],
+                docs: vec![],
                 fields: svec![
                     StructField::new("c", Builtin::String),
                     StructField::new("h", "B")
-                ]
+                ],
+                attrs: vec![],
+            },
+            Struct
+        );
+    }
+    #[test]
+    fn parse_optional() {
+        assert_parse!("string?", Type::optional(Builtin::String), Type);
+        assert_parse!("[Point?]", Type::list(Type::optional("Point")), Type);
+        assert_parse!("[Point]?", Type::optional(Type::list("Point")), Type);
+    }
+
+    #[test]
+    fn parse_map() {
+        assert_parse!("{u64: Point}", Type::map(Builtin::U64, "Point"), Type);
+        assert_parse!(
+            "{string: [Point?]}?",
+            Type::optional(Type::map(
+                Builtin::String,
+                Type::list(Type::optional("Point"))
+            )),
+            Type
+        );
+    }
+
+    #[test]
+    fn parse_defaults() {
+        let with_default = |name, typ: Type, value| StructField {
+            default: Some(S::new_unspanned(value)),
+            ..StructField::new(name, typ)
+        };
+        assert_parse!(
+            "struct Job {
+                attempts: u32 = 5;
+                ratio: f32 = -0.5,
+                tags: [string] = [\"a\", \"b\"];
+                limits: {string: i32} = {\"cpu\": 2};
+                level: Level = High;
+            }",
+            Struct {
+                name: s("Job"),
+                params: vec![],
+                docs: vec![],
+                fields: svec![
+                    with_default("attempts", Builtin::U32.into(), Literal::Int(5)),
+                    with_default("ratio", Builtin::F32.into(), Literal::Float(-0.5)),
+                    with_default(
+                        "tags",
+                        Type::list(Builtin::String),
+                        Literal::List(vec![
+                            Literal::String("a".into()),
+                            Literal::String("b".into())
+                        ])
+                    ),
+                    with_default(
+                        "limits",
+                        Type::map(Builtin::String, Builtin::I32),
+                        Literal::Map(vec![(Literal::String("cpu".into()), Literal::Int(2))])
+                    ),
+                    with_default("level", "Level".into(), Literal::Variant("High".into())),
+                ],
+                attrs: vec![],
+            },
+            Struct
+        );
+    }
+
+    #[test]
+    fn parse_string_escapes() {
+        let with_default = |name, value: &str| StructField {
+            default: Some(S::new_unspanned(Literal::String(value.into()))),
+            ..StructField::new(name, Builtin::String)
+        };
+        assert_parse!(
+            r#"struct A {
+                h: string = "q\"";
+                path: string = "C:\\wing";
+                lines: string = "a\nb";
+            }"#,
+            Struct {
+                name: s("A"),
+                params: vec![],
+                docs: vec![],
+                fields: svec![
+                    with_default("h", "q\""),
+                    with_default("path", "C:\\wing"),
+                    with_default("lines", "a\nb"),
+                ],
+                attrs: vec![],
+            },
+            Struct
+        );
+        assert!(WingParser::parse(Rule::string, r#""\t""#).is_err());
+    }
+
+    #[test]
+    fn parse_docs() {
+        assert_parse!(
+            "/// A
+            ///
+            ///   indented
+            struct A { // comment
+                /// c
+                c: string; /* comment */
+                //// comment
+                h: B;
+            }",
+            Struct {
+                name: s("A"),
+                params: vec![],
+                docs: vec![s("A"), s(""), s("  indented")],
+                fields: svec![
+                    StructField {
+                        docs: vec![s("c")],
+                        ..StructField::new("c", Builtin::String)
+                    },
+                    StructField::new("h", "B")
+                ],
+                attrs: vec![],
+            },
+            Struct
+        );
+    }
+    #[test]
+    fn parse_unattached_docs() {
+        assert_parse!(
+            "struct A {
+                x: i32, /// trailing
+                /// before the end
+            }",
+            Struct {
+                name: s("A"),
+                params: vec![],
+                docs: vec![],
+                fields: svec![StructField::new("x", Builtin::I32)],
+                attrs: vec![],
             },
             Struct
         );
+        assert_parse!(
+            "enum E { A /// trailing
+                /// before the end
+            }",
+            Enum {
+                name: s("E"),
+                params: vec![],
+                docs: vec![],
+                definitions: svec![UnitVariant {
+                    name: s("A"),
+                    tag: None,
+                    docs: vec![],
+                    attrs: vec![],
+                }],
+                attrs: vec![],
+            },
+            Enum
+        );
+        let document = parse_document(
+            "/// The package
+            package shop;
+            /// An import
+            import \"common.wing\";
+            struct A;
+            /// The service
+            service Search {
+                /// A method
+                get(A) -> A;
+                /// before the end
+            }
+            /// The protocol
+            protocol Chat {
+                /// The client
+                client: A;
+                /// The server
+                server: A;
+            }
+            /// The end of the file",
+        )
+        .unwrap();
+        assert_eq!(document.package.unwrap().0, vec![s("shop")]);
+        assert_eq!(document.imports.len(), 1);
+        assert_eq!(document.services[0].methods[0].name, "get");
+        assert_eq!(document.protocols[0].server.value, "A");
     }
     #[test]
     fn parse_enum() {
@@ -149,16 +349,59 @@ mod test {
             }",
             Enum {
                 name: s("A"),
+                params: vec![],
+                docs: vec![],
                 definitions: svec![
                     UserType::Struct(S::new_unspanned(Struct {
                         name: s("B"),
-                        fields: svec![]
+                        params: vec![],
+                        docs: vec![],
+                        fields: svec![],
+                        attrs: vec![],
                     })),
                     UserType::Struct(S::new_unspanned(Struct {
                         name: s("C"),
-                        fields: svec![StructField::new("name", Builtin::String)]
+                        params: vec![],
+                        docs: vec![],
+                        fields: svec![StructField::new("name", Builtin::String)],
+                        attrs: vec![],
                     }))
-                ]
+                ],
+                attrs: vec![],
+            },
+            Enum
+        );
+    }
+
+    #[test]
+    fn parse_unit_variants() {
+        assert_parse!(
+            "enum Theme {
+                Light = \"light\",
+                /// The default.
+                Dark
+                Named: string
+            }",
+            Enum {
+                name: s("Theme"),
+                params: vec![],
+                docs: vec![],
+                definitions: svec![
+                    UnitVariant {
+                        name: s("Light"),
+                        tag: Some(s("light")),
+                        docs: vec![],
+                        attrs: vec![],
+                    },
+                    UnitVariant {
+                        name: s("Dark"),
+                        tag: None,
+                        docs: vec![s("The default.")],
+                        attrs: vec![],
+                    },
+                    StructField::new("Named", Builtin::String),
+                ],
+                attrs: vec![],
             },
             Enum
         );
@@ -176,15 +419,24 @@ mod test {
                 }
             ",
             Document {
+                package: None,
+                imports: vec![],
                 user_types: svec![Struct {
                     name: s("Person"),
+                    params: vec![],
+                    docs: vec![],
                     fields: svec![
                         StructField::new("age", Builtin::U8),
                         StructField::new("name", Builtin::String),
                         StructField::new("mood", Builtin::F32),
                         StructField::new("hair", "Hair"),
-                    ]
-                }]
+                    ],
+                    attrs: vec![],
+                }],
+                aliases: vec![],
+                services: vec![],
+                protocols: vec![],
+                foreign_types: vec![]
             }
         )
     }
@@ -202,15 +454,24 @@ mod test {
                 }
             ",
             Document {
+                package: None,
+                imports: vec![],
                 user_types: svec![Struct {
                     name: s("Person"),
+                    params: vec![],
+                    docs: vec![],
                     fields: svec![
                         StructField::new("age", Builtin::U8),
                         StructField::new("name", Builtin::String),
                         StructField::new("mood", Builtin::F32),
                         StructField::new("hair", "Hair"),
-                    ]
-                }]
+                    ],
+                    attrs: vec![],
+                }],
+                aliases: vec![],
+                services: vec![],
+                protocols: vec![],
+                foreign_types: vec![]
             }
         )
     }
@@ -233,24 +494,36 @@ mod test {
                 }
             ",
             Document {
+                package: None,
+                imports: vec![],
                 user_types: svec![
                     Struct {
                         name: s("A1"),
+                        params: vec![],
+                        docs: vec![],
                         fields: svec![
                             StructField::new("darega", Builtin::U32),
                             StructField::new("omaga", Builtin::I32),
                             StructField::new("odiga", Builtin::F32),
-                        ]
+                        ],
+                        attrs: vec![],
                     },
                     Struct {
                         name: s("A2"),
+                        params: vec![],
+                        docs: vec![],
                         fields: svec![
                             StructField::new("lerolero", Builtin::U8),
                             StructField::new("lepolepo", Builtin::Int),
                             StructField::new("tibirabirom", Builtin::USize),
-                        ]
+                        ],
+                        attrs: vec![],
                     }
-                ]
+                ],
+                aliases: vec![],
+                services: vec![],
+                protocols: vec![],
+                foreign_types: vec![]
             }
         )
     }
@@ -296,18 +569,216 @@ mod test {
                 }
             ",
             Document {
+                package: None,
+                imports: vec![],
                 user_types: svec![Enum {
                     name: s("Color"),
+                    params: vec![],
+                    docs: vec![],
                     definitions: svec![
                         StructField::new("RGB", "RGB"),
                         StructField::new("HSLV", "HSLV"),
                         StructField::new("Gray", "Gray"),
-                    ]
-                }]
+                    ],
+                    attrs: vec![],
+                }],
+                aliases: vec![],
+                services: vec![],
+                protocols: vec![],
+                foreign_types: vec![]
             }
         )
     }
 
+    #[test]
+    fn parse_service() {
+        assert_parse!(
+            "service Search {
+                get(ById) -> Item;
+                find(Query) -> stream Item
+                streams(Query) -> streamed
+            }",
+            Service {
+                name: s("Search"),
+                methods: svec![
+                    Method {
+                        name: s("get"),
+                        input: S::new_unspanned("ById".into()),
+                        output: S::new_unspanned("Item".into()),
+                        stream: false,
+                    },
+                    Method {
+                        name: s("find"),
+                        input: S::new_unspanned("Query".into()),
+                        output: S::new_unspanned("Item".into()),
+                        stream: true,
+                    },
+                    Method {
+                        name: s("streams"),
+                        input: S::new_unspanned("Query".into()),
+                        output: S::new_unspanned("streamed".into()),
+                        stream: false,
+                    },
+                ]
+            },
+            Service
+        );
+    }
+
+    #[test]
+    fn parse_protocol() {
+        let chat = Protocol {
+            name: s("Chat"),
+            client: S::new_unspanned(s("ClientMessage")),
+            server: S::new_unspanned(s("ServerMessage")),
+        };
+        assert_parse!(
+            "protocol Chat { client: ClientMessage, server: ServerMessage }",
+            chat.clone(),
+            Protocol
+        );
+        assert_parse!(
+            "protocol Chat {
+                server: ServerMessage;
+                client: ClientMessage;
+            }",
+            chat,
+            Protocol
+        );
+    }
+
+    #[test]
+    fn parse_package() {
+        assert_parse!("package shop;", Package(vec![s("shop")]), Package);
+        assert_parse!(
+            "package shop.orders",
+            Package(vec![s("shop"), s("orders")]),
+            Package
+        );
+    }
+
+    #[test]
+    fn parse_aliases_and_newtypes() {
+        assert_parse!(
+            "type Ids = [UserId];",
+            Alias {
+                name: s("Ids"),
+                typ: Type::list("UserId"),
+                docs: vec![],
+            },
+            Alias
+        );
+        assert_parse!(
+            "/// Identifies a user.
+            newtype UserId(u64)",
+            Newtype {
+                name: s("UserId"),
+                typ: Box::new(Builtin::U64.into()),
+                docs: vec![s("Identifies a user.")],
+                attrs: vec![],
+            },
+            Newtype
+        );
+    }
+
+    #[test]
+    fn parse_generics() {
+        assert_parse!(
+            "struct Pair<A, B> { first: A, second: [B] }",
+            Struct {
+                name: s("Pair"),
+                params: vec![s("A"), s("B")],
+                docs: vec![],
+                fields: svec![
+                    StructField::new("first", "A"),
+                    StructField::new("second", Type::list("B"))
+                ],
+                attrs: vec![],
+            },
+            Struct
+        );
+        assert_parse!(
+            "Page<User>?",
+            Type::optional(Type::Generic(s("Page"), vec!["User".into()])),
+            Type
+        );
+        assert_parse!(
+            "Pair<string, [u8]>",
+            Type::Generic(
+                s("Pair"),
+                vec![Builtin::String.into(), Type::list(Builtin::U8)]
+            ),
+            Type
+        );
+    }
+
+    #[test]
+    fn parse_attributes() {
+        let attr =
+            |name: &str, arg: Option<Literal>| S::new_unspanned(Attribute { name: s(name), arg });
+        assert_parse!(
+            "/// A hair.
+            @deprecated
+            @since(2)
+            struct Hair {
+                @rename(\"type\")
+                kind: string,
+                @flatten
+                color: Color,
+            }",
+            Struct {
+                name: s("Hair"),
+                params: vec![],
+                docs: vec![s("A hair.")],
+                fields: svec![
+                    StructField {
+                        attrs: vec![attr("rename", Some(Literal::String(s("type"))))],
+                        ..StructField::new("kind", Builtin::String)
+                    },
+                    StructField {
+                        attrs: vec![attr("flatten", None)],
+                        ..StructField::new("color", "Color")
+                    },
+                ],
+                attrs: vec![
+                    attr("deprecated", None),
+                    attr("since", Some(Literal::Int(2)))
+                ],
+            },
+            Struct
+        );
+        assert_parse!(
+            "@rename(\"light\") Light",
+            UnitVariant {
+                name: s("Light"),
+                tag: None,
+                docs: vec![],
+                attrs: vec![attr("rename", Some(Literal::String(s("light"))))],
+            },
+            UnitVariant
+        );
+    }
+
+    #[test]
+    fn parse_import() {
+        assert_parse!(
+            "import \"common.wing\";",
+            Import {
+                path: S::new_unspanned(s("common.wing")),
+                names: None,
+            },
+            Import
+        );
+        assert_parse!(
+            "import { Point, Size } from \"../geometry.wing\"",
+            Import {
+                path: S::new_unspanned(s("../geometry.wing")),
+                names: Some(svec![s("Point"), s("Size")]),
+            },
+            Import
+        );
+    }
+
     impl EnumVariant {
         fn user_type(val: impl Into<UserType>) -> Self {
             Self::UserType(val.into())
@@ -332,31 +803,52 @@ mod test {
                 }
             ",
             Document {
+                package: None,
+                imports: vec![],
                 user_types: svec![Enum {
                     name: s("Message"),
+                    params: vec![],
+                    docs: vec![],
                     definitions: svec![
                         EnumVariant::user_type(Struct {
                             name: s("Ping"),
+                            params: vec![],
+                            docs: vec![],
                             fields: svec![
                                 StructField::new("val", Builtin::String),
                                 StructField::new("code", Builtin::U32),
-                            ]
+                            ],
+                            attrs: vec![],
                         }),
                         EnumVariant::user_type(Enum {
                             name: s("Download"),
+                            params: vec![],
+                            docs: vec![],
                             definitions: svec![
                                 EnumVariant::user_type(Struct {
                                     name: s("Covers"),
-                                    fields: vec![]
+                                    params: vec![],
+                                    docs: vec![],
+                                    fields: vec![],
+                                    attrs: vec![],
                                 }),
                                 EnumVariant::user_type(Struct {
                                     name: s("Images"),
-                                    fields: svec![StructField::new("by", Builtin::String)]
+                                    params: vec![],
+                                    docs: vec![],
+                                    fields: svec![StructField::new("by", Builtin::String)],
+                                    attrs: vec![],
                                 })
-                            ]
+                            ],
+                            attrs: vec![],
                         })
-                    ]
-                }]
+                    ],
+                    attrs: vec![],
+                }],
+                aliases: vec![],
+                services: vec![],
+                protocols: vec![],
+                foreign_types: vec![]
             }
         )
     }
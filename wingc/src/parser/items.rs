@@ -1,4 +1,4 @@
-use std::ops::Deref;
+use std::collections::HashMap;
 
 use super::*;
 
@@ -25,59 +25,215 @@ pub enum Builtin {
     Bool,
     String,
     Binary,
+    /// A file descriptor, passed over Unix sockets.
+    Fd,
 }
 #[derive(Debug, Clone, PartialEq)]
 pub enum Type {
     Builtin(Builtin),
     List(Box<Type>),
+    /// May be absent, written `T?`.
+    Optional(Box<Type>),
+    /// Maps keys to values, written `{K: V}`.
+    Map(Box<Type>, Box<Type>),
     User(String),
     UserInline(UserType),
+    /// A generic user type given its type arguments, written `Page<User>`.
+    Generic(String, Vec<Type>),
 }
 
 #[derive(Debug, Clone, PartialEq, From)]
 pub enum EnumVariant {
     NamedVariant(StructField),
     UserType(UserType),
+    Unit(UnitVariant),
+}
+
+/// A variant without data, sent as a bare string.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitVariant {
+    pub name: String,
+    /// The name it is sent as, when it differs from `name`.
+    pub tag: Option<String>,
+    pub docs: Vec<String>,
+    pub attrs: SVec<Attribute>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Enum {
     pub name: String,
+    /// The type parameters of a generic enum.
+    pub params: Vec<String>,
     pub definitions: SVec<EnumVariant>,
+    /// One entry per `///` line in front of the enum.
+    pub docs: Vec<String>,
+    pub attrs: SVec<Attribute>,
+}
+
+/// A value written in a schema, such as the default value of a field.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    String(String),
+    List(Vec<Literal>),
+    Map(Vec<(Literal, Literal)>),
+    /// A unit variant of an enum.
+    Variant(String),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct StructField {
     pub name: String,
     pub typ: Type,
+    pub docs: Vec<String>,
+    /// Used when the field is missing, written `name: T = value`.
+    pub default: Option<S<Literal>>,
+    pub attrs: SVec<Attribute>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Struct {
     pub name: String,
+    /// The type parameters of a generic struct.
+    pub params: Vec<String>,
     pub fields: SVec<StructField>,
+    pub docs: Vec<String>,
+    pub attrs: SVec<Attribute>,
+}
+
+/// A distinct type, sent as the type it wraps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Newtype {
+    pub name: String,
+    pub typ: Box<Type>,
+    pub docs: Vec<String>,
+    pub attrs: SVec<Attribute>,
+}
+
+/// Changes how a type, field or variant is sent, written `@name` or `@name(argument)` in front
+/// of it. Checked by [`crate::semantic_analyzer::analyze_schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Attribute {
+    pub name: String,
+    pub arg: Option<Literal>,
 }
 
 #[derive(Debug, Clone, From, PartialEq)]
 pub enum UserType {
     Struct(S<Struct>),
     Enum(S<Enum>),
+    Newtype(S<Newtype>),
+}
+
+/// Another name for a type, resolved by [`crate::semantic_analyzer::analyze_schema`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alias {
+    pub name: String,
+    pub typ: Type,
+    pub docs: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Method {
+    pub name: String,
+    pub input: S<Type>,
+    pub output: S<Type>,
+    /// The reply is a stream of `output` items instead of a single one.
+    pub stream: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Service {
+    pub name: String,
+    pub methods: SVec<Method>,
+}
+
+/// The messages each side of a connection is allowed to send.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Protocol {
+    pub name: String,
+    /// The enum sent from the client to the server.
+    pub client: S<String>,
+    /// The enum sent from the server to the client.
+    pub server: S<String>,
+}
+
+/// Makes the types of another file usable, written `import "file.wing"` or
+/// `import { A, B } from "file.wing"` to only use some of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    /// Relative to the importing file.
+    pub path: S<String>,
+    /// The top-level types taken from the file, or `None` for all of them.
+    pub names: Option<SVec<String>>,
+}
+
+/// The module the types of a file are generated in, written `package foo.bar;`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Package(pub Vec<String>);
+
+/// A type of another package, which is used by a document but generated along its own package.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForeignType {
+    pub package: Package,
+    pub user_type: UserType,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Document {
+    pub package: Option<S<Package>>,
+    pub imports: SVec<Import>,
     pub user_types: SVec<UserType>,
+    pub aliases: SVec<Alias>,
+    pub services: SVec<Service>,
+    pub protocols: SVec<Protocol>,
+    /// Only found in documents merged by [`crate::loader::Schema::merged`].
+    pub foreign_types: Vec<ForeignType>,
 }
 impl std::fmt::Display for Type {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Builtin(bt) => write!(f, "{}", <&str>::from(bt)),
             Self::List(inner) => write!(f, "List<{}>", inner),
+            Self::Optional(inner) => write!(f, "Optional<{}>", inner),
+            Self::Map(key, value) => write!(f, "Map<{}, {}>", key, value),
             Self::User(name) => write!(f, "{}", name),
             Self::UserInline(user) => write!(f, "{}", user.name()),
+            Self::Generic(name, args) => {
+                let args: Vec<_> = args.iter().map(ToString::to_string).collect();
+                write!(f, "{}<{}>", name, args.join(", "))
+            }
+        }
+    }
+}
+impl std::fmt::Display for Literal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let join = |items: Vec<String>| items.join(", ");
+        match self {
+            Self::Int(n) => write!(f, "{n}"),
+            Self::Float(n) => write!(f, "{n:?}"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::String(text) => write!(f, "\"{text}\""),
+            Self::List(items) => {
+                write!(f, "[{}]", join(items.iter().map(Self::to_string).collect()))
+            }
+            Self::Map(entries) => {
+                let entries = entries.iter().map(|(key, value)| format!("{key}: {value}"));
+                write!(f, "{{{}}}", join(entries.collect()))
+            }
+            Self::Variant(name) => write!(f, "{name}"),
         }
     }
 }
+
+impl std::fmt::Display for Package {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.join("."))
+    }
+}
+
 use easy_ext::ext;
 #[ext]
 impl<T> Vec<T> {
@@ -88,62 +244,274 @@ impl<T> Vec<T> {
 }
 
 impl Struct {
-    pub fn children_user_types<'a>(&'a self) -> Vec<&'a UserType> {
-        let val = self
-            .fields
+    pub fn children_user_types(&self) -> Vec<&UserType> {
+        self.fields
             .iter()
-            .flat_map(|def| match &def.typ {
-                Type::UserInline(user) => user.children_user_types(),
-                _ => vec![],
-            })
-            .collect();
-        val
+            .flat_map(|def| def.typ.inline_user_types())
+            .collect()
+    }
+    /// The keys the struct is sent with, each spanned by the field it comes from. Skipped fields
+    /// are left out, and flattened ones are replaced by the keys of the struct they hold, which
+    /// `lookup` finds by name.
+    pub fn wire_keys<'a>(&'a self, lookup: &dyn Fn(&str) -> Option<&'a UserType>) -> SVec<String> {
+        self.collect_wire_keys(lookup, &mut vec![])
+    }
+    /// `visited` holds the structs being flattened, so one holding itself isn't expanded forever.
+    fn collect_wire_keys<'a>(
+        &'a self,
+        lookup: &dyn Fn(&str) -> Option<&'a UserType>,
+        visited: &mut Vec<&'a str>,
+    ) -> SVec<String> {
+        visited.push(&self.name);
+        let mut keys = vec![];
+        for field in self.fields.iter() {
+            if field.attrs.has_attr("skip") {
+                continue;
+            }
+            let inner = match &field.typ {
+                Type::UserInline(ut) => Some(ut),
+                typ => typ.as_user().and_then(lookup),
+            };
+            match inner {
+                Some(UserType::Struct(inner))
+                    if field.attrs.has_attr("flatten") && !visited.contains(&&*inner.name) =>
+                {
+                    let inner_keys = inner.collect_wire_keys(lookup, visited);
+                    keys.extend(
+                        inner_keys
+                            .into_iter()
+                            .map(|key| field.as_ref().map(|_| key.value)),
+                    );
+                }
+                _ => keys.push(field.as_ref().map(|field| field.wire_name().to_owned())),
+            }
+        }
+        visited.pop();
+        keys
     }
 }
 
 impl UserType {
-    pub fn children_user_types<'a>(&'a self) -> Vec<&'a UserType> {
+    pub fn children_user_types(&self) -> Vec<&UserType> {
         match self {
             UserType::Enum(en) => en.children_user_types(),
             UserType::Struct(st) => st.children_user_types(),
+            UserType::Newtype(nt) => nt.typ.inline_user_types(),
         }
         .with(self)
     }
 }
 
 impl Enum {
-    pub fn children_user_types<'a>(&'a self) -> Vec<&'a UserType> {
+    pub fn children_user_types(&self) -> Vec<&UserType> {
         self.definitions
             .iter()
             .flat_map(|def| match &def.value {
                 EnumVariant::UserType(ut) => ut.children_user_types(),
-                EnumVariant::NamedVariant(StructField {
-                    typ: Type::UserInline(user),
-                    ..
-                }) => user.children_user_types(),
-                _ => vec![],
+                EnumVariant::NamedVariant(field) => field.typ.inline_user_types(),
+                EnumVariant::Unit(_) => vec![],
             })
             .collect()
     }
+    /// The variants holding data, as if they were fields. Unit variants are left out.
     pub fn variants(&self) -> impl Iterator<Item = S<StructField>> {
-        self.definitions.iter().map(|var| {
-            var.as_ref().map(|var| match var {
+        self.definitions.iter().filter_map(|var| {
+            let field = match &var.value {
                 EnumVariant::NamedVariant(f) => f.clone(),
                 EnumVariant::UserType(ut) => StructField {
                     name: ut.name().into(),
                     typ: Type::User(ut.name().into()),
+                    docs: ut.docs().to_vec(),
+                    default: None,
+                    attrs: ut.attrs().to_vec(),
                 },
-            })
+                EnumVariant::Unit(_) => return None,
+            };
+            Some(var.as_ref().map(|_| field))
+        })
+    }
+    pub fn units(&self) -> impl Iterator<Item = &UnitVariant> {
+        self.definitions.iter().filter_map(|var| match &var.value {
+            EnumVariant::Unit(unit) => Some(unit),
+            _ => None,
         })
     }
+    /// Returns `true` for C-like enums, whose variants are all units.
+    pub fn has_only_units(&self) -> bool {
+        !self.definitions.is_empty() && self.variants().next().is_none()
+    }
+}
+
+impl EnumVariant {
+    /// Variants holding a user type are documented by the type itself.
+    pub fn docs(&self) -> &[String] {
+        match self {
+            EnumVariant::NamedVariant(field) => &field.docs,
+            EnumVariant::UserType(ut) => ut.docs(),
+            EnumVariant::Unit(unit) => &unit.docs,
+        }
+    }
+    pub fn name(&self) -> &str {
+        match self {
+            EnumVariant::NamedVariant(field) => &field.name,
+            EnumVariant::UserType(ut) => ut.name(),
+            EnumVariant::Unit(unit) => &unit.name,
+        }
+    }
+    pub fn attrs(&self) -> &[S<Attribute>] {
+        match self {
+            EnumVariant::NamedVariant(field) => &field.attrs,
+            EnumVariant::UserType(ut) => ut.attrs(),
+            EnumVariant::Unit(unit) => &unit.attrs,
+        }
+    }
+    /// The name the variant is sent as.
+    pub fn tag(&self) -> &str {
+        match self {
+            EnumVariant::Unit(unit) => unit.tag(),
+            _ => self.attrs().rename().unwrap_or(self.name()),
+        }
+    }
+}
+
+impl UnitVariant {
+    /// The name the variant is sent as.
+    pub fn tag(&self) -> &str {
+        (self.tag.as_deref())
+            .or(self.attrs.rename())
+            .unwrap_or(&self.name)
+    }
+}
+
+impl StructField {
+    /// The name the field is sent as.
+    pub fn wire_name(&self) -> &str {
+        self.attrs.rename().unwrap_or(&self.name)
+    }
+}
+
+#[ext(Attributes)]
+pub impl [S<Attribute>] {
+    fn attr(&self, name: &str) -> Option<&Attribute> {
+        self.iter()
+            .map(|attr| &attr.value)
+            .find(|attr| attr.name == name)
+    }
+    fn has_attr(&self, name: &str) -> bool {
+        self.attr(name).is_some()
+    }
+    /// The name given by `@rename("name")`.
+    fn rename(&self) -> Option<&str> {
+        match self.attr("rename")?.arg.as_ref()? {
+            Literal::String(name) => Some(name),
+            _ => None,
+        }
+    }
+    /// Whether the item is `@deprecated`, along with the reason, if one was given.
+    fn deprecated(&self) -> Option<Option<&str>> {
+        let attr = self.attr("deprecated")?;
+        Some(match &attr.arg {
+            Some(Literal::String(reason)) => Some(reason),
+            _ => None,
+        })
+    }
+    /// The version given by `@since(version)`, in which the item was added.
+    ///
+    /// Unlike `@deprecated`, it only ends up in the docs of the generated code, in every language.
+    fn since(&self) -> Option<String> {
+        match self.attr("since")?.arg.as_ref()? {
+            Literal::String(version) => Some(version.clone()),
+            version => Some(version.to_string()),
+        }
+    }
 }
 
 impl Type {
+    /// Whether the type can be the key of a map. Keys are sent as JSON object keys, which only
+    /// strings and integers can be turned into, so besides those only newtypes holding one and
+    /// enums without data can be keys. `lookup` finds user types by name.
+    pub fn is_map_key<'a>(&self, lookup: &dyn Fn(&str) -> Option<&'a UserType>) -> bool {
+        self.is_map_key_within(lookup, &mut vec![])
+    }
+    /// `visited` holds the newtypes being looked through, so one holding itself isn't followed
+    /// forever.
+    fn is_map_key_within<'a>(
+        &self,
+        lookup: &dyn Fn(&str) -> Option<&'a UserType>,
+        visited: &mut Vec<&'a str>,
+    ) -> bool {
+        match self {
+            Type::Builtin(builtin) => !matches!(
+                builtin,
+                Builtin::F32 | Builtin::F64 | Builtin::Bool | Builtin::Binary | Builtin::Fd
+            ),
+            Type::User(name) if !visited.contains(&name.as_str()) => match lookup(name) {
+                Some(UserType::Newtype(nt)) => {
+                    visited.push(&nt.name);
+                    let is_key = nt.typ.is_map_key_within(lookup, visited);
+                    visited.pop();
+                    is_key
+                }
+                Some(UserType::Enum(en)) => {
+                    (en.definitions.iter()).all(|var| matches!(var.value, EnumVariant::Unit(_)))
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+    /// This type and every type it is made of, outermost first.
+    pub fn nested_types(&self) -> Vec<&Type> {
+        let mut types = vec![self];
+        match self {
+            Type::List(inner) | Type::Optional(inner) => types.extend(inner.nested_types()),
+            Type::Map(key, value) => {
+                types.extend(key.nested_types());
+                types.extend(value.nested_types());
+            }
+            Type::Generic(_, args) => types.extend(args.iter().flat_map(Type::nested_types)),
+            _ => {}
+        }
+        types
+    }
+    /// The user types declared inside of this type, and their children.
+    fn inline_user_types(&self) -> Vec<&UserType> {
+        self.nested_types()
+            .into_iter()
+            .flat_map(|typ| match typ {
+                Type::UserInline(user) => user.children_user_types(),
+                _ => vec![],
+            })
+            .collect()
+    }
+    /// Replaces each user type named after a key of `types` by its value, such as aliases by the
+    /// types they stand for.
+    pub fn substitute(&mut self, types: &HashMap<String, Type>) {
+        match self {
+            Type::User(name) => {
+                if let Some(typ) = types.get(name) {
+                    *self = typ.clone();
+                }
+            }
+            Type::List(inner) | Type::Optional(inner) => inner.substitute(types),
+            Type::Map(key, value) => {
+                key.substitute(types);
+                value.substitute(types);
+            }
+            Type::Generic(_, args) => {
+                for arg in args {
+                    arg.substitute(types);
+                }
+            }
+            Type::UserInline(ut) => ut.substitute(types),
+            Type::Builtin(_) => {}
+        }
+    }
+    /// The name of the user type this refers to, without its type arguments.
     pub fn as_user(&self) -> Option<&str> {
-        if let Type::User(tp) = self {
-            Some(tp)
-        } else {
-            None
+        match self {
+            Type::User(tp) | Type::Generic(tp, _) => Some(tp),
+            _ => None,
         }
     }
 }
@@ -152,12 +520,83 @@ impl UserType {
         match self {
             Self::Struct(st) => &st.name,
             Self::Enum(en) => &en.name,
+            Self::Newtype(nt) => &nt.name,
+        }
+    }
+    /// The name of the type, spanned by its whole definition.
+    pub fn spanned_name(&self) -> S<String> {
+        match self {
+            Self::Struct(st) => st.as_ref().map(|st| st.name.clone()),
+            Self::Enum(en) => en.as_ref().map(|en| en.name.clone()),
+            Self::Newtype(nt) => nt.as_ref().map(|nt| nt.name.clone()),
         }
     }
-    pub fn children_types<'a>(&'a self) -> impl Iterator<Item = S<Type>> {
+    /// See [`Type::substitute`].
+    pub fn substitute(&mut self, types: &HashMap<String, Type>) {
+        match self {
+            UserType::Struct(st) => {
+                for field in st.fields.iter_mut() {
+                    field.typ.substitute(types);
+                }
+            }
+            UserType::Enum(en) => {
+                for var in en.definitions.iter_mut() {
+                    match &mut var.value {
+                        EnumVariant::NamedVariant(field) => field.typ.substitute(types),
+                        EnumVariant::UserType(ut) => ut.substitute(types),
+                        EnumVariant::Unit(_) => {}
+                    }
+                }
+            }
+            UserType::Newtype(nt) => nt.typ.substitute(types),
+        }
+    }
+    /// The type given `args` for its type parameters.
+    pub fn instantiate(&self, args: &[Type]) -> UserType {
+        let types = (self.params().iter().cloned())
+            .zip(args.iter().cloned())
+            .collect();
+        let mut ut = self.clone();
+        ut.substitute(&types);
+        ut
+    }
+    /// The type parameters of a generic type, empty for other types.
+    pub fn params(&self) -> &[String] {
+        match self {
+            Self::Struct(st) => &st.params,
+            Self::Enum(en) => &en.params,
+            Self::Newtype(_) => &[],
+        }
+    }
+    pub fn docs(&self) -> &[String] {
+        match self {
+            Self::Struct(st) => &st.docs,
+            Self::Enum(en) => &en.docs,
+            Self::Newtype(nt) => &nt.docs,
+        }
+    }
+    pub fn attrs(&self) -> &[S<Attribute>] {
+        match self {
+            Self::Struct(st) => &st.attrs,
+            Self::Enum(en) => &en.attrs,
+            Self::Newtype(nt) => &nt.attrs,
+        }
+    }
+    /// The name the type is sent as, without its package.
+    pub fn wire_name(&self) -> &str {
+        self.attrs().rename().unwrap_or(self.name())
+    }
+    pub fn children_types(&self) -> impl Iterator<Item = S<Type>> {
         let iter: Box<dyn Iterator<Item = S<StructField>>> = match self {
             Self::Struct(st) => Box::new(st.fields.iter().cloned()),
             Self::Enum(en) => Box::new(en.variants()),
+            Self::Newtype(nt) => Box::new(std::iter::once(nt.as_ref().map(|nt| StructField {
+                name: nt.name.clone(),
+                typ: (*nt.typ).clone(),
+                docs: vec![],
+                default: None,
+                attrs: vec![],
+            }))),
         };
         iter.map(|fd| fd.as_ref().map(|fd| fd.typ.clone()))
     }
@@ -165,6 +604,7 @@ impl UserType {
         match self {
             UserType::Struct(st) => st.fields.is_empty(),
             UserType::Enum(en) => en.definitions.is_empty(),
+            UserType::Newtype(_) => false,
         }
     }
 }
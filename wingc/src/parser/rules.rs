@@ -1,5 +1,6 @@
 use super::*;
 use macro_rules_attribute::apply;
+use miette::IntoDiagnostic;
 
 macro_rules! impl_parse_composite {
     (
@@ -17,7 +18,7 @@ macro_rules! impl_parse_composite {
     };
 }
 
-fn match_rule<'i, 't>(rule: Rule, pair: &Pair<'i, Rule>) -> miette::Result<()> {
+fn match_rule<'i>(rule: Rule, pair: &Pair<'i, Rule>) -> miette::Result<()> {
     if rule == pair.as_rule() {
         Ok(())
     } else {
@@ -27,10 +28,62 @@ fn match_rule<'i, 't>(rule: Rule, pair: &Pair<'i, Rule>) -> miette::Result<()> {
         )
     }
 }
+/// Takes the `///` comments in front of an item, without their prefix.
+fn parse_docs(pairs: &mut Pairs<Rule>) -> Vec<String> {
+    let mut docs = vec![];
+    while pairs
+        .peek()
+        .is_some_and(|pair| pair.as_rule() == Rule::doc_comment)
+    {
+        let text = pairs.next2().into_inner().next2().as_str();
+        docs.push(text.strip_prefix(' ').unwrap_or(text).trim_end().to_owned());
+    }
+    docs
+}
+
+/// Takes the `@attributes` in front of an item, which come after its docs.
+fn parse_attrs(pairs: &mut Pairs<Rule>) -> miette::Result<SVec<Attribute>> {
+    let mut attrs = vec![];
+    while pairs
+        .peek()
+        .is_some_and(|pair| pair.as_rule() == Rule::attribute)
+    {
+        attrs.push(pairs.next_item()?);
+    }
+    Ok(attrs)
+}
+
+/// Takes the type parameters of a generic type, if there are any.
+fn parse_params(pairs: &mut Pairs<Rule>) -> miette::Result<Vec<String>> {
+    match pairs.peek() {
+        Some(pair) if pair.as_rule() == Rule::type_params => {
+            pairs.next2().into_inner().collect_items()
+        }
+        _ => Ok(vec![]),
+    }
+}
+
+/// Takes the text between the quotes of a string, with its escapes replaced.
+fn parse_string(pair: Pair<Rule>) -> String {
+    let mut text = String::new();
+    let mut chars = pair.into_inner().next2().as_str().chars();
+    while let Some(c) = chars.next() {
+        // The grammar only lets through `\"`, `\\` and `\n`.
+        text.push(match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                escaped => escaped.unwrap(),
+            },
+            c => c,
+        });
+    }
+    text
+}
+
 pub trait ParseItem: Sized {
     const RULE: Rule;
     fn parse<'i>(pair: Pair<'i, Rule>) -> miette::Result<Self>;
-    fn match_rule<'i, 't>(pair: &Pair<'i, Rule>) -> miette::Result<()> {
+    fn match_rule<'i>(pair: &Pair<'i, Rule>) -> miette::Result<()> {
         match_rule(Self::RULE, pair)
     }
 }
@@ -47,12 +100,18 @@ impl ParseItem for String {
 #[rule(r#type)]
 fn parse(pairs: Pairs<Rule>) -> Type {
     let tk = pairs.peek().unwrap();
-    if tk.as_rule() == Rule::r#type {
+    let typ = if tk.as_rule() == Rule::r#type {
         pairs.next_item::<Self>()?
     } else if tk.as_rule() == Rule::list_type {
         Self::List(Box::new(pairs.next_item()?))
+    } else if tk.as_rule() == Rule::map_type {
+        let mut inner = pairs.next2().into_inner();
+        Self::Map(Box::new(inner.next_item()?), Box::new(inner.next_item()?))
     } else if tk.as_rule() == Rule::user_type {
         Self::UserInline(pairs.next_item()?)
+    } else if tk.as_rule() == Rule::generic_type {
+        let mut inner = pairs.next2().into_inner();
+        Self::Generic(inner.next_item()?, inner.collect_items()?)
     } else {
         let tname = pairs.next2().as_str();
         tname
@@ -60,6 +119,14 @@ fn parse(pairs: Pairs<Rule>) -> Type {
             .parse::<Builtin>()
             .map(Type::Builtin)
             .unwrap_or_else(|_| Type::User(tname.to_owned()))
+    };
+    if pairs
+        .peek()
+        .is_some_and(|tk| tk.as_rule() == Rule::optional)
+    {
+        Self::Optional(Box::new(typ))
+    } else {
+        typ
     }
 }
 
@@ -67,9 +134,49 @@ fn parse(pairs: Pairs<Rule>) -> Type {
 #[ignore(spantree)]
 #[rule(struct_field)]
 fn parse(pairs: Pairs<Rule>) -> StructField {
+    let docs = parse_docs(&mut pairs);
+    let attrs = parse_attrs(&mut pairs)?;
     StructField {
         name: pairs.next_item()?,
         typ: pairs.next_item()?,
+        docs,
+        default: pairs.next().map(ParseItem::parse).transpose()?,
+        attrs,
+    }
+}
+
+#[apply(impl_parse_composite)]
+#[rule(attribute)]
+fn parse(pairs: Pairs<Rule>) -> Attribute {
+    Attribute {
+        name: pairs.next_item()?,
+        arg: pairs.next().map(ParseItem::parse).transpose()?,
+    }
+}
+
+#[apply(impl_parse_composite)]
+#[rule(literal)]
+fn parse(pairs: Pairs<Rule>) -> Literal {
+    let inner = pairs.next2();
+    match inner.as_rule() {
+        Rule::number => {
+            let text = inner.as_str();
+            match text.parse() {
+                Ok(n) => Literal::Int(n),
+                Err(_) => Literal::Float(text.parse().into_diagnostic()?),
+            }
+        }
+        Rule::boolean => Literal::Bool(inner.as_str() == "true"),
+        Rule::string => Literal::String(parse_string(inner)),
+        Rule::list_literal => Literal::List(inner.into_inner().collect_items()?),
+        Rule::map_literal => {
+            let entries = inner.into_inner().map(|entry| {
+                let mut pairs = entry.into_inner();
+                Ok((pairs.next_item()?, pairs.next_item()?))
+            });
+            Literal::Map(entries.collect::<miette::Result<_>>()?)
+        }
+        _ => Literal::Variant(inner.as_str().to_owned()),
     }
 }
 #[apply(impl_parse_composite)]
@@ -81,16 +188,68 @@ fn parse(pairs: Pairs<Rule>) -> SVec<StructField> {
 #[apply(impl_parse_composite)]
 #[rule(r#struct)]
 fn parse(pairs: Pairs<Rule>) -> Struct {
+    let docs = parse_docs(&mut pairs);
+    let attrs = parse_attrs(&mut pairs)?;
     Struct {
         name: pairs.next_item()?,
+        params: parse_params(&mut pairs)?,
         fields: pairs.next_item()?,
+        docs,
+        attrs,
     }
 }
 #[apply(impl_parse_composite)]
 #[rule(document)]
 fn parse(pairs: Pairs<Rule>) -> Document {
-    Document {
-        user_types: pairs.collect_items()?,
+    let mut document = Document {
+        package: None,
+        imports: vec![],
+        user_types: vec![],
+        aliases: vec![],
+        services: vec![],
+        protocols: vec![],
+        foreign_types: vec![],
+    };
+    for pair in &mut pairs {
+        if pair.as_rule() == Rule::package {
+            document.package = Some(ParseItem::parse(pair)?);
+        } else if pair.as_rule() == Rule::import {
+            document.imports.push(ParseItem::parse(pair)?);
+        } else if pair.as_rule() == Rule::service {
+            document.services.push(ParseItem::parse(pair)?);
+        } else if pair.as_rule() == Rule::protocol {
+            document.protocols.push(ParseItem::parse(pair)?);
+        } else if pair.as_rule() == Rule::alias {
+            document.aliases.push(ParseItem::parse(pair)?);
+        } else {
+            document.user_types.push(ParseItem::parse(pair)?);
+        }
+    }
+    document
+}
+
+#[apply(impl_parse_composite)]
+#[rule(package)]
+fn parse(pairs: Pairs<Rule>) -> Package {
+    parse_docs(&mut pairs);
+    Package(pairs.collect_items()?)
+}
+
+#[apply(impl_parse_composite)]
+#[rule(import)]
+fn parse(pairs: Pairs<Rule>) -> Import {
+    parse_docs(&mut pairs);
+    let names = match pairs.peek().unwrap().as_rule() {
+        Rule::import_names => Some(pairs.next2().into_inner().collect_items()?),
+        _ => None,
+    };
+    let path = pairs.next2();
+    Import {
+        path: S {
+            span: path.as_span().into(),
+            value: parse_string(path),
+        },
+        names,
     }
 }
 
@@ -100,11 +259,26 @@ fn parse(pairs: Pairs<Rule>) -> EnumVariant {
     let inner = pairs.next2();
     if inner.as_rule() == Rule::struct_field {
         EnumVariant::NamedVariant(ParseItem::parse(inner)?)
+    } else if inner.as_rule() == Rule::unit_variant {
+        EnumVariant::Unit(ParseItem::parse(inner)?)
     } else {
         EnumVariant::UserType(ParseItem::parse(inner)?)
     }
 }
 
+#[apply(impl_parse_composite)]
+#[rule(unit_variant)]
+fn parse(pairs: Pairs<Rule>) -> UnitVariant {
+    let docs = parse_docs(&mut pairs);
+    let attrs = parse_attrs(&mut pairs)?;
+    UnitVariant {
+        name: pairs.next_item()?,
+        tag: pairs.next().map(parse_string),
+        docs,
+        attrs,
+    }
+}
+
 #[apply(impl_parse_composite)]
 #[rule(enum_body)]
 fn parse(pairs: Pairs<Rule>) -> SVec<EnumVariant> {
@@ -114,9 +288,14 @@ fn parse(pairs: Pairs<Rule>) -> SVec<EnumVariant> {
 #[apply(impl_parse_composite)]
 #[rule(r#enum)]
 fn parse(pairs: Pairs<Rule>) -> Enum {
+    let docs = parse_docs(&mut pairs);
+    let attrs = parse_attrs(&mut pairs)?;
     Enum {
         name: pairs.next_item()?,
+        params: parse_params(&mut pairs)?,
         definitions: pairs.next_item()?,
+        docs,
+        attrs,
     }
 }
 
@@ -127,7 +306,86 @@ fn parse(pairs: Pairs<Rule>) -> UserType {
     let inner = pairs.next2();
     if inner.as_rule() == Rule::r#struct {
         UserType::Struct(ParseItem::parse(inner)?)
+    } else if inner.as_rule() == Rule::newtype {
+        UserType::Newtype(ParseItem::parse(inner)?)
     } else {
         UserType::Enum(ParseItem::parse(inner)?)
     }
 }
+
+#[apply(impl_parse_composite)]
+#[rule(newtype)]
+fn parse(pairs: Pairs<Rule>) -> Newtype {
+    let docs = parse_docs(&mut pairs);
+    let attrs = parse_attrs(&mut pairs)?;
+    Newtype {
+        name: pairs.next_item()?,
+        typ: Box::new(pairs.next_item()?),
+        docs,
+        attrs,
+    }
+}
+
+#[apply(impl_parse_composite)]
+#[rule(alias)]
+fn parse(pairs: Pairs<Rule>) -> Alias {
+    let docs = parse_docs(&mut pairs);
+    Alias {
+        name: pairs.next_item()?,
+        typ: pairs.next_item()?,
+        docs,
+    }
+}
+
+#[apply(impl_parse_composite)]
+#[rule(method)]
+fn parse(pairs: Pairs<Rule>) -> Method {
+    parse_docs(&mut pairs);
+    let name = pairs.next_item()?;
+    let input = pairs.next_item()?;
+    let stream = pairs.peek().unwrap().as_rule() == Rule::stream;
+    if stream {
+        pairs.next2();
+    }
+    Method {
+        name,
+        input,
+        output: pairs.next_item()?,
+        stream,
+    }
+}
+
+#[apply(impl_parse_composite)]
+#[rule(service)]
+fn parse(pairs: Pairs<Rule>) -> Service {
+    parse_docs(&mut pairs);
+    Service {
+        name: pairs.next_item()?,
+        methods: pairs.collect_items()?,
+    }
+}
+
+#[apply(impl_parse_composite)]
+#[rule(protocol)]
+fn parse(pairs: Pairs<Rule>) -> Protocol {
+    parse_docs(&mut pairs);
+    let name = pairs.next_item()?;
+    let (mut client, mut server) = (None, None);
+    for side in pairs {
+        let rule = side.as_rule();
+        let mut inner = side.into_inner();
+        parse_docs(&mut inner);
+        let typ = inner.next_item()?;
+        if rule == Rule::client_side {
+            client = Some(typ);
+        } else {
+            server = Some(typ);
+        }
+    }
+    // The grammar requires exactly one of each.
+    Protocol {
+        name,
+        client: client.unwrap(),
+        server: server.unwrap(),
+    }
+}
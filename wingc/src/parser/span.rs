@@ -90,7 +90,7 @@ impl<T> Spanned<T> {
             span: self.span,
         }
     }
-    pub fn as_ref<'a>(&'a self) -> Spanned<&'a T> {
+    pub fn as_ref(&self) -> Spanned<&T> {
         Spanned {
             span: self.span,
             value: &self.value,
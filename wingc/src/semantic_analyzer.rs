@@ -1,10 +1,16 @@
-use std::{collections::HashSet, iter::once};
+use std::collections::{HashMap, HashSet};
 
 use miette::{Diagnostic, LabeledSpan};
 use nucleo_matcher::{Matcher, pattern::Atom};
 use thiserror::Error;
 
-use crate::parser::{Document, S, Type};
+use crate::{
+    loader::{Schema, SourceFile},
+    parser::{
+        Alias, Attribute, Attributes, Builtin, Document, EnumVariant, Literal, S, StructField,
+        Type, UserType,
+    },
+};
 
 type R = miette::Result<()>;
 
@@ -25,7 +31,77 @@ pub enum Error {
         suggestion: Option<String>,
     },
     #[error("Repeated variant '{variant}' in enum '{parent}'.")]
-    RepeatedEnumVariant { variant: S<Type>, parent: S<String> },
+    RepeatedEnumVariant {
+        variant: S<String>,
+        parent: S<String>,
+    },
+    #[error("Request '{request}' is taken by more than one method of service '{parent}'.")]
+    RepeatedRequest {
+        request: S<String>,
+        parent: S<String>,
+    },
+    #[error("Repeated method '{method}' in service '{parent}'.")]
+    RepeatedMethod {
+        method: S<String>,
+        parent: S<String>,
+    },
+    #[error("Protocol '{protocol}' uses '{name}', which is not a top-level enum.")]
+    NotAnEnum {
+        name: S<String>,
+        protocol: S<String>,
+    },
+    #[error("Type '{name}' is defined more than once.")]
+    RepeatedType { name: S<String> },
+    #[error("Type '{name}' is already defined in '{path}'.")]
+    AlreadyDefined { name: S<String>, path: String },
+    #[error("Type '{name}' is not defined at the top level of '{path}'.")]
+    UndefinedImport { name: S<String>, path: String },
+    #[error(
+        "Map keys must be strings, integers, newtypes of those or enums without data, but '{key}' was used."
+    )]
+    InvalidMapKey { key: S<String> },
+    #[error("Default value '{value}' is not a valid '{typ}'.")]
+    InvalidDefault { value: S<String>, typ: String },
+    #[error("Variant '{variant}' can't have a default value, only struct fields can.")]
+    DefaultInEnum { variant: S<String> },
+    #[error("Type alias '{name}' refers to itself.")]
+    AliasCycle { name: S<String> },
+    #[error("'{name}' can't be sent on its own, only structs and enums can.")]
+    NotAMessage { name: S<String> },
+    #[error("Type '{name}' takes {expected} type arguments, but {found} were given.")]
+    WrongTypeArguments {
+        name: S<String>,
+        expected: usize,
+        found: usize,
+    },
+    #[error("Unknown attribute '@{name}'.")]
+    UnknownAttribute {
+        name: S<String>,
+        suggestion: Option<String>,
+    },
+    #[error("Attribute '@{name}' takes {expected}.")]
+    InvalidAttributeArgument {
+        name: S<String>,
+        expected: &'static str,
+    },
+    #[error("Attribute '@{name}' can't be used on {place}.")]
+    MisplacedAttribute {
+        name: S<String>,
+        place: &'static str,
+    },
+    #[error("Attribute '@{name}' is given more than once.")]
+    RepeatedAttribute { name: S<String> },
+    #[error("Field '{field}' is '@{attr}', so it needs a default value or an optional type.")]
+    MissingDefault {
+        field: S<String>,
+        attr: &'static str,
+    },
+    #[error(
+        "Field '{field}' can't be flattened, only fields holding a struct without a default value can."
+    )]
+    InvalidFlatten { field: S<String> },
+    #[error("Key '{key}' is sent more than once by struct '{parent}'.")]
+    RepeatedKey { key: S<String>, parent: S<String> },
     #[error("Many errors where found.\n{}", join_errors(.0))]
     MultipleErrors(Errors),
 }
@@ -54,6 +130,57 @@ impl Diagnostic for Error {
                 variant.as_ref().map(|_| "Here".into()),
                 parent.as_ref().map(|_| "In this enum".into()),
             ]),
+            Self::RepeatedRequest { request, parent } => spanned_labels([
+                request.as_ref().map(|_| "Here".into()),
+                parent.as_ref().map(|_| "In this service".into()),
+            ]),
+            Self::RepeatedMethod { method, parent } => spanned_labels([
+                method.as_ref().map(|_| "Here".into()),
+                parent.as_ref().map(|_| "In this service".into()),
+            ]),
+            Self::UndefinedImport { name, .. } => {
+                spanned_labels([name.as_ref().map(|_| "Imported here".into())])
+            }
+            Self::RepeatedType { name } | Self::AlreadyDefined { name, .. } => {
+                spanned_labels([name.as_ref().map(|_| "Defined again here".into())])
+            }
+            Self::InvalidDefault { value, .. } => {
+                spanned_labels([value.as_ref().map(|_| "Here".into())])
+            }
+            Self::UnknownAttribute { name, suggestion } => {
+                let label = match suggestion {
+                    Some(sugg) => format!("Did you mean '@{sugg}'?"),
+                    None => "Here".into(),
+                };
+                spanned_labels([name.as_ref().map(|_| label)])
+            }
+            Self::InvalidAttributeArgument { name, .. }
+            | Self::MisplacedAttribute { name, .. }
+            | Self::RepeatedAttribute { name } => {
+                spanned_labels([name.as_ref().map(|_| "Here".into())])
+            }
+            Self::MissingDefault { field, .. } | Self::InvalidFlatten { field } => {
+                spanned_labels([field.as_ref().map(|_| "Here".into())])
+            }
+            Self::RepeatedKey { key, parent } => spanned_labels([
+                key.as_ref().map(|_| "Here".into()),
+                parent.as_ref().map(|_| "In this struct".into()),
+            ]),
+            Self::DefaultInEnum { variant } => {
+                spanned_labels([variant.as_ref().map(|_| "Here".into())])
+            }
+            Self::AliasCycle { name }
+            | Self::NotAMessage { name }
+            | Self::WrongTypeArguments { name, .. } => {
+                spanned_labels([name.as_ref().map(|_| "Here".into())])
+            }
+            Self::InvalidMapKey { key } => {
+                spanned_labels([key.as_ref().map(|_| "In this map".into())])
+            }
+            Self::NotAnEnum { name, protocol } => spanned_labels([
+                name.as_ref().map(|_| "Here".into()),
+                protocol.as_ref().map(|_| "In this protocol".into()),
+            ]),
             _ => None,
         }
     }
@@ -69,7 +196,7 @@ impl Error {}
 
 fn fuzzy_match<'a>(
     candidates_iter: impl Clone + Iterator<Item = &'a str>,
-    mut matcher: &mut Matcher,
+    matcher: &mut Matcher,
     search: &str,
 ) -> Option<&'a str> {
     let candidates = candidates_iter.clone().collect::<Vec<_>>();
@@ -85,7 +212,7 @@ fn fuzzy_match<'a>(
         .collect::<Vec<_>>();
     if !candidates.is_empty() {
         candidates.sort_by(|(_, val), (_, val2)| val.total_cmp(val2));
-        candidates.get(0).map(|(c, _)| *c)
+        candidates.first().map(|(c, _)| *c)
     } else {
         Atom::new(
             search,
@@ -94,30 +221,58 @@ fn fuzzy_match<'a>(
             nucleo_matcher::pattern::AtomKind::Fuzzy,
             false,
         )
-        .match_list(candidates_iter, &mut matcher)
-        .get(0)
+        .match_list(candidates_iter, matcher)
+        .first()
         .map(|(original, _)| *original)
     }
 }
 
-fn analyze_missing_types(document: &Document) -> Errors {
-    let known_types: HashSet<_> = document
-        .user_types
-        .iter()
+fn analyze_missing_types(document: &Document, imported: &[&S<UserType>]) -> Errors {
+    let known_types: HashSet<_> = (document.user_types.iter().chain(imported.iter().copied()))
         .flat_map(|ut| ut.children_user_types())
-        .chain(document.user_types.iter().map(|ut| &ut.value))
         .map(|ut| ut.name())
         .collect();
 
     let mut matcher = Matcher::default();
+    let method_types = document
+        .services
+        .iter()
+        .flat_map(|sv| sv.methods.iter())
+        .flat_map(|method| [method.input.clone(), method.output.clone()]);
+    let alias_types = (document.aliases.iter()).map(|alias| alias.as_ref().map(|a| a.typ.clone()));
+    let used_names = |tp: S<Type>, params: &[String]| {
+        let names: Vec<_> = (tp.nested_types().into_iter())
+            .filter_map(|typ| typ.as_user())
+            // Type parameters can only be used by the type declaring them.
+            .filter(|name| !params.iter().any(|param| param == name))
+            .map(|name| tp.as_ref().map(|_| name.to_owned()))
+            .collect();
+        names
+    };
+    let protocol_types = document
+        .protocols
+        .iter()
+        .flat_map(|pr| [pr.client.clone(), pr.server.clone()]);
     document
         .user_types
         .iter()
+        .flat_map(|ut| ut.children_user_types())
         .flat_map(|ut| {
+            let params = ut.params();
             ut.children_types()
-                .filter_map(|tp| tp.map(|h| h.as_user().map(|h| h.to_owned())).transpose())
-                .filter(|name| !known_types.contains(name.value.as_str()))
+                .flat_map(move |tp| used_names(tp, params))
+        })
+        .chain(alias_types.flat_map(|tp| used_names(tp, &[])))
+        // Aliases that are still used refer to themselves, which is reported on its own.
+        .filter(|name| {
+            !document
+                .aliases
+                .iter()
+                .any(|alias| alias.name == name.value)
         })
+        .chain(method_types.flat_map(|tp| used_names(tp, &[])))
+        .chain(protocol_types)
+        .filter(|name| !known_types.contains(name.value.as_str()))
         .map(|missing| Error::UndefinedType {
             suggestion: fuzzy_match(
                 known_types.iter().copied(),
@@ -130,13 +285,821 @@ fn analyze_missing_types(document: &Document) -> Errors {
         .collect()
 }
 
-pub fn analyze_errors(document: &Document) -> R {
-    let mut errs = analyze_missing_types(document);
+/// Types are emitted side by side, so no two types may share a name, even nested ones.
+fn analyze_repeated_types(document: &Document) -> Errors {
+    let mut errs = Errors::new();
+    let mut names = HashSet::new();
+    for ut in document
+        .user_types
+        .iter()
+        .flat_map(|ut| ut.children_user_types())
+    {
+        if !names.insert(ut.name()) {
+            errs.push(Error::RepeatedType {
+                name: ut.spanned_name(),
+            });
+        }
+    }
+    errs
+}
+
+/// Calls are dispatched by the name of their request, so each method of a service needs its own.
+/// Methods are emitted side by side, so their names must be unique as well.
+fn analyze_repeated_requests(document: &Document) -> Errors {
+    let mut errs = Errors::new();
+    for service in document.services.iter() {
+        let mut requests = HashSet::new();
+        let mut names = HashSet::new();
+        for method in service.methods.iter() {
+            if !names.insert(method.name.as_str()) {
+                errs.push(Error::RepeatedMethod {
+                    method: method.as_ref().map(|method| method.name.clone()),
+                    parent: service.as_ref().map(|sv| sv.name.clone()),
+                });
+            }
+            if !requests.insert(method.input.to_string()) {
+                errs.push(Error::RepeatedRequest {
+                    request: method.input.as_ref().map(ToString::to_string),
+                    parent: service.as_ref().map(|sv| sv.name.clone()),
+                });
+            }
+        }
+    }
+    errs
+}
+
+/// Variants are told apart by their tag, so no two variants of an enum may share one. Nor may they
+/// share a name, which they are emitted with.
+fn analyze_repeated_variants(document: &Document) -> Errors {
+    let mut errs = Errors::new();
+    for ut in document
+        .user_types
+        .iter()
+        .flat_map(|ut| ut.children_user_types())
+    {
+        let UserType::Enum(en) = ut else {
+            continue;
+        };
+        let mut names = HashSet::new();
+        let mut tags = HashSet::new();
+        for var in en.definitions.iter() {
+            let repeated = if !names.insert(var.name()) {
+                var.name()
+            } else if !tags.insert(var.tag()) {
+                var.tag()
+            } else {
+                continue;
+            };
+            errs.push(Error::RepeatedEnumVariant {
+                variant: var.as_ref().map(|_| repeated.to_owned()),
+                parent: en.as_ref().map(|en| en.name.clone()),
+            });
+        }
+    }
+    errs
+}
+
+/// See [`Type::is_map_key`].
+fn analyze_map_keys(document: &Document, imported: &[&S<UserType>]) -> Errors {
+    let user_types: HashMap<_, _> = (document.user_types.iter().chain(imported.iter().copied()))
+        .flat_map(|ut| ut.children_user_types())
+        .map(|ut| (ut.name(), ut))
+        .collect();
+    let lookup = |name: &str| user_types.get(name).copied();
+    let mut errs = Errors::new();
+    for ut in document
+        .user_types
+        .iter()
+        .flat_map(|ut| ut.children_user_types())
+    {
+        for tp in ut.children_types() {
+            for typ in tp.nested_types() {
+                if let Type::Map(key, _) = typ
+                    && !key.is_map_key(&lookup)
+                {
+                    errs.push(Error::InvalidMapKey {
+                        key: tp.as_ref().map(|_| key.to_string()),
+                    });
+                }
+            }
+        }
+    }
+    errs
+}
+
+/// Returns `true` if `value` can be used as a `typ`, whose user types are found in `user_types`.
+fn literal_fits(value: &Literal, typ: &Type, user_types: &HashMap<&str, &UserType>) -> bool {
+    let in_range =
+        |min: i128, max: i128| matches!(value, Literal::Int(n) if (min..=max).contains(n));
+    match typ {
+        Type::Builtin(builtin) => match builtin {
+            Builtin::U8 => in_range(0, u8::MAX.into()),
+            Builtin::U16 => in_range(0, u16::MAX.into()),
+            Builtin::U32 | Builtin::UInt => in_range(0, u32::MAX.into()),
+            Builtin::U64 | Builtin::USize => in_range(0, u64::MAX.into()),
+            Builtin::I8 => in_range(i8::MIN.into(), i8::MAX.into()),
+            Builtin::I16 => in_range(i16::MIN.into(), i16::MAX.into()),
+            Builtin::I32 | Builtin::Int => in_range(i32::MIN.into(), i32::MAX.into()),
+            Builtin::I64 | Builtin::ISize => in_range(i64::MIN.into(), i64::MAX.into()),
+            Builtin::F32 | Builtin::F64 => matches!(value, Literal::Int(_) | Literal::Float(_)),
+            Builtin::Bool => matches!(value, Literal::Bool(_)),
+            Builtin::String => matches!(value, Literal::String(_)),
+            Builtin::Binary => literal_fits(
+                value,
+                &Type::List(Box::new(Type::Builtin(Builtin::U8))),
+                user_types,
+            ),
+            // Descriptors only exist once they are sent.
+            Builtin::Fd => false,
+        },
+        Type::List(inner) => match value {
+            Literal::List(items) => items
+                .iter()
+                .all(|item| literal_fits(item, inner, user_types)),
+            _ => false,
+        },
+        Type::Map(key, inner) => match value {
+            Literal::Map(entries) => entries.iter().all(|(k, v)| {
+                literal_fits(k, key, user_types) && literal_fits(v, inner, user_types)
+            }),
+            _ => false,
+        },
+        Type::Optional(inner) => literal_fits(value, inner, user_types),
+        Type::User(name) | Type::Generic(name, _) => user_types
+            .get(name.as_str())
+            .is_some_and(|ut| user_fits(value, ut, user_types)),
+        Type::UserInline(ut) => user_fits(value, ut, user_types),
+    }
+}
+
+/// Only unit variants of enums and values of the type wrapped by a newtype can be written.
+fn user_fits(value: &Literal, ut: &UserType, user_types: &HashMap<&str, &UserType>) -> bool {
+    match (value, ut) {
+        (Literal::Variant(name), UserType::Enum(en)) => en.units().any(|unit| &unit.name == name),
+        (_, UserType::Newtype(nt)) => literal_fits(value, &nt.typ, user_types),
+        _ => false,
+    }
+}
+
+fn analyze_defaults(document: &Document, imported: &[&S<UserType>]) -> Errors {
+    let user_types: HashMap<_, _> = (document.user_types.iter().chain(imported.iter().copied()))
+        .flat_map(|ut| ut.children_user_types())
+        .map(|ut| (ut.name(), ut))
+        .collect();
+    let mut errs = Errors::new();
+    for ut in document
+        .user_types
+        .iter()
+        .flat_map(|ut| ut.children_user_types())
+    {
+        match ut {
+            UserType::Struct(st) => {
+                for field in st.fields.iter() {
+                    if let Some(default) = &field.default
+                        && !literal_fits(default, &field.typ, &user_types)
+                    {
+                        errs.push(Error::InvalidDefault {
+                            value: default.as_ref().map(ToString::to_string),
+                            typ: field.typ.to_string(),
+                        });
+                    }
+                }
+            }
+            UserType::Enum(en) => {
+                for var in en.definitions.iter() {
+                    if let EnumVariant::NamedVariant(field) = &var.value
+                        && field.default.is_some()
+                    {
+                        errs.push(Error::DefaultInEnum {
+                            variant: var.as_ref().map(|_| field.name.clone()),
+                        });
+                    }
+                }
+            }
+            UserType::Newtype(_) => {}
+        }
+    }
+    errs
+}
+
+/// Each side of a protocol sends one of the variants of a top-level enum.
+fn analyze_protocol_enums(document: &Document, imported: &[&S<UserType>]) -> Errors {
+    let mut errs = Errors::new();
+    let user_types = || document.user_types.iter().chain(imported.iter().copied());
+    for protocol in document.protocols.iter() {
+        for side in [&protocol.client, &protocol.server] {
+            let top_level_enum = user_types()
+                .any(|ut| ut.name() == side.value && matches!(ut.value, UserType::Enum(_)));
+            // Undefined types are already reported by `analyze_missing_types`.
+            let defined = user_types()
+                .flat_map(|ut| ut.children_user_types())
+                .any(|ut| ut.name() == side.value);
+            if defined && !top_level_enum {
+                errs.push(Error::NotAnEnum {
+                    name: side.clone(),
+                    protocol: protocol.as_ref().map(|pr| pr.name.clone()),
+                });
+            }
+        }
+    }
+    errs
+}
+
+/// Requests and replies are sent with their name, which only structs and enums have on the wire.
+fn analyze_messages(document: &Document, imported: &[&S<UserType>]) -> Errors {
+    let user_types: HashMap<_, _> = (document.user_types.iter().chain(imported.iter().copied()))
+        .flat_map(|ut| ut.children_user_types())
+        .map(|ut| (ut.name(), ut))
+        .collect();
+    (document.services.iter())
+        .flat_map(|sv| sv.methods.iter())
+        .flat_map(|method| [&method.input, &method.output])
+        .filter(|typ| {
+            let user_type = match &typ.value {
+                Type::User(name) | Type::Generic(name, _) => user_types.get(name.as_str()),
+                _ => return true,
+            };
+            // Undefined types are already reported by `analyze_missing_types`.
+            matches!(user_type, Some(UserType::Newtype(_)))
+        })
+        .map(|typ| Error::NotAMessage {
+            name: typ.as_ref().map(ToString::to_string),
+        })
+        .collect()
+}
+
+/// Generic types can only be used with as many type arguments as they have parameters.
+fn analyze_type_arguments(document: &Document, imported: &[&S<UserType>]) -> Errors {
+    let user_types: HashMap<_, _> = (document.user_types.iter().chain(imported.iter().copied()))
+        .flat_map(|ut| ut.children_user_types())
+        .map(|ut| (ut.name(), ut))
+        .collect();
+    let field_types = (document.user_types.iter())
+        .flat_map(|ut| ut.children_user_types())
+        .flat_map(|ut| ut.children_types());
+    let alias_types = (document.aliases.iter()).map(|alias| alias.as_ref().map(|a| a.typ.clone()));
+    let method_types = (document.services.iter())
+        .flat_map(|sv| sv.methods.iter())
+        .flat_map(|method| [method.input.clone(), method.output.clone()]);
+    let protocol_types = (document.protocols.iter())
+        .flat_map(|pr| [&pr.client, &pr.server])
+        .map(|side| side.as_ref().map(|name| Type::User(name.clone())));
+    let mut errs = Errors::new();
+    for tp in field_types
+        .chain(alias_types)
+        .chain(method_types)
+        .chain(protocol_types)
+    {
+        for typ in tp.nested_types() {
+            let (name, found) = match typ {
+                Type::User(name) => (name, 0),
+                Type::Generic(name, args) => (name, args.len()),
+                _ => continue,
+            };
+            if let Some(ut) = user_types.get(name.as_str())
+                && ut.params().len() != found
+            {
+                errs.push(Error::WrongTypeArguments {
+                    name: tp.as_ref().map(|_| name.clone()),
+                    expected: ut.params().len(),
+                    found,
+                });
+            }
+        }
+    }
+    errs
+}
+
+/// The argument an attribute takes.
+#[derive(Debug, Clone, Copy)]
+enum Argument {
+    None,
+    /// Such as the name given by `@rename("name")`.
+    String,
+    /// Such as the reason given by `@deprecated("reason")`, which may be left out.
+    OptionalString,
+    /// A number or a string, such as `@since(2)` or `@since("1.4")`.
+    Version,
+}
+
+impl Argument {
+    fn fits(self, arg: Option<&Literal>) -> bool {
+        matches!(
+            (self, arg),
+            (Self::None | Self::OptionalString, None)
+                | (
+                    Self::String | Self::OptionalString | Self::Version,
+                    Some(Literal::String(_))
+                )
+                | (Self::Version, Some(Literal::Int(_) | Literal::Float(_)))
+        )
+    }
+    fn describe(self) -> &'static str {
+        match self {
+            Self::None => "no argument",
+            Self::String => "a string",
+            Self::OptionalString => "an optional string",
+            Self::Version => "a version, written as a number or a string",
+        }
+    }
+}
+
+/// Every attribute, along with the argument it takes.
+const ATTRIBUTES: [(&str, Argument); 5] = [
+    ("rename", Argument::String),
+    ("deprecated", Argument::OptionalString),
+    ("since", Argument::Version),
+    ("flatten", Argument::None),
+    ("skip", Argument::None),
+];
+
+/// The attributes of types and variants, which are only about how they are named and documented.
+const ITEM_ATTRIBUTES: &[&str] = &["rename", "deprecated", "since"];
+
+/// Checks that `attrs` exist, are given once with the right argument, and are `allowed` on the
+/// item they are put on, which `place` describes.
+fn analyze_item_attributes(
+    attrs: &[S<Attribute>],
+    allowed: &[&str],
+    place: &'static str,
+    matcher: &mut Matcher,
+) -> Errors {
+    let mut errs = Errors::new();
+    let mut seen = HashSet::new();
+    for attr in attrs {
+        let name = attr.as_ref().map(|attr| attr.name.clone());
+        let Some((_, argument)) = ATTRIBUTES.iter().find(|(known, _)| *known == attr.name) else {
+            let known = ATTRIBUTES.iter().map(|(known, _)| *known);
+            errs.push(Error::UnknownAttribute {
+                suggestion: fuzzy_match(known, matcher, &attr.name).map(ToString::to_string),
+                name,
+            });
+            continue;
+        };
+        if !seen.insert(attr.name.as_str()) {
+            errs.push(Error::RepeatedAttribute { name });
+        } else if !allowed.contains(&attr.name.as_str()) {
+            errs.push(Error::MisplacedAttribute { name, place });
+        } else if !argument.fits(attr.arg.as_ref()) {
+            errs.push(Error::InvalidAttributeArgument {
+                name,
+                expected: argument.describe(),
+            });
+        }
+    }
+    errs
+}
+
+/// Fields that older peers don't know about have to be optional, and only fields holding a
+/// struct can be flattened into their parent.
+fn analyze_field_attributes<'a>(
+    field: &S<StructField>,
+    lookup: &dyn Fn(&str) -> Option<&'a UserType>,
+    matcher: &mut Matcher,
+) -> Errors {
+    let flatten = field.attrs.has_attr("flatten");
+    let mut errs = match flatten {
+        // Flattened fields have no key of their own, which could be renamed or skipped.
+        true => analyze_item_attributes(
+            &field.attrs,
+            &["deprecated", "since", "flatten"],
+            "flattened fields",
+            matcher,
+        ),
+        false => {
+            let all: Vec<_> = ATTRIBUTES.iter().map(|(name, _)| *name).collect();
+            analyze_item_attributes(&field.attrs, &all, "struct fields", matcher)
+        }
+    };
+    let name = field.as_ref().map(|field| field.name.clone());
+    let optional = field.default.is_some() || matches!(field.typ, Type::Optional(_));
+    for attr in ["since", "skip"] {
+        if field.attrs.has_attr(attr) && !optional {
+            errs.push(Error::MissingDefault {
+                field: name.clone(),
+                attr,
+            });
+        }
+    }
+    if flatten {
+        let inner = match &field.typ {
+            Type::UserInline(ut) => Some(ut),
+            typ => typ.as_user().and_then(lookup),
+        };
+        let valid = match inner {
+            Some(inner) => matches!(inner, UserType::Struct(_)) && field.default.is_none(),
+            // Undefined types are already reported by `analyze_missing_types`.
+            None => field.typ.as_user().is_some(),
+        };
+        if !valid {
+            errs.push(Error::InvalidFlatten { field: name });
+        }
+    }
+    errs
+}
+
+fn analyze_attributes(document: &Document, imported: &[&S<UserType>]) -> Errors {
+    let user_types: HashMap<_, _> = (document.user_types.iter().chain(imported.iter().copied()))
+        .flat_map(|ut| ut.children_user_types())
+        .map(|ut| (ut.name(), ut))
+        .collect();
+    let lookup = |name: &str| user_types.get(name).copied();
+    let mut matcher = Matcher::default();
+    let mut errs = Errors::new();
+    for ut in document
+        .user_types
+        .iter()
+        .flat_map(|ut| ut.children_user_types())
+    {
+        errs.extend(match ut {
+            // Newtypes are sent as the type they wrap, so they have no name to change.
+            UserType::Newtype(nt) => analyze_item_attributes(
+                &nt.attrs,
+                &["deprecated", "since"],
+                "newtypes",
+                &mut matcher,
+            ),
+            _ => analyze_item_attributes(ut.attrs(), ITEM_ATTRIBUTES, "types", &mut matcher),
+        });
+        match ut {
+            UserType::Struct(st) => {
+                for field in st.fields.iter() {
+                    errs.extend(analyze_field_attributes(field, &lookup, &mut matcher));
+                }
+                let mut keys = HashSet::new();
+                for key in st.wire_keys(&lookup) {
+                    if !keys.insert(key.value.clone()) {
+                        errs.push(Error::RepeatedKey {
+                            key,
+                            parent: st.as_ref().map(|st| st.name.clone()),
+                        });
+                    }
+                }
+            }
+            UserType::Enum(en) => {
+                for var in en.definitions.iter() {
+                    let (attrs, allowed, place) = match &var.value {
+                        EnumVariant::NamedVariant(field) => {
+                            (&field.attrs, ITEM_ATTRIBUTES, "enum variants")
+                        }
+                        EnumVariant::Unit(unit) if unit.tag.is_some() => (
+                            &unit.attrs,
+                            &["deprecated", "since"][..],
+                            "unit variants with a tag",
+                        ),
+                        EnumVariant::Unit(unit) => (&unit.attrs, ITEM_ATTRIBUTES, "enum variants"),
+                        // Those of the type itself, which is checked on its own.
+                        EnumVariant::UserType(_) => continue,
+                    };
+                    errs.extend(analyze_item_attributes(attrs, allowed, place, &mut matcher));
+                }
+            }
+            UserType::Newtype(_) => {}
+        }
+    }
+    errs
+}
+
+/// Adds the alias `name` of `own` to `resolved`, after the aliases it refers to.
+///
+/// `path` holds the aliases being resolved, to catch those referring to themselves.
+fn resolve_alias(
+    name: &str,
+    own: &HashMap<&str, &S<Alias>>,
+    resolved: &mut HashMap<String, Type>,
+    path: &mut Vec<String>,
+) -> Result<(), Error> {
+    if resolved.contains_key(name) {
+        return Ok(());
+    }
+    let alias = own[name];
+    if path.iter().any(|visited| visited == name) {
+        return Err(Error::AliasCycle {
+            name: alias.as_ref().map(|alias| alias.name.clone()),
+        });
+    }
+    path.push(name.to_owned());
+    for typ in alias.typ.nested_types() {
+        if let Some(other) = typ.as_user()
+            && own.contains_key(other)
+        {
+            resolve_alias(other, own, resolved, path)?;
+        }
+    }
+    path.pop();
+    let mut typ = alias.typ.clone();
+    typ.substitute(resolved);
+    resolved.insert(name.to_owned(), typ);
+    Ok(())
+}
+
+/// Replaces the aliases used by `document`, which may be its own or `imported` ones, by the
+/// types they stand for.
+fn resolve_aliases(document: &mut Document, mut imported: HashMap<String, Type>) -> Errors {
+    let own: HashMap<_, _> = (document.aliases.iter())
+        .map(|alias| (alias.name.as_str(), alias))
+        .collect();
+    let mut errs = Errors::new();
+    for alias in document.aliases.iter() {
+        if let Err(err) = resolve_alias(&alias.name, &own, &mut imported, &mut vec![]) {
+            errs.push(err);
+        }
+    }
+    let aliases = imported;
+    for alias in document.aliases.iter_mut() {
+        if let Some(typ) = aliases.get(&alias.name) {
+            alias.typ = typ.clone();
+        }
+    }
+    for ut in document.user_types.iter_mut() {
+        ut.substitute(&aliases);
+    }
+    for method in document
+        .services
+        .iter_mut()
+        .flat_map(|sv| sv.methods.iter_mut())
+    {
+        method.input.substitute(&aliases);
+        method.output.substitute(&aliases);
+    }
+    // Protocol sides are named, so only aliases of named types can be used.
+    let names = (document.protocols.iter_mut())
+        .map(|pr| &mut pr.value)
+        .flat_map(|pr| [&mut pr.client, &mut pr.server]);
+    for name in names {
+        if let Some(Type::User(aliased)) = aliases.get(name.as_str()) {
+            name.value = aliased.clone();
+        }
+    }
+    errs
+}
+
+/// Checks `document` as if it was alone, but could use the `imported` types.
+fn analyze(document: &Document, imported: &[&S<UserType>]) -> Errors {
+    let mut errs = analyze_missing_types(document, imported);
+    errs.extend(analyze_repeated_types(document));
+    errs.extend(analyze_repeated_requests(document));
+    errs.extend(analyze_repeated_variants(document));
+    errs.extend(analyze_map_keys(document, imported));
+    errs.extend(analyze_defaults(document, imported));
+    errs.extend(analyze_protocol_enums(document, imported));
+    errs.extend(analyze_messages(document, imported));
+    errs.extend(analyze_type_arguments(document, imported));
+    errs.extend(analyze_attributes(document, imported));
+    errs
+}
+
+fn into_result(mut errs: Errors) -> Result<(), Error> {
     if errs.is_empty() {
         Ok(())
     } else if errs.len() == 1 {
-        Err(errs.pop().unwrap().into())
+        Err(errs.pop().unwrap())
     } else {
-        Err(Error::MultipleErrors(errs).into())
+        Err(Error::MultipleErrors(errs))
+    }
+}
+
+pub fn analyze_errors(document: &Document) -> R {
+    Ok(into_result(analyze(document, &[]))?)
+}
+
+/// Errors found in more than one file of a schema, each reported against its own file.
+#[derive(Error, Debug)]
+#[error("Errors were found in {} files.", .0.len())]
+pub struct SchemaErrors(Vec<miette::Report>);
+
+impl Diagnostic for SchemaErrors {
+    fn related<'a>(&'a self) -> Option<Box<dyn Iterator<Item = &'a dyn Diagnostic> + 'a>> {
+        Some(Box::new(self.0.iter().map(|report| {
+            let diagnostic: &dyn Diagnostic = report.as_ref();
+            diagnostic
+        })))
+    }
+}
+
+/// The aliases `file` imports, which were already resolved.
+fn imported_aliases(schema: &Schema, file: &SourceFile) -> HashMap<String, Type> {
+    let mut aliases = HashMap::new();
+    for (import, &index) in file.document.imports.iter().zip(&file.imports) {
+        for alias in schema.files[index].document.aliases.iter() {
+            let wanted = (import.names.as_ref())
+                .is_none_or(|names| names.iter().any(|name| name.value == alias.name));
+            if wanted {
+                aliases.insert(alias.name.clone(), alias.typ.clone());
+            }
+        }
+    }
+    aliases
+}
+
+/// Checks every file of `schema`, each with the types it imports, and replaces their aliases
+/// by the types they stand for.
+pub fn analyze_schema(schema: &mut Schema) -> R {
+    let mut reports: Vec<_> = (schema_errors(schema).into_iter().enumerate())
+        .filter_map(|(i, errs)| {
+            let err = into_result(errs).err()?;
+            Some(miette::Report::from(err).with_source_code(schema.files[i].named_source()))
+        })
+        .collect();
+    match reports.len() {
+        0 => Ok(()),
+        1 => Err(reports.pop().unwrap()),
+        _ => Err(SchemaErrors(reports).into()),
+    }
+}
+
+/// The errors found in each file of `schema`, see [`analyze_schema`].
+fn schema_errors(schema: &mut Schema) -> Vec<Errors> {
+    let mut file_errs = vec![];
+    // The file defining each type, which must be the only one to do so.
+    let mut defined = HashMap::<String, usize>::new();
+    // Imported files come first, so their aliases are resolved before they are used.
+    for i in 0..schema.files.len() {
+        let aliases = imported_aliases(schema, &schema.files[i]);
+        let mut errs = resolve_aliases(&mut schema.files[i].document, aliases);
+        let file = &schema.files[i];
+        let mut imported = vec![];
+        for (import, &index) in file.document.imports.iter().zip(&file.imports) {
+            let document = &schema.files[index].document;
+            let user_types = &document.user_types;
+            let Some(names) = &import.names else {
+                imported.extend(user_types);
+                continue;
+            };
+            for name in names {
+                match user_types.iter().find(|ut| ut.name() == name.value) {
+                    Some(ut) => imported.push(ut),
+                    None if document
+                        .aliases
+                        .iter()
+                        .any(|alias| alias.name == name.value) => {}
+                    None => errs.push(Error::UndefinedImport {
+                        name: name.clone(),
+                        path: import.path.value.clone(),
+                    }),
+                }
+            }
+        }
+        errs.extend(analyze(&file.document, &imported));
+        for ut in (file.document.user_types.iter()).flat_map(|ut| ut.children_user_types()) {
+            match defined.get(ut.name()) {
+                Some(&other) if other != i => errs.push(Error::AlreadyDefined {
+                    name: ut.spanned_name(),
+                    path: schema.files[other].path.display().to_string(),
+                }),
+                Some(_) => {}
+                None => {
+                    defined.insert(ut.name().to_owned(), i);
+                }
+            }
+        }
+        file_errs.push(errs);
+    }
+    file_errs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::loader;
+
+    /// The errors found in `err_examples/{name}.wing`, by any file of its schema.
+    fn errors(name: &str) -> Errors {
+        let mut schema = loader::load(format!("err_examples/{name}.wing")).unwrap();
+        schema_errors(&mut schema).concat()
+    }
+
+    fn names(errs: &[Error]) -> Vec<(&str, &str)> {
+        errs.iter()
+            .map(|err| match err {
+                Error::RepeatedType { name } => ("RepeatedType", name.as_str()),
+                Error::AlreadyDefined { name, .. } => ("AlreadyDefined", name.as_str()),
+                Error::RepeatedEnumVariant { variant, .. } => {
+                    ("RepeatedEnumVariant", variant.as_str())
+                }
+                Error::InvalidMapKey { key } => ("InvalidMapKey", key.as_str()),
+                Error::InvalidDefault { value, .. } => ("InvalidDefault", value.as_str()),
+                Error::AliasCycle { name } => ("AliasCycle", name.as_str()),
+                Error::NotAMessage { name } => ("NotAMessage", name.as_str()),
+                Error::UnknownAttribute { name, .. } => ("UnknownAttribute", name.as_str()),
+                Error::InvalidAttributeArgument { name, .. } => {
+                    ("InvalidAttributeArgument", name.as_str())
+                }
+                Error::MisplacedAttribute { name, .. } => ("MisplacedAttribute", name.as_str()),
+                Error::RepeatedAttribute { name } => ("RepeatedAttribute", name.as_str()),
+                Error::MissingDefault { field, .. } => ("MissingDefault", field.as_str()),
+                Error::InvalidFlatten { field } => ("InvalidFlatten", field.as_str()),
+                Error::RepeatedKey { key, .. } => ("RepeatedKey", key.as_str()),
+                Error::RepeatedMethod { method, .. } => ("RepeatedMethod", method.as_str()),
+                Error::RepeatedRequest { request, .. } => ("RepeatedRequest", request.as_str()),
+                err => panic!("Unexpected error: {err}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn repeated_types() {
+        let errs = errors("repeated_types");
+        assert_eq!(
+            names(&errs),
+            [("RepeatedType", "Point"), ("AlreadyDefined", "Shape")]
+        );
+    }
+
+    #[test]
+    fn repeated_methods() {
+        let errs = errors("repeated_methods");
+        assert_eq!(
+            names(&errs),
+            [("RepeatedMethod", "get"), ("RepeatedRequest", "Get")]
+        );
+    }
+
+    #[test]
+    fn repeated_variants() {
+        let errs = errors("repeated_variants");
+        assert_eq!(
+            names(&errs),
+            [
+                ("RepeatedEnumVariant", "A"),
+                ("RepeatedEnumVariant", "b"),
+                ("RepeatedEnumVariant", "Point"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_map_keys() {
+        let errs = errors("invalid_map_keys");
+        assert_eq!(
+            names(&errs),
+            [
+                ("InvalidMapKey", "Weight"),
+                ("InvalidMapKey", "Shape"),
+                ("InvalidMapKey", "bool"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_default() {
+        let errs = errors("invalid_default");
+        assert_eq!(
+            names(&errs),
+            [
+                ("InvalidDefault", "300"),
+                ("InvalidDefault", "Mid"),
+                ("InvalidDefault", "3"),
+            ]
+        );
+    }
+
+    #[test]
+    fn alias_cycle() {
+        let errs = errors("alias_cycle");
+        assert_eq!(
+            names(&errs),
+            [
+                ("AliasCycle", "A"),
+                ("AliasCycle", "B"),
+                ("NotAMessage", "Id"),
+            ]
+        );
+    }
+
+    #[test]
+    fn wrong_type_arguments() {
+        let errs = errors("wrong_type_arguments");
+        let counts: Vec<_> = (errs.iter())
+            .map(|err| match err {
+                Error::WrongTypeArguments {
+                    name,
+                    expected,
+                    found,
+                } => (name.as_str(), *expected, *found),
+                err => panic!("Unexpected error: {err}"),
+            })
+            .collect();
+        assert_eq!(counts, [("Page", 1, 2), ("Page", 1, 0)]);
+    }
+
+    #[test]
+    fn invalid_attributes() {
+        let errs = errors("invalid_attributes");
+        assert_eq!(
+            names(&errs),
+            [
+                ("MisplacedAttribute", "rename"),
+                ("RepeatedKey", "x"),
+                ("UnknownAttribute", "flaten"),
+                ("InvalidFlatten", "id"),
+                ("RepeatedAttribute", "since"),
+                ("MissingDefault", "size"),
+                ("MissingDefault", "area"),
+                ("InvalidAttributeArgument", "rename"),
+                ("MisplacedAttribute", "skip"),
+                ("MisplacedAttribute", "rename"),
+            ]
+        );
     }
 }
@@ -2,7 +2,7 @@ use std::path::Path;
 
 use crate::{
     emitter::{Emitter, PyEmitter, RustEmitter},
-    parser, semantic_analyzer,
+    loader, semantic_analyzer,
 };
 
 #[derive(Clone, Copy)]
@@ -21,28 +21,55 @@ const LANGS: &[Lang] = &[Lang::Rust, Lang::Python];
 
 pub fn test(name: &str, mode: Mode) {
     let cwd = Path::new("test-files/");
-    let input = std::fs::read_to_string(cwd.join(format!("{name}.wing"))).unwrap();
-    let doc = parser::parse_document(&input).expect("Failed to parse document");
-    if let Err(e) = semantic_analyzer::analyze_errors(&doc) {
-        panic!("Got error while analyzing {name}: {e}");
+    let mut schema = loader::load(cwd.join(format!("{name}.wing"))).expect("Failed to load schema");
+    if let Err(e) = semantic_analyzer::analyze_schema(&mut schema) {
+        panic!("Got error while analyzing {name}: {e:?}");
     }
+    let doc = schema.merged();
+    // Qualified names only make a difference to files of a package.
+    let qualified = match doc.package {
+        Some(_) => &[false, true][..],
+        None => &[false],
+    };
     let mut output = Vec::new();
     for lang in LANGS {
-        let (mut emitter, ext): (Box<dyn Emitter>, &str) = match lang {
-            Lang::Rust => (Box::new(RustEmitter::new()), "rs"),
-            Lang::Python => (Box::new(PyEmitter::new()), "py"),
-        };
-        let output_file = cwd.join(format!("{name}.{ext}"));
-        output.clear();
-        emitter.emit(&doc, &mut output).unwrap();
-        let output = std::str::from_utf8(output.as_slice()).expect("Got invalid utf-8 sequence");
-        match mode {
-            Mode::Test => {
-                let expected = std::fs::read_to_string(&output_file).unwrap();
-                assert_eq!(expected, output)
+        for &qualified_names in qualified {
+            let (mut emitter, ext): (Box<dyn Emitter>, &str) = match lang {
+                Lang::Rust => (
+                    Box::new(RustEmitter::new().with_qualified_names(qualified_names)),
+                    "rs",
+                ),
+                Lang::Python => (
+                    Box::new(PyEmitter::new().with_qualified_names(qualified_names)),
+                    "py",
+                ),
+            };
+            let output_file = match qualified_names {
+                true => cwd.join(format!("{name}.qualified.{ext}")),
+                false => cwd.join(format!("{name}.{ext}")),
+            };
+            output.clear();
+            if let Err(err) = emitter.emit(&doc, &mut output) {
+                // Documents an emitter rejects have no golden file for its language.
+                assert_eq!(err.kind(), std::io::ErrorKind::Unsupported, "{err}");
+                match mode {
+                    Mode::Test => assert!(!output_file.exists(), "Failed to emit {name}: {err}"),
+                    Mode::Emit => {
+                        let _ = std::fs::remove_file(&output_file);
+                    }
+                }
+                continue;
             }
-            Mode::Emit => {
-                std::fs::write(&output_file, output).unwrap();
+            let output =
+                std::str::from_utf8(output.as_slice()).expect("Got invalid utf-8 sequence");
+            match mode {
+                Mode::Test => {
+                    let expected = std::fs::read_to_string(&output_file).unwrap();
+                    assert_eq!(expected, output)
+                }
+                Mode::Emit => {
+                    std::fs::write(&output_file, output).unwrap();
+                }
             }
         }
     }
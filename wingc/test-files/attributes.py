@@ -0,0 +1,79 @@
+from wing_rpc import Schema, Enum, FlatSchema
+from wing_rpc.peer import Peer, CallStream, ReplyStream
+from pydantic import ConfigDict, Field
+from typing import ClassVar
+from typing_extensions import deprecated
+from enum import StrEnum
+
+
+class Person(FlatSchema):
+    """Someone who can log in."""
+    __wing_name__: ClassVar[str] = 'Human'
+    model_config = ConfigDict(validate_by_name=True, serialize_by_alias=True)
+    __wing_flatten__: ClassVar[dict] = {'hair': ('color', 'length',)}
+    __match_args__: ClassVar[tuple] = ('name', 'kind', 'hair', 'nickname', 'age', 'seen',)
+    name: str
+    kind: str = Field(alias='type')
+    hair: 'Hair'
+    nickname: str | None = Field(None, deprecated='use "name" instead')
+    """Shown instead of the name."""
+    age: int = 0
+    """Added in version 2."""
+    seen: list[str] = Field([], exclude=True)
+
+
+class Hair(Schema):
+    model_config = ConfigDict(validate_by_name=True, serialize_by_alias=True)
+    __match_args__: ClassVar[tuple] = ('color', 'len',)
+    color: int
+    len: int | None = Field(None, alias='length')
+
+
+@deprecated('Deprecated.', category=None)
+class Badge(Schema):
+    __match_args__: ClassVar[tuple] = ('id',)
+    id: int
+
+
+class Event(Enum):
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    __units__: ClassVar[tuple] = ('left', 'idle',)
+    class Tag(StrEnum):
+        Joined = 'joined'
+        Awarded = 'Awarded'
+        """Deprecated: badges are gone"""
+        Left = 'left'
+        """Added in version 1.4."""
+        Idle = 'idle'
+    tag: Tag
+    value: Person | Badge | None = None
+
+
+@deprecated('use Event instead', category=None)
+class Status(StrEnum):
+    """Kept for old clients."""
+    Online = 'Online'
+    Away = 'Away'
+
+
+class PeopleClient:
+    def __init__(self, peer: Peer):
+        self.peer = peer
+
+    def greet(self, request: Person) -> Event:
+        return self.peer.call(request, Event)
+
+
+class People:
+    def greet(self, request: Person) -> Event:
+        raise NotImplementedError
+
+    def serve(self, peer: Peer):
+        call = peer.next_call()
+        match call.name:
+            case 'Human':
+                peer.answer(call, Person, self.greet)
+            case name:
+                peer.reply_error(call, f"Unknown request '{name}'")
+
+
@@ -0,0 +1,110 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+/// Someone who can log in.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename = "Human")]
+pub struct Person {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(flatten)]
+    pub hair: Hair,
+    /// Shown instead of the name.
+    #[deprecated = "use \"name\" instead"]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    /// Added in version 2.
+    #[serde(default = "Person::default_age")]
+    pub age: u32,
+    #[serde(skip, default = "Person::default_seen")]
+    pub seen: Vec<String>,
+}
+
+#[allow(deprecated)]
+impl Person {
+    fn default_age() -> u32 {
+        0
+    }
+    fn default_seen() -> Vec<String> {
+        vec![]
+    }
+}
+
+impl<'a> WingMessage<'a> for Person {
+    const NAME: &'static str = "Human";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Hair {
+    pub color: u32,
+    #[serde(rename = "length", default, skip_serializing_if = "Option::is_none")]
+    pub len: Option<u32>,
+}
+
+impl<'a> WingMessage<'a> for Hair {
+    const NAME: &'static str = "Hair";
+}
+
+#[deprecated]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Badge {
+    pub id: u64,
+}
+
+#[allow(deprecated)]
+impl<'a> WingMessage<'a> for Badge {
+    const NAME: &'static str = "Badge";
+}
+
+#[allow(deprecated)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Event {
+    #[serde(rename = "joined")]
+    Joined(Person),
+    #[deprecated = "badges are gone"]
+    Awarded(Badge),
+    /// Added in version 1.4.
+    #[serde(rename = "left")]
+    Left,
+    #[serde(rename = "idle")]
+    Idle,
+}
+
+impl<'a> WingMessage<'a> for Event {
+    const NAME: &'static str = "Event";
+}
+
+/// Kept for old clients.
+#[deprecated = "use Event instead"]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Status {
+    Online,
+    Away,
+}
+
+#[allow(deprecated)]
+impl<'a> WingMessage<'a> for Status {
+    const NAME: &'static str = "Status";
+}
+
+pub struct PeopleClient<'p>(pub &'p mut wing_rpc::Peer);
+
+impl PeopleClient<'_> {
+    pub fn greet(&mut self, request: Person) -> wing_rpc::WingResult<Event> {
+        self.0.call(request)
+    }
+}
+
+pub trait People {
+    fn greet(&mut self, request: Person) -> wing_rpc::WingResult<Event>;
+    fn serve(&mut self, peer: &mut wing_rpc::Peer) -> wing_rpc::WingResult<()> {
+        let call = peer.next_call()?;
+        match call.name() {
+            "Human" => peer.answer(&call, |request| self.greet(request)),
+            name => peer.reply_error(&call, format!("Unknown request '{name}'")),
+        }
+    }
+}
+
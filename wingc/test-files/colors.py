@@ -0,0 +1,30 @@
+from wing_rpc import Schema, Enum
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Rgb(Schema):
+    __match_args__: ClassVar[tuple] = ('r', 'g', 'b',)
+    r: int
+    g: int
+    b: int
+
+
+class HSLV(Schema):
+    __match_args__: ClassVar[tuple] = ('h', 's', 'l',)
+    h: float
+    s: float
+    l: float
+
+
+class Gray(Schema):
+    __match_args__: ClassVar[tuple] = ('value',)
+    value: float
+
+
+class Color(StrEnum):
+    Rgb = 'Rgb'
+    HSL = 'HSL'
+    Gray = 'Gray'
+
+
@@ -0,0 +1,46 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl<'a> WingMessage<'a> for Rgb {
+    const NAME: &'static str = "Rgb";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HSLV {
+    pub h: f32,
+    pub s: f32,
+    pub l: f32,
+}
+
+impl<'a> WingMessage<'a> for HSLV {
+    const NAME: &'static str = "HSLV";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Gray {
+    pub value: f32,
+}
+
+impl<'a> WingMessage<'a> for Gray {
+    const NAME: &'static str = "Gray";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Color {
+    Rgb,
+    HSL,
+    Gray,
+}
+
+impl<'a> WingMessage<'a> for Color {
+    const NAME: &'static str = "Color";
+}
+
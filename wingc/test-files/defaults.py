@@ -0,0 +1,34 @@
+from wing_rpc import Schema, Enum
+from pydantic import Field
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Level(StrEnum):
+    Low = 'low'
+    High = 'high'
+
+
+class Retry(Schema):
+    __match_args__: ClassVar[tuple] = ('attempts', 'backoff', 'jitter', 'enabled', 'reason',)
+    attempts: int = 5
+    backoff: float = 1.0
+    jitter: float = 0.25
+    enabled: bool = False
+    reason: str | None = None
+    """Left out unless set."""
+
+
+class Job(Schema):
+    __match_args__: ClassVar[tuple] = ('name', 'tags', 'priority', 'limits', 'levels', 'owner', 'magic', 'banner',)
+    name: str
+    tags: list[str] = []
+    priority: Level = Field('high', validate_default=True)
+    limits: dict[str, int] = {'memory': -1, 'cpu': 2}
+    levels: list[Level] = Field(['low', 'high'], validate_default=True)
+    owner: str | None = 'nobody'
+    magic: bytes = bytes([1, 2])
+    banner: str = 'say "hi"\\\n'
+    """Quotes, backslashes and newlines are escaped."""
+
+
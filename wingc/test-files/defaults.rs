@@ -0,0 +1,110 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Level {
+    #[serde(rename = "low")]
+    Low,
+    #[serde(rename = "high")]
+    High,
+}
+
+impl<'a> WingMessage<'a> for Level {
+    const NAME: &'static str = "Level";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Retry {
+    #[serde(default = "Retry::default_attempts")]
+    pub attempts: u32,
+    #[serde(default = "Retry::default_backoff")]
+    pub backoff: f64,
+    #[serde(default = "Retry::default_jitter")]
+    pub jitter: f32,
+    #[serde(default = "Retry::default_enabled")]
+    pub enabled: bool,
+    /// Left out unless set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl Retry {
+    fn default_attempts() -> u32 {
+        5
+    }
+    fn default_backoff() -> f64 {
+        1.0
+    }
+    fn default_jitter() -> f32 {
+        0.25
+    }
+    fn default_enabled() -> bool {
+        false
+    }
+}
+
+impl Default for Retry {
+    fn default() -> Self {
+        Self {
+            attempts: Self::default_attempts(),
+            backoff: Self::default_backoff(),
+            jitter: Self::default_jitter(),
+            enabled: Self::default_enabled(),
+            reason: None,
+        }
+    }
+}
+
+impl<'a> WingMessage<'a> for Retry {
+    const NAME: &'static str = "Retry";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Job {
+    pub name: String,
+    #[serde(default = "Job::default_tags")]
+    pub tags: Vec<String>,
+    #[serde(default = "Job::default_priority")]
+    pub priority: Level,
+    #[serde(default = "Job::default_limits")]
+    pub limits: std::collections::HashMap<String, i64>,
+    #[serde(default = "Job::default_levels")]
+    pub levels: Vec<Level>,
+    #[serde(default = "Job::default_owner")]
+    pub owner: Option<String>,
+    #[serde(default = "Job::default_magic")]
+    pub magic: Vec<u8>,
+    /// Quotes, backslashes and newlines are escaped.
+    #[serde(default = "Job::default_banner")]
+    pub banner: String,
+}
+
+impl Job {
+    fn default_tags() -> Vec<String> {
+        vec![]
+    }
+    fn default_priority() -> Level {
+        Level::High
+    }
+    fn default_limits() -> std::collections::HashMap<String, i64> {
+        [("memory".to_owned(), -1), ("cpu".to_owned(), 2)].into_iter().collect()
+    }
+    fn default_levels() -> Vec<Level> {
+        vec![Level::Low, Level::High]
+    }
+    fn default_owner() -> Option<String> {
+        Some("nobody".to_owned())
+    }
+    fn default_magic() -> Vec<u8> {
+        vec![1, 2]
+    }
+    fn default_banner() -> String {
+        "say \"hi\"\\\n".to_owned()
+    }
+}
+
+impl<'a> WingMessage<'a> for Job {
+    const NAME: &'static str = "Job";
+}
+
@@ -0,0 +1,35 @@
+from wing_rpc import Schema, Enum
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Point(Schema):
+    """A point on the screen.
+
+    Measured in pixels from the top left corner.
+    """
+    __match_args__: ClassVar[tuple] = ('x', 'y',)
+    x: int
+    """Grows to the right."""
+    y: int
+
+
+class Delete(Schema):
+    """Removes the shape."""
+    __match_args__: ClassVar[tuple] = ('id',)
+    id: int
+
+
+class Action(Enum):
+    """What a user can do with a \"shape\"."""
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    class Tag(StrEnum):
+        Move = 'Move'
+        """Moves the shape by this much."""
+        Delete = 'Delete'
+        """Removes the shape."""
+        Rename = 'Rename'
+    tag: Tag
+    value: Point | Delete | str
+
+
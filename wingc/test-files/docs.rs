@@ -0,0 +1,42 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+/// A point on the screen.
+///
+/// Measured in pixels from the top left corner.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    /// Grows to the right.
+    pub x: i32,
+    pub y: i32,
+}
+
+impl<'a> WingMessage<'a> for Point {
+    const NAME: &'static str = "Point";
+}
+
+/// Removes the shape.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delete {
+    pub id: u32,
+}
+
+impl<'a> WingMessage<'a> for Delete {
+    const NAME: &'static str = "Delete";
+}
+
+/// What a user can do with a "shape".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Action {
+    /// Moves the shape by this much.
+    Move(Point),
+    /// Removes the shape.
+    Delete(Delete),
+    Rename(String),
+}
+
+impl<'a> WingMessage<'a> for Action {
+    const NAME: &'static str = "Action";
+}
+
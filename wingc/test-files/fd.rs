@@ -0,0 +1,39 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VideoFrame {
+    #[serde(with = "wing_rpc::fd")]
+    pub pixels: std::os::fd::OwnedFd,
+    pub width: u32,
+    pub height: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none", with = "wing_rpc::fd")]
+    pub audio: Option<std::os::fd::OwnedFd>,
+}
+
+impl<'a> WingMessage<'a> for VideoFrame {
+    const NAME: &'static str = "VideoFrame";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Bundle {
+    #[serde(with = "wing_rpc::fd")]
+    pub files: Vec<std::os::fd::OwnedFd>,
+    pub frame: VideoFrame,
+}
+
+impl<'a> WingMessage<'a> for Bundle {
+    const NAME: &'static str = "Bundle";
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Shared {
+    file(#[serde(with = "wing_rpc::fd")] std::os::fd::OwnedFd),
+    name(String),
+}
+
+impl<'a> WingMessage<'a> for Shared {
+    const NAME: &'static str = "Shared";
+}
+
@@ -0,0 +1,99 @@
+from wing_rpc import Schema, Enum
+from wing_rpc.peer import Peer, CallStream, ReplyStream
+from typing import ClassVar, Generic, TypeVar, Annotated
+from enum import StrEnum
+
+A = TypeVar('A')
+B = TypeVar('B')
+T = TypeVar('T')
+
+
+class Page(Schema, Generic[T]):
+    """One page of the results of a query."""
+    __wing_instances__: ClassVar[dict] = {}
+    __match_args__: ClassVar[tuple] = ('items', 'next', 'total',)
+    items: list[T]
+    next: str | None = None
+    """Where the next page starts, if there is one."""
+    total: int = 0
+
+
+class Pair(Schema, Generic[A, B]):
+    __wing_instances__: ClassVar[dict] = {}
+    __match_args__: ClassVar[tuple] = ('first', 'second',)
+    first: A
+    second: B
+
+
+class Outcome(Enum, Generic[T]):
+    __wing_instances__: ClassVar[dict] = {}
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    __units__: ClassVar[tuple] = ('Pending',)
+    class Tag(StrEnum):
+        Done = 'Done'
+        Failed = 'Failed'
+        Pending = 'Pending'
+    tag: Tag
+    value: T | str | None = None
+
+
+class User(Schema):
+    __match_args__: ClassVar[tuple] = ('name',)
+    name: str
+
+
+class Query(Schema):
+    __match_args__: ClassVar[tuple] = ('text', 'extra',)
+    text: str
+    extra: Pair[str, list[Annotated[int, "u8"]]]
+
+
+class Totals(Schema):
+    """Rust sends each of these with its own name, so Python must tell them apart."""
+    __match_args__: ClassVar[tuple] = ('small', 'large',)
+    small: Page[Annotated[int, "u32"]]
+    large: Page[Annotated[int, "u64"]]
+
+
+class Progress(Schema):
+    __match_args__: ClassVar[tuple] = ('step',)
+    step: Outcome[Page[User]]
+
+
+Pair.__wing_instances__[(str, list[Annotated[int, "u8"]],)] = 'Pair<string, [u8]>'
+Page.__wing_instances__[(Annotated[int, "u32"],)] = 'Page<u32>'
+Page.__wing_instances__[(Annotated[int, "u64"],)] = 'Page<u64>'
+Outcome.__wing_instances__[(Page[User],)] = 'Outcome<Page<User>>'
+Page.__wing_instances__[(User,)] = 'Page<User>'
+Outcome.__wing_instances__[(Annotated[int, "u32"],)] = 'Outcome<u32>'
+
+
+class UsersClient:
+    def __init__(self, peer: Peer):
+        self.peer = peer
+
+    def search(self, request: Query) -> Page[User]:
+        return self.peer.call(request, Page[User])
+
+    def watch(self, request: Progress) -> CallStream[Outcome[Annotated[int, "u32"]]]:
+        return self.peer.call_stream(request, Outcome[Annotated[int, "u32"]])
+
+
+class Users:
+    def search(self, request: Query) -> Page[User]:
+        raise NotImplementedError
+
+    def watch(self, request: Progress, reply: ReplyStream[Outcome[Annotated[int, "u32"]]]):
+        raise NotImplementedError
+
+    def serve(self, peer: Peer):
+        call = peer.next_call()
+        match call.name:
+            case 'Query':
+                peer.answer(call, Query, self.search)
+            case 'Progress':
+                peer.answer_stream(call, Progress, self.watch)
+            case name:
+                peer.reply_error(call, f"Unknown request '{name}'")
+
+
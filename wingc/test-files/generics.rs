@@ -0,0 +1,121 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+/// One page of the results of a query.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Where the next page starts, if there is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(default = "Page::<T>::default_total")]
+    pub total: u32,
+}
+
+impl<T> Page<T> {
+    fn default_total() -> u32 {
+        0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pair<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Outcome<T> {
+    Done(T),
+    Failed(String),
+    Pending,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+}
+
+impl<'a> WingMessage<'a> for User {
+    const NAME: &'static str = "User";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Query {
+    pub text: String,
+    pub extra: Pair<String, Vec<u8>>,
+}
+
+impl<'a> WingMessage<'a> for Query {
+    const NAME: &'static str = "Query";
+}
+
+/// Rust sends each of these with its own name, so Python must tell them apart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Totals {
+    pub small: Page<u32>,
+    pub large: Page<u64>,
+}
+
+impl<'a> WingMessage<'a> for Totals {
+    const NAME: &'static str = "Totals";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    pub step: Outcome<Page<User>>,
+}
+
+impl<'a> WingMessage<'a> for Progress {
+    const NAME: &'static str = "Progress";
+}
+
+impl<'a> WingMessage<'a> for Pair<String, Vec<u8>> {
+    const NAME: &'static str = "Pair<string, [u8]>";
+}
+
+impl<'a> WingMessage<'a> for Page<u32> {
+    const NAME: &'static str = "Page<u32>";
+}
+
+impl<'a> WingMessage<'a> for Page<u64> {
+    const NAME: &'static str = "Page<u64>";
+}
+
+impl<'a> WingMessage<'a> for Outcome<Page<User>> {
+    const NAME: &'static str = "Outcome<Page<User>>";
+}
+
+impl<'a> WingMessage<'a> for Page<User> {
+    const NAME: &'static str = "Page<User>";
+}
+
+impl<'a> WingMessage<'a> for Outcome<u32> {
+    const NAME: &'static str = "Outcome<u32>";
+}
+
+pub struct UsersClient<'p>(pub &'p mut wing_rpc::Peer);
+
+impl UsersClient<'_> {
+    pub fn search(&mut self, request: Query) -> wing_rpc::WingResult<Page<User>> {
+        self.0.call(request)
+    }
+    pub fn watch(&mut self, request: Progress) -> wing_rpc::WingResult<wing_rpc::call::CallStream<'_, Outcome<u32>>> {
+        self.0.call_stream(request)
+    }
+}
+
+pub trait Users {
+    fn search(&mut self, request: Query) -> wing_rpc::WingResult<Page<User>>;
+    fn watch(&mut self, request: Progress, reply: &mut wing_rpc::call::ReplyStream<'_, Outcome<u32>>) -> wing_rpc::WingResult<()>;
+    fn serve(&mut self, peer: &mut wing_rpc::Peer) -> wing_rpc::WingResult<()> {
+        let call = peer.next_call()?;
+        match call.name() {
+            "Query" => peer.answer(&call, |request| self.search(request)),
+            "Progress" => peer.answer_stream(&call, |request, reply| self.watch(request, reply)),
+            name => peer.reply_error(&call, format!("Unknown request '{name}'")),
+        }
+    }
+}
+
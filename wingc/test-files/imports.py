@@ -0,0 +1,37 @@
+from wing_rpc import Schema, Enum
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Point(Schema):
+    __match_args__: ClassVar[tuple] = ('x', 'y',)
+    x: float
+    y: float
+
+
+class Size(Schema):
+    __match_args__: ClassVar[tuple] = ('width', 'height',)
+    width: float
+    height: float
+
+
+class Color(StrEnum):
+    Black = 'Black'
+    White = 'White'
+
+
+class Stroke(Schema):
+    __match_args__: ClassVar[tuple] = ('color', 'width', 'start',)
+    color: Color
+    width: float
+    start: Point | None = None
+    """Where the dashes start."""
+
+
+class Rect(Schema):
+    __match_args__: ClassVar[tuple] = ('origin', 'size', 'border',)
+    origin: Point
+    size: Size
+    border: Stroke | None = None
+
+
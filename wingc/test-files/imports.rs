@@ -0,0 +1,59 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl<'a> WingMessage<'a> for Point {
+    const NAME: &'static str = "Point";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Size {
+    pub width: f64,
+    pub height: f64,
+}
+
+impl<'a> WingMessage<'a> for Size {
+    const NAME: &'static str = "Size";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Color {
+    Black,
+    White,
+}
+
+impl<'a> WingMessage<'a> for Color {
+    const NAME: &'static str = "Color";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Stroke {
+    pub color: Color,
+    pub width: f32,
+    /// Where the dashes start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<Point>,
+}
+
+impl<'a> WingMessage<'a> for Stroke {
+    const NAME: &'static str = "Stroke";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rect {
+    pub origin: Point,
+    pub size: Size,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border: Option<Stroke>,
+}
+
+impl<'a> WingMessage<'a> for Rect {
+    const NAME: &'static str = "Rect";
+}
+
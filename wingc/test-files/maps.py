@@ -0,0 +1,41 @@
+from wing_rpc import Schema, Enum
+from typing import ClassVar, NewType
+from enum import StrEnum
+
+
+class Result(Schema):
+    __match_args__: ClassVar[tuple] = ('score',)
+    score: float
+
+
+class Results(Schema):
+    __match_args__: ClassVar[tuple] = ('by_id', 'labels', 'history', 'groups', 'by_sku', 'by_grade',)
+    by_id: dict[int, Result]
+    labels: dict[str, str]
+    history: dict[int, list[Result]] | None = None
+    groups: list[dict[str, int]]
+    by_sku: dict['Sku', int]
+    by_grade: dict['Grade', list[Result]]
+
+
+Sku = NewType('Sku', str)
+
+
+class Grade(StrEnum):
+    Low = 'Low'
+    High = 'high'
+
+
+class Missing(Schema):
+    pass
+
+
+class Lookup(Enum):
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    class Tag(StrEnum):
+        Found = 'Found'
+        Missing = 'Missing'
+    tag: Tag
+    value: dict[int, str] | Missing
+
+
@@ -0,0 +1,61 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Result {
+    pub score: f64,
+}
+
+impl<'a> WingMessage<'a> for Result {
+    const NAME: &'static str = "Result";
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Results {
+    pub by_id: std::collections::HashMap<u64, Result>,
+    pub labels: std::collections::HashMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<std::collections::HashMap<i32, Vec<Result>>>,
+    pub groups: Vec<std::collections::HashMap<String, u8>>,
+    pub by_sku: std::collections::HashMap<Sku, u32>,
+    pub by_grade: std::collections::HashMap<Grade, Vec<Result>>,
+}
+
+impl<'a> WingMessage<'a> for Results {
+    const NAME: &'static str = "Results";
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Sku(pub String);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Grade {
+    Low,
+    #[serde(rename = "high")]
+    High,
+}
+
+impl<'a> WingMessage<'a> for Grade {
+    const NAME: &'static str = "Grade";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Missing {
+}
+
+impl<'a> WingMessage<'a> for Missing {
+    const NAME: &'static str = "Missing";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Lookup {
+    Found(std::collections::HashMap<u32, String>),
+    Missing(Missing),
+}
+
+impl<'a> WingMessage<'a> for Lookup {
+    const NAME: &'static str = "Lookup";
+}
+
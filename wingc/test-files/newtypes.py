@@ -0,0 +1,63 @@
+from wing_rpc import Schema, Enum
+from wing_rpc.peer import Peer, CallStream, ReplyStream
+from typing import ClassVar, NewType
+from enum import StrEnum
+
+
+UserId = NewType('UserId', int)
+"""Identifies a user."""
+
+
+class Account(Schema):
+    __match_args__: ClassVar[tuple] = ('id', 'owner', 'tags', 'friends', 'quota',)
+    id: UserId
+    owner: UserId | None = None
+    tags: list[str] = ['new']
+    friends: dict[str, UserId]
+    quota: 'Quota' = 10
+
+
+Quota = NewType('Quota', int)
+"""The number of files a user can keep."""
+
+
+Group = NewType('Group', int)
+"""A group of users."""
+
+
+class Principal(Enum):
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    class Tag(StrEnum):
+        User = 'User'
+        Group = 'Group'
+        """A group of users."""
+    tag: Tag
+    value: UserId | Group
+
+
+class Lookup(Schema):
+    __match_args__: ClassVar[tuple] = ('by',)
+    by: Principal
+
+
+class AccountsClient:
+    def __init__(self, peer: Peer):
+        self.peer = peer
+
+    def get(self, request: Lookup) -> Account:
+        return self.peer.call(request, Account)
+
+
+class Accounts:
+    def get(self, request: Lookup) -> Account:
+        raise NotImplementedError
+
+    def serve(self, peer: Peer):
+        call = peer.next_call()
+        match call.name:
+            case 'Lookup':
+                peer.answer(call, Lookup, self.get)
+            case name:
+                peer.reply_error(call, f"Unknown request '{name}'")
+
+
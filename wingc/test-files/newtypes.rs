@@ -0,0 +1,83 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+/// Identifies a user.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UserId(pub u64);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Account {
+    pub id: UserId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<UserId>,
+    #[serde(default = "Account::default_tags")]
+    pub tags: Vec<String>,
+    pub friends: std::collections::HashMap<String, UserId>,
+    #[serde(default = "Account::default_quota")]
+    pub quota: Quota,
+}
+
+impl Account {
+    fn default_tags() -> Vec<String> {
+        vec!["new".to_owned()]
+    }
+    fn default_quota() -> Quota {
+        Quota(10)
+    }
+}
+
+impl<'a> WingMessage<'a> for Account {
+    const NAME: &'static str = "Account";
+}
+
+/// The number of files a user can keep.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Quota(pub u32);
+
+/// A group of users.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Group(pub u32);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Principal {
+    User(UserId),
+    /// A group of users.
+    Group(Group),
+}
+
+impl<'a> WingMessage<'a> for Principal {
+    const NAME: &'static str = "Principal";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lookup {
+    pub by: Principal,
+}
+
+impl<'a> WingMessage<'a> for Lookup {
+    const NAME: &'static str = "Lookup";
+}
+
+pub struct AccountsClient<'p>(pub &'p mut wing_rpc::Peer);
+
+impl AccountsClient<'_> {
+    pub fn get(&mut self, request: Lookup) -> wing_rpc::WingResult<Account> {
+        self.0.call(request)
+    }
+}
+
+pub trait Accounts {
+    fn get(&mut self, request: Lookup) -> wing_rpc::WingResult<Account>;
+    fn serve(&mut self, peer: &mut wing_rpc::Peer) -> wing_rpc::WingResult<()> {
+        let call = peer.next_call()?;
+        match call.name() {
+            "Lookup" => peer.answer(&call, |request| self.get(request)),
+            name => peer.reply_error(&call, format!("Unknown request '{name}'")),
+        }
+    }
+}
+
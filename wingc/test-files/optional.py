@@ -0,0 +1,34 @@
+from wing_rpc import Schema, Enum
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Point(Schema):
+    __match_args__: ClassVar[tuple] = ('x', 'y',)
+    x: int
+    y: int
+
+
+class Profile(Schema):
+    __match_args__: ClassVar[tuple] = ('name', 'nickname', 'home', 'path', 'tags',)
+    name: str
+    nickname: str | None = None
+    home: Point | None = None
+    path: list[Point | None]
+    tags: list[str] | None = None
+
+
+class Move(Schema):
+    __match_args__: ClassVar[tuple] = ('to',)
+    to: Point | None = None
+
+
+class Update(Enum):
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    class Tag(StrEnum):
+        Rename = 'Rename'
+        Move = 'Move'
+    tag: Tag
+    value: str | None | Move
+
+
@@ -0,0 +1,50 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl<'a> WingMessage<'a> for Point {
+    const NAME: &'static str = "Point";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub home: Option<Point>,
+    pub path: Vec<Option<Point>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+}
+
+impl<'a> WingMessage<'a> for Profile {
+    const NAME: &'static str = "Profile";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Move {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub to: Option<Point>,
+}
+
+impl<'a> WingMessage<'a> for Move {
+    const NAME: &'static str = "Move";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Update {
+    Rename(Option<String>),
+    Move(Move),
+}
+
+impl<'a> WingMessage<'a> for Update {
+    const NAME: &'static str = "Update";
+}
+
@@ -0,0 +1,49 @@
+from wing_rpc import Schema, Enum
+from wing_rpc.peer import Peer, CallStream, ReplyStream
+import shop.catalog
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Result(Schema):
+    __match_args__: ClassVar[tuple] = ('accepted', 'item',)
+    accepted: bool
+    item: shop.catalog.Item | None = None
+
+
+class Order(Schema):
+    __match_args__: ClassVar[tuple] = ('item', 'quantity',)
+    item: shop.catalog.Item
+    quantity: int
+
+
+class Message(Enum):
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    class Tag(StrEnum):
+        Order = 'Order'
+        Results = 'Results'
+    tag: Tag
+    value: Order | list[Result]
+
+
+class OrdersClient:
+    def __init__(self, peer: Peer):
+        self.peer = peer
+
+    def place(self, request: Order) -> Result:
+        return self.peer.call(request, Result)
+
+
+class Orders:
+    def place(self, request: Order) -> Result:
+        raise NotImplementedError
+
+    def serve(self, peer: Peer):
+        call = peer.next_call()
+        match call.name:
+            case 'Order':
+                peer.answer(call, Order, self.place)
+            case name:
+                peer.reply_error(call, f"Unknown request '{name}'")
+
+
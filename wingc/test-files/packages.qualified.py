@@ -0,0 +1,52 @@
+from wing_rpc import Schema, Enum
+from wing_rpc.peer import Peer, CallStream, ReplyStream
+import shop.catalog
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Result(Schema):
+    __wing_name__: ClassVar[str] = 'shop.orders.Result'
+    __match_args__: ClassVar[tuple] = ('accepted', 'item',)
+    accepted: bool
+    item: shop.catalog.Item | None = None
+
+
+class Order(Schema):
+    __wing_name__: ClassVar[str] = 'shop.orders.Order'
+    __match_args__: ClassVar[tuple] = ('item', 'quantity',)
+    item: shop.catalog.Item
+    quantity: int
+
+
+class Message(Enum):
+    __wing_name__: ClassVar[str] = 'shop.orders.Message'
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    class Tag(StrEnum):
+        Order = 'Order'
+        Results = 'Results'
+    tag: Tag
+    value: Order | list[Result]
+
+
+class OrdersClient:
+    def __init__(self, peer: Peer):
+        self.peer = peer
+
+    def place(self, request: Order) -> Result:
+        return self.peer.call(request, Result)
+
+
+class Orders:
+    def place(self, request: Order) -> Result:
+        raise NotImplementedError
+
+    def serve(self, peer: Peer):
+        call = peer.next_call()
+        match call.name:
+            case 'shop.orders.Order':
+                peer.answer(call, Order, self.place)
+            case name:
+                peer.reply_error(call, f"Unknown request '{name}'")
+
+
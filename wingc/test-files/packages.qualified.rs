@@ -0,0 +1,54 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Result {
+    pub accepted: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<crate::shop::catalog::Item>,
+}
+
+impl<'a> WingMessage<'a> for Result {
+    const NAME: &'static str = "shop.orders.Result";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Order {
+    pub item: crate::shop::catalog::Item,
+    pub quantity: u32,
+}
+
+impl<'a> WingMessage<'a> for Order {
+    const NAME: &'static str = "shop.orders.Order";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Message {
+    Order(Order),
+    Results(Vec<Result>),
+}
+
+impl<'a> WingMessage<'a> for Message {
+    const NAME: &'static str = "shop.orders.Message";
+}
+
+pub struct OrdersClient<'p>(pub &'p mut wing_rpc::Peer);
+
+impl OrdersClient<'_> {
+    pub fn place(&mut self, request: Order) -> wing_rpc::WingResult<Result> {
+        self.0.call(request)
+    }
+}
+
+pub trait Orders {
+    fn place(&mut self, request: Order) -> wing_rpc::WingResult<Result>;
+    fn serve(&mut self, peer: &mut wing_rpc::Peer) -> wing_rpc::WingResult<()> {
+        let call = peer.next_call()?;
+        match call.name() {
+            "shop.orders.Order" => peer.answer(&call, |request| self.place(request)),
+            name => peer.reply_error(&call, format!("Unknown request '{name}'")),
+        }
+    }
+}
+
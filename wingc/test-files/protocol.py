@@ -0,0 +1,49 @@
+from wing_rpc import Schema, Enum
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Join(Schema):
+    __match_args__: ClassVar[tuple] = ('room',)
+    room: str
+
+
+class Say(Schema):
+    __match_args__: ClassVar[tuple] = ('text',)
+    text: str
+
+
+class ClientMessage(Enum):
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    class Tag(StrEnum):
+        Join = 'Join'
+        Say = 'Say'
+        Leave = 'Leave'
+    tag: Tag
+    value: 'Join | Say | Leave'
+
+
+class Leave(Schema):
+    pass
+
+
+class Welcome(Schema):
+    __match_args__: ClassVar[tuple] = ('id',)
+    id: int
+
+
+class Said(Schema):
+    __match_args__: ClassVar[tuple] = ('sender', 'text',)
+    sender: int
+    text: str
+
+
+class ServerMessage(Enum):
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    class Tag(StrEnum):
+        Welcome = 'Welcome'
+        Said = 'Said'
+    tag: Tag
+    value: Welcome | Said
+
+
@@ -0,0 +1,73 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Join {
+    pub room: String,
+}
+
+impl<'a> WingMessage<'a> for Join {
+    const NAME: &'static str = "Join";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Say {
+    pub text: String,
+}
+
+impl<'a> WingMessage<'a> for Say {
+    const NAME: &'static str = "Say";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ClientMessage {
+    Join(Join),
+    Say(Say),
+    Leave(Leave),
+}
+
+impl<'a> WingMessage<'a> for ClientMessage {
+    const NAME: &'static str = "ClientMessage";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Leave {
+}
+
+impl<'a> WingMessage<'a> for Leave {
+    const NAME: &'static str = "Leave";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Welcome {
+    pub id: u32,
+}
+
+impl<'a> WingMessage<'a> for Welcome {
+    const NAME: &'static str = "Welcome";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Said {
+    pub sender: u32,
+    pub text: String,
+}
+
+impl<'a> WingMessage<'a> for Said {
+    const NAME: &'static str = "Said";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ServerMessage {
+    Welcome(Welcome),
+    Said(Said),
+}
+
+impl<'a> WingMessage<'a> for ServerMessage {
+    const NAME: &'static str = "ServerMessage";
+}
+
+pub type ChatClientPeer = wing_rpc::TypedPeer<ServerMessage, ClientMessage>;
+pub type ChatServerPeer = wing_rpc::TypedPeer<ClientMessage, ServerMessage>;
+
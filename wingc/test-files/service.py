@@ -0,0 +1,51 @@
+from wing_rpc import Schema, Enum
+from wing_rpc.peer import Peer, CallStream, ReplyStream
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Query(Schema):
+    __match_args__: ClassVar[tuple] = ('text',)
+    text: str
+
+
+class ById(Schema):
+    __match_args__: ClassVar[tuple] = ('id',)
+    id: int
+
+
+class Item(Schema):
+    __match_args__: ClassVar[tuple] = ('id', 'title',)
+    id: int
+    title: str
+
+
+class CatalogClient:
+    def __init__(self, peer: Peer):
+        self.peer = peer
+
+    def get(self, request: ById) -> Item:
+        return self.peer.call(request, Item)
+
+    def search(self, request: Query) -> CallStream[Item]:
+        return self.peer.call_stream(request, Item)
+
+
+class Catalog:
+    def get(self, request: ById) -> Item:
+        raise NotImplementedError
+
+    def search(self, request: Query, reply: ReplyStream[Item]):
+        raise NotImplementedError
+
+    def serve(self, peer: Peer):
+        call = peer.next_call()
+        match call.name:
+            case 'ById':
+                peer.answer(call, ById, self.get)
+            case 'Query':
+                peer.answer_stream(call, Query, self.search)
+            case name:
+                peer.reply_error(call, f"Unknown request '{name}'")
+
+
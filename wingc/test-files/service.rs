@@ -0,0 +1,56 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Query {
+    pub text: String,
+}
+
+impl<'a> WingMessage<'a> for Query {
+    const NAME: &'static str = "Query";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ById {
+    pub id: u64,
+}
+
+impl<'a> WingMessage<'a> for ById {
+    const NAME: &'static str = "ById";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Item {
+    pub id: u64,
+    pub title: String,
+}
+
+impl<'a> WingMessage<'a> for Item {
+    const NAME: &'static str = "Item";
+}
+
+pub struct CatalogClient<'p>(pub &'p mut wing_rpc::Peer);
+
+impl CatalogClient<'_> {
+    pub fn get(&mut self, request: ById) -> wing_rpc::WingResult<Item> {
+        self.0.call(request)
+    }
+    pub fn search(&mut self, request: Query) -> wing_rpc::WingResult<wing_rpc::call::CallStream<'_, Item>> {
+        self.0.call_stream(request)
+    }
+}
+
+pub trait Catalog {
+    fn get(&mut self, request: ById) -> wing_rpc::WingResult<Item>;
+    fn search(&mut self, request: Query, reply: &mut wing_rpc::call::ReplyStream<'_, Item>) -> wing_rpc::WingResult<()>;
+    fn serve(&mut self, peer: &mut wing_rpc::Peer) -> wing_rpc::WingResult<()> {
+        let call = peer.next_call()?;
+        match call.name() {
+            "ById" => peer.answer(&call, |request| self.get(request)),
+            "Query" => peer.answer_stream(&call, |request, reply| self.search(request, reply)),
+            name => peer.reply_error(&call, format!("Unknown request '{name}'")),
+        }
+    }
+}
+
@@ -0,0 +1,36 @@
+from wing_rpc import Schema, Enum
+from typing import ClassVar
+from enum import StrEnum
+
+
+class Theme(StrEnum):
+    """How the interface is drawn."""
+    Light = 'light'
+    Dark = 'dark'
+    """Easier on the eyes at night."""
+    System = 'System'
+
+
+class Settings(Schema):
+    __match_args__: ClassVar[tuple] = ('theme', 'fallback',)
+    theme: Theme
+    fallback: Theme | None = None
+
+
+class Apply(Schema):
+    __match_args__: ClassVar[tuple] = ('settings',)
+    settings: Settings
+
+
+class Command(Enum):
+    __match_args__: ClassVar[tuple] = ('tag', 'value',)
+    __units__: ClassVar[tuple] = ('reload', 'Quit',)
+    class Tag(StrEnum):
+        Open = 'Open'
+        Apply = 'Apply'
+        Reload = 'reload'
+        Quit = 'Quit'
+    tag: Tag
+    value: str | Apply | None = None
+
+
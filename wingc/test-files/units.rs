@@ -0,0 +1,52 @@
+use serde::{Serialize, Deserialize};
+use wing_rpc::Message as WingMessage;
+
+
+/// How the interface is drawn.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Theme {
+    #[serde(rename = "light")]
+    Light,
+    /// Easier on the eyes at night.
+    #[serde(rename = "dark")]
+    Dark,
+    System,
+}
+
+impl<'a> WingMessage<'a> for Theme {
+    const NAME: &'static str = "Theme";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub theme: Theme,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fallback: Option<Theme>,
+}
+
+impl<'a> WingMessage<'a> for Settings {
+    const NAME: &'static str = "Settings";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Apply {
+    pub settings: Settings,
+}
+
+impl<'a> WingMessage<'a> for Apply {
+    const NAME: &'static str = "Apply";
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Command {
+    Open(String),
+    Apply(Apply),
+    #[serde(rename = "reload")]
+    Reload,
+    Quit,
+}
+
+impl<'a> WingMessage<'a> for Command {
+    const NAME: &'static str = "Command";
+}
+
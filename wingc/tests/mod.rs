@@ -1,8 +1,20 @@
 use wingc::utils::{test, Mode};
 
 #[test]
-fn simple(){
-    test("simple", Mode::Test);
+fn newtypes(){
+    test("newtypes", Mode::Test);
+}
+
+
+#[test]
+fn docs(){
+    test("docs", Mode::Test);
+}
+
+
+#[test]
+fn generics(){
+    test("generics", Mode::Test);
 }
 
 
@@ -13,8 +25,38 @@ fn nested(){
 
 
 #[test]
-fn composite_simple(){
-    test("composite-simple", Mode::Test);
+fn units(){
+    test("units", Mode::Test);
+}
+
+
+#[test]
+fn defaults(){
+    test("defaults", Mode::Test);
+}
+
+
+#[test]
+fn protocol(){
+    test("protocol", Mode::Test);
+}
+
+
+#[test]
+fn colors(){
+    test("colors", Mode::Test);
+}
+
+
+#[test]
+fn simple(){
+    test("simple", Mode::Test);
+}
+
+
+#[test]
+fn optional(){
+    test("optional", Mode::Test);
 }
 
 
@@ -22,3 +64,45 @@ fn composite_simple(){
 fn composite(){
     test("composite", Mode::Test);
 }
+
+
+#[test]
+fn composite_simple(){
+    test("composite-simple", Mode::Test);
+}
+
+
+#[test]
+fn attributes(){
+    test("attributes", Mode::Test);
+}
+
+
+#[test]
+fn imports(){
+    test("imports", Mode::Test);
+}
+
+
+#[test]
+fn fd(){
+    test("fd", Mode::Test);
+}
+
+
+#[test]
+fn service(){
+    test("service", Mode::Test);
+}
+
+
+#[test]
+fn maps(){
+    test("maps", Mode::Test);
+}
+
+
+#[test]
+fn packages(){
+    test("packages", Mode::Test);
+}